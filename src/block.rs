@@ -0,0 +1,219 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use primitive_types::{H160, H256, U256};
+use crate::error::exit::ExitError;
+use crate::globalState::AccountState;
+use crate::receipt::Receipt;
+use crate::result::ExecutionResult;
+use crate::tracer::getAccountState::AccountStateEx;
+
+/// 区块中一笔成功执行(包括revert)的交易的执行结果以及收据
+#[derive(Debug, Clone)]
+pub struct TxOutcome {
+    pub result: ExecutionResult,
+    pub receipt: Receipt,
+}
+
+/// 区块的执行结果
+#[derive(Debug)]
+pub struct BlockResult {
+    /// 按照交易顺序排列，校验失败的交易为对应的错误，不产生收据也不修改状态
+    pub transactions: Vec<Result<TxOutcome, Box<dyn ExitError>>>,
+    /// 区块中所有交易累计消耗的gas
+    pub gas_used: u64,
+    /// 执行完全部交易之后的全局状态
+    pub post_state: HashMap<H160, AccountState>,
+}
+
+impl BlockResult {
+    /// 按照交易顺序返回所有成功执行的交易的收据
+    pub fn receipts(&self) -> Vec<&Receipt> {
+        self.transactions.iter().filter_map(|outcome| outcome.as_ref().ok()).map(|outcome| &outcome.receipt).collect()
+    }
+}
+
+/// 复现链上交易时本地执行后的状态与节点给出的post state之间的差异
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StateMismatch {
+    Balance { address: H160, expected: U256, actual: U256 },
+    Nonce { address: H160, expected: usize, actual: usize },
+    Code { address: H160 },
+    Storage { address: H160, slot: H256, expected: H256, actual: H256 },
+}
+
+impl fmt::Display for StateMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateMismatch::Balance { address, expected, actual } => {
+                write!(f, "balance of {:?} mismatch: expected {}, actual {}", address, expected, actual)
+            }
+            StateMismatch::Nonce { address, expected, actual } => {
+                write!(f, "nonce of {:?} mismatch: expected {}, actual {}", address, expected, actual)
+            }
+            StateMismatch::Code { address } => write!(f, "code of {:?} mismatch", address),
+            StateMismatch::Storage { address, slot, expected, actual } => {
+                write!(f, "storage {:?} of {:?} mismatch: expected {:?}, actual {:?}", slot, address, expected, actual)
+            }
+        }
+    }
+}
+
+/// 将本地的全局状态与节点给出的交易执行后的账户状态逐一比较
+/// 本地不存在的账户视为空账户，不存在的存储槽视为0，只比较节点给出的账户以及存储槽
+pub fn compare_post_state(state: &HashMap<H160, AccountState>, expected: &BTreeMap<H160, AccountStateEx>) -> Vec<StateMismatch> {
+    let mut mismatches = Vec::new();
+    for (address, expected_account) in expected {
+        let address = *address;
+        let account = state.get(&address);
+        let balance = account.map_or(U256::zero(), |account| account.balance);
+        if balance != expected_account.balance {
+            mismatches.push(StateMismatch::Balance { address, expected: expected_account.balance, actual: balance });
+        }
+        let nonce = account.map_or(0, |account| account.nonce);
+        if nonce != expected_account.nonce {
+            mismatches.push(StateMismatch::Nonce { address, expected: expected_account.nonce, actual: nonce });
+        }
+        let code = account.and_then(|account| account.code.clone()).unwrap_or_default();
+        if code != expected_account.code.clone().unwrap_or_default() {
+            mismatches.push(StateMismatch::Code { address });
+        }
+        let storage = account.and_then(|account| account.storage.as_ref());
+        for (slot, expected_value) in expected_account.storage.iter().flatten() {
+            let value = storage.and_then(|storage| storage.get(slot)).copied().unwrap_or_default();
+            if value != *expected_value {
+                mismatches.push(StateMismatch::Storage { address, slot: *slot, expected: *expected_value, actual: value });
+            }
+        }
+    }
+    mismatches
+}
+
+/// 复现的区块中一笔交易的执行结果以及与链上post state的差异
+#[derive(Debug)]
+pub struct TxReplay {
+    pub tx_hash: H256,
+    pub outcome: Result<TxOutcome, Box<dyn ExitError>>,
+    pub mismatches: Vec<StateMismatch>,
+}
+
+/// 复现链上区块的结果，按照交易在区块中的顺序排列
+#[derive(Debug)]
+pub struct BlockReplay {
+    pub transactions: Vec<TxReplay>,
+}
+
+impl BlockReplay {
+    /// 所有交易均执行成功，并且执行后的状态与链上一致
+    pub fn is_consistent(&self) -> bool {
+        self.transactions.iter().all(|tx| tx.outcome.is_ok() && tx.mismatches.is_empty())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::types::Bytes;
+    use crate::tracer::getTransaction::StateTracerType;
+    use crate::evm::EVM;
+    use crate::execute_block;
+    use crate::globalState::{Block, WorldState};
+    use crate::transaction::{LegacyTx, Transaction, TxEnv};
+
+    #[test]
+    fn test_execute_block() {
+        let caller = H160::repeat_byte(0x01);
+        let coinbase = H160::repeat_byte(0xcb);
+        let mut world_state = WorldState::default();
+        world_state.new_account(caller, AccountState::new_eoa(0, U256::from(1_000_000_000)));
+        let mut evm = EVM::new(world_state);
+        let block = Block {
+            blockhash: H256::zero(),
+            coinbase,
+            timestamp: 0,
+            number: 1,
+            prevrandao: H256::zero(),
+            gas_limit: U256::from(250_000),
+            chainid: 1,
+            basefee: 10,
+            excess_blob_gas: Some(0),
+        };
+        let tx = |nonce: u64, to: Option<H160>, data: &str, gas_limit: u64| TxEnv {
+            caller,
+            transaction: Transaction::Legacy(LegacyTx {
+                nonce,
+                to,
+                data: data.parse().unwrap(),
+                gas_limit,
+                gas_price: U256::from(15),
+                ..Default::default()
+            }),
+        };
+        // 部署runtime code为 PUSH1 1 PUSH1 0 SSTORE STOP 的合约
+        let deploy = tx(0, None, "0x656001600055006000526006601af3", 100_000);
+        let contract = deploy.create_address().unwrap();
+        let transactions = vec![
+            deploy,
+            // 调用上一笔交易部署的合约
+            tx(1, Some(contract), "0x", 100_000),
+            // nonce不连续的交易被跳过
+            tx(5, Some(contract), "0x", 100_000),
+            tx(2, Some(H160::repeat_byte(0x02)), "0x", 100_000),
+            // 超出区块剩余的gas
+            tx(3, Some(H160::repeat_byte(0x02)), "0x", 200_000),
+        ];
+        let result = execute_block(&mut evm, block, transactions);
+
+        assert_eq!(result.transactions.len(), 5);
+        assert!(result.transactions[2].is_err());
+        assert!(result.transactions[4].is_err());
+        let receipts = result.receipts();
+        assert_eq!(receipts.len(), 3);
+        assert!(receipts.iter().all(|receipt| receipt.status));
+        assert_eq!(receipts[0].contract_address, Some(contract));
+        assert_eq!(receipts[1].contract_address, None);
+        assert_eq!(receipts[1].cumulative_gas_used, receipts[0].gas_used + receipts[1].gas_used);
+        assert_eq!(receipts[2].gas_used, 21000);
+        assert_eq!(result.gas_used, receipts[2].cumulative_gas_used);
+
+        // 执行后的状态包含每笔交易的修改
+        let storage = result.post_state[&contract].storage.clone().unwrap();
+        assert_eq!(storage[&H256::zero()], H256::from_low_u64_be(1));
+        assert_eq!(result.post_state[&caller].nonce, 3);
+        assert_eq!(result.post_state[&coinbase].balance, U256::from(result.gas_used * 5));
+    }
+
+    #[test]
+    fn test_compare_post_state() {
+        let user = H160::repeat_byte(0x01);
+        let contract = H160::repeat_byte(0x02);
+        let missing = H160::repeat_byte(0x03);
+        let slot = H256::from_low_u64_be;
+        let mut state = HashMap::new();
+        state.insert(user, AccountState::new_eoa(1, U256::from(100)));
+        state.insert(contract, AccountState::new_contract(1, U256::zero(), H256::zero(), BTreeMap::from([(slot(1), slot(1))]), Bytes::from(vec![0x00])));
+        let expected_account = |nonce: usize, balance: u64, storage: Option<BTreeMap<H256, H256>>, code: Option<Bytes>| AccountStateEx {
+            nonce,
+            balance: U256::from(balance),
+            storage,
+            code_hash: None,
+            code,
+            state_tracer_type: StateTracerType::TurnOnDiffPost,
+        };
+        let mut expected = BTreeMap::from([
+            (user, expected_account(1, 100, None, None)),
+            (contract, expected_account(1, 0, Some(BTreeMap::from([(slot(1), slot(1)), (slot(2), H256::zero())])), Some(Bytes::from(vec![0x00])))),
+            // 被删除的账户与本地不存在的账户一致
+            (missing, expected_account(0, 0, Some(BTreeMap::new()), None)),
+        ]);
+        assert!(compare_post_state(&state, &expected).is_empty());
+
+        expected.insert(user, expected_account(2, 90, None, None));
+        expected.insert(contract, expected_account(1, 0, Some(BTreeMap::from([(slot(2), slot(3))])), None));
+        assert_eq!(compare_post_state(&state, &expected), vec![
+            StateMismatch::Balance { address: user, expected: U256::from(90), actual: U256::from(100) },
+            StateMismatch::Nonce { address: user, expected: 2, actual: 1 },
+            StateMismatch::Code { address: contract },
+            StateMismatch::Storage { address: contract, slot: slot(2), expected: slot(3), actual: H256::zero() },
+        ]);
+    }
+}
@@ -6,7 +6,12 @@
     /// todo!:明天要做的第一件事，将错误分开，最好使用特征来分开处理各类型错误
 
     /// 错误特征，便于统一处理不同错误类型
-    pub trait ExitError: Display + Debug {}
+    pub trait ExitError: Display + Debug {
+        /// 如果该错误属于字节码执行错误，则返回对应的OpcodeExecutionError
+        fn as_opcode_error(&self) -> Option<OpcodeExecutionError> {
+            None
+        }
+    }
     /// 字节码执行错误
     #[derive(Clone, Debug, Eq, PartialEq)]
     #[cfg_attr(
@@ -65,6 +70,12 @@
         DivisionByZero,
         /// 未实现的操作码
         NotImplemented(u8),
+        /// 在STATICCALL中尝试修改状态
+        StaticStateChange,
+        /// EIP-3541: 部署的runtime code以0xEF开头
+        CreateContractStartingWithEF,
+        /// 其他导致执行中止的错误
+        Other(String),
     }
 
     impl Display for OpcodeExecutionError {
@@ -120,12 +131,25 @@
                 },
                 OpcodeExecutionError::NotImplemented(_) => {
                     write!(f, "Not implemented")
+                },
+                OpcodeExecutionError::StaticStateChange => {
+                    write!(f, "State change during static call")
+                },
+                OpcodeExecutionError::CreateContractStartingWithEF => {
+                    write!(f, "Create contract starting with EF")
+                },
+                OpcodeExecutionError::Other(reason) => {
+                    write!(f, "{}", reason)
                 }
             }
         }
     }
 
-    impl ExitError for OpcodeExecutionError {}
+    impl ExitError for OpcodeExecutionError {
+        fn as_opcode_error(&self) -> Option<OpcodeExecutionError> {
+            Some(self.clone())
+        }
+    }
 
 
     /// 其他错误类型
@@ -146,6 +170,8 @@
         StorageNotExist(H256),
         /// 部署合约失败
         DeployContractFailed,
+        /// 从节点获取链上数据失败
+        Provider(String),
         /// 通用执行错误
         Error,
     }
@@ -168,6 +194,9 @@
                 EVMError::DeployContractFailed => {
                     write!(f, "Deploy contract failed")
                 },
+                EVMError::Provider(reason) => {
+                    write!(f, "Provider error: {}", reason)
+                },
                 EVMError::Error => {
                     write!(f, "EVM execution error")
                 }
@@ -175,7 +204,176 @@
         }
     }
 
-    impl ExitError for EVMError {}
+    impl ExitError for EVMError {
+        fn as_opcode_error(&self) -> Option<OpcodeExecutionError> {
+            match self {
+                EVMError::InvalidRange => Some(OpcodeExecutionError::InvalidRange),
+                _ => None,
+            }
+        }
+    }
+
+
+    /// 交易校验失败，交易不会被执行，也不会修改任何状态
+    #[derive(Clone, Debug, Eq, PartialEq)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub enum InvalidTransaction {
+        /// 交易的nonce小于发送者当前的nonce
+        NonceTooLow { tx: u64, state: u64 },
+        /// 交易的nonce大于发送者当前的nonce
+        NonceTooHigh { tx: u64, state: u64 },
+        /// EIP-3607: 发送者为合约账户
+        RejectCallerWithCode,
+        /// 发送者余额不足以支付 gas_limit * gas_price + value
+        LackOfFundForMaxFee { fee: U256, balance: U256 },
+        /// 交易的gas上限不足以支付固有gas
+        CallGasCostMoreThanGasLimit { intrinsic: u64, gas_limit: u64 },
+        /// 交易的gas上限超出区块的gas上限
+        CallerGasLimitMoreThanBlock,
+        /// EIP-1559: gas价格低于区块的base fee
+        GasPriceLessThanBasefee,
+        /// EIP-1559: 小费上限大于gas价格上限
+        PriorityFeeGreaterThanMaxFee,
+        /// EIP-4844: blob gas价格上限低于区块的blob base fee
+        BlobGasPriceGreaterThanMax,
+        /// EIP-3860: 创建合约交易的init code超出长度上限
+        CreateInitCodeSizeLimit,
+        /// 当前硬分叉尚未引入该交易类型
+        TxTypeNotSupported,
+        /// 交易的chain id与区块不一致
+        InvalidChainId,
+        /// EIP-4844: blob交易至少携带一个blob
+        EmptyBlobs,
+        /// EIP-4844: blob数量超出区块上限
+        TooManyBlobs { max: usize, have: usize },
+        /// EIP-4844: versioned hash的版本号不是0x01
+        BlobVersionNotSupported,
+        /// EIP-7702: 授权列表不能为空
+        EmptyAuthorizationList,
+        /// 原始交易的RLP编码无效
+        InvalidRlp(String),
+        /// 无法根据签名恢复交易的发送者
+        InvalidSignature,
+    }
+
+    impl Display for InvalidTransaction {
+        fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+            match self {
+                InvalidTransaction::NonceTooLow { tx, state } => {
+                    write!(f, "Nonce too low: tx {}, state {}", tx, state)
+                },
+                InvalidTransaction::NonceTooHigh { tx, state } => {
+                    write!(f, "Nonce too high: tx {}, state {}", tx, state)
+                },
+                InvalidTransaction::RejectCallerWithCode => {
+                    write!(f, "Reject transactions from senders with deployed code")
+                },
+                InvalidTransaction::LackOfFundForMaxFee { fee, balance } => {
+                    write!(f, "Lack of funds ({}) for max fee ({})", balance, fee)
+                },
+                InvalidTransaction::CallGasCostMoreThanGasLimit { intrinsic, gas_limit } => {
+                    write!(f, "Intrinsic gas {} is more than gas limit {}", intrinsic, gas_limit)
+                },
+                InvalidTransaction::CallerGasLimitMoreThanBlock => {
+                    write!(f, "Gas limit is more than block gas limit")
+                },
+                InvalidTransaction::GasPriceLessThanBasefee => {
+                    write!(f, "Gas price is less than basefee")
+                },
+                InvalidTransaction::PriorityFeeGreaterThanMaxFee => {
+                    write!(f, "Priority fee is greater than max fee")
+                },
+                InvalidTransaction::BlobGasPriceGreaterThanMax => {
+                    write!(f, "Blob gas price is greater than max fee per blob gas")
+                },
+                InvalidTransaction::CreateInitCodeSizeLimit => {
+                    write!(f, "Create initcode size limit")
+                },
+                InvalidTransaction::TxTypeNotSupported => {
+                    write!(f, "Transaction type is not supported")
+                },
+                InvalidTransaction::InvalidChainId => {
+                    write!(f, "Invalid chain id")
+                },
+                InvalidTransaction::EmptyBlobs => {
+                    write!(f, "Blob transaction has no blobs")
+                },
+                InvalidTransaction::TooManyBlobs { max, have } => {
+                    write!(f, "Too many blobs: max {}, have {}", max, have)
+                },
+                InvalidTransaction::BlobVersionNotSupported => {
+                    write!(f, "Blob version is not supported")
+                },
+                InvalidTransaction::EmptyAuthorizationList => {
+                    write!(f, "Authorization list is empty")
+                },
+                InvalidTransaction::InvalidRlp(reason) => {
+                    write!(f, "Invalid transaction rlp: {}", reason)
+                },
+                InvalidTransaction::InvalidSignature => {
+                    write!(f, "Invalid transaction signature")
+                }
+            }
+        }
+    }
+
+    impl ExitError for InvalidTransaction {}
+
+
+    /// 预编译合约执行错误，出现错误时转发给预编译合约的gas全部被消耗
+    #[derive(Clone, Debug, Eq, PartialEq)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub enum PrecompileError {
+        /// gas不足以支付预编译合约的费用
+        OutOfGas,
+        /// 输入长度不符合要求
+        InvalidInputLength,
+        /// bn128: 坐标不是有效的域元素
+        Bn128FieldPointNotAMember,
+        /// bn128: 点不在曲线上
+        Bn128AffinePointCreationFailed,
+        /// blake2f: final标志只能为0或1
+        Blake2WrongFinalIndicatorFlag,
+        /// point evaluation: versioned hash与commitment不匹配
+        BlobMismatchedVersion,
+        /// point evaluation: kzg证明验证失败
+        BlobVerifyKzgProofFailed,
+        /// 其他错误
+        Other(String),
+    }
+
+    impl Display for PrecompileError {
+        fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+            match self {
+                PrecompileError::OutOfGas => {
+                    write!(f, "Precompile out of gas")
+                },
+                PrecompileError::InvalidInputLength => {
+                    write!(f, "Invalid input length")
+                },
+                PrecompileError::Bn128FieldPointNotAMember => {
+                    write!(f, "Field point not a member of bn128 curve")
+                },
+                PrecompileError::Bn128AffinePointCreationFailed => {
+                    write!(f, "Failed to create bn128 affine point")
+                },
+                PrecompileError::Blake2WrongFinalIndicatorFlag => {
+                    write!(f, "Wrong blake2 final indicator flag")
+                },
+                PrecompileError::BlobMismatchedVersion => {
+                    write!(f, "Mismatched blob version")
+                },
+                PrecompileError::BlobVerifyKzgProofFailed => {
+                    write!(f, "Verifying kzg proof failed")
+                },
+                PrecompileError::Other(reason) => {
+                    write!(f, "Precompile error: {}", reason)
+                }
+            }
+        }
+    }
+
+    impl ExitError for PrecompileError {}
 
 
     #[derive(Clone, Debug, Eq, PartialEq)]
@@ -208,7 +406,15 @@
             }
         }
     }
-    impl ExitError for StackError {}
+    impl ExitError for StackError {
+        fn as_opcode_error(&self) -> Option<OpcodeExecutionError> {
+            match self {
+                StackError::InvalidRange => Some(OpcodeExecutionError::InvalidRange),
+                StackError::StackOverflow => Some(OpcodeExecutionError::StackOverflow),
+                StackError::StackUnderflow => Some(OpcodeExecutionError::StackUnderflow),
+            }
+        }
+    }
 
 
 
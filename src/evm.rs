@@ -1,32 +1,43 @@
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::{fmt, process};
 use std::hash::Hash;
+use std::sync::Arc;
 use std::string::FromUtf8Error;
 use ethers::utils::keccak256 as ethers_keccak256;
 use revm_primitives::Address;
-use ethers::types::{Selector, Bytes, Transaction};
+use ethers::types::{Selector, Bytes};
+use ethers::types::transaction::eip2930::AccessList;
 use primitive_types::{U256, H160, H256};
 use crate::AccountState;
 use crate::error::exit::*;
 use crate::machine::Stack::Stack;
 use crate::machine::Memory::Memory;
+use crate::machine::JumpDest::JumpDestMap;
+use crate::machine::Gas::{memory_expansion_cost, Gas, CODEDEPOSIT, COLD_ACCOUNT_ACCESS, WARM_STORAGE_READ};
+use crate::spec::SpecId;
+use crate::precompile::{Precompile, Precompiles};
 use crate::globalState::{WorldState, Block, CallType};
 use crate::globalState::Call;
+use crate::journal::{JournalCheckpoint, JournalEntry};
 use crate::opcode::{flow::*, account::*, arithmatic::*, bitewise::*, comparison::*, enviroment::*, flow::*, structure::*};
 use crate::opcode::opcode::Opcode;
-use crate::utils::{address_to_h160, increment_nonce, map_op, u256_to_h160, vec_to_string, vec_to_u256};
+use crate::opcode::enviroment::Log;
+use crate::transaction::{delegation_address, Transaction};
+use crate::utils::{address_to_h160, map_op, u256_to_h160, vec_to_string, vec_to_u256};
 
 #[derive(Debug, Clone)]
 pub struct EVM {
     ///数据结构
     pub stack: Stack,
     pub memory: Memory,
+    pub gas: Gas,
     pub transient_storage: HashMap<H160, BTreeMap<H256, H256>>,
 
     ///出现外部调用时进行压栈
     pub call_stack: Vec<Call>,
     pub memory_stack: Vec<Memory>,
     pub evm_stack: Vec<Stack>,
+    pub gas_stack: Vec<Gas>,
     pub function_stack: Vec<(H160, Selector)>,
 
     ///全局信息
@@ -38,16 +49,41 @@ pub struct EVM {
 
     ///交易发起者
     pub origin: H160,
+    ///当前执行所使用的硬分叉，决定可用的操作码、gas费用以及执行规则
+    pub spec_id: SpecId,
+    ///最外层调用可使用的gas上限
+    pub gas_limit: u64,
+    ///EIP-2930: 交易携带的访问列表
+    pub access_list: AccessList,
+    ///EIP-2929: 当前交易中已经访问过(warm)的地址以及存储槽
+    pub accessed_addresses: HashSet<H160>,
+    pub accessed_storage_keys: HashSet<(H160, H256)>,
+    ///EIP-2200: 存储槽在当前交易开始前的原始值，在交易中第一次写入该存储槽时记录
+    pub original_storage: HashMap<(H160, H256), H256>,
+    ///用户注册的自定义预编译合约，与当前硬分叉的标准预编译合约地址相同时覆盖标准预编译合约
+    custom_precompiles: Precompiles,
+    ///当前可用的预编译合约，只在硬分叉或者自定义预编译合约发生变化时重新构建
+    precompiles: Precompiles,
+    ///precompiles构建时所使用的硬分叉
+    precompiles_spec: SpecId,
 
     // 只要出现call，则下面的信息不断更新，这些都代表着一笔内部交易
     pub bytecode: Option<Bytes>,        // bytecode一定是to地址的code
-    pub before_world_state: WorldState, // 万一revert
     pub return_data: Option<Vec<u8>>, // returndata实际上和memory很像，就类似于一个很长的u8数组
     pub sub_return_data: Option<Vec<u8>>,
+    ///当前执行的bytecode中合法的跳转目标
+    pub jumpdests: Arc<JumpDestMap>,
+    ///按照code hash缓存的跳转目标分析结果，相同的bytecode只分析一次
+    pub jumpdest_cache: HashMap<H256, Arc<JumpDestMap>>,
+    ///当前交易产生的日志
+    pub logs: Vec<Log>,
 
     // 交易复现使用
     pub block: Option<Block>,
+    ///当前执行的交易，GASPRICE等操作码从中读取交易信息
     pub transaction: Option<Transaction>,
+    ///EIP-4844: 当前交易携带的blob versioned hashes
+    pub blob_hashes: Vec<H256>,
 }
 
 impl EVM {
@@ -55,13 +91,24 @@ impl EVM {
         Self {
             sub_return_data: None,
             origin: H160::zero(),
+            spec_id: SpecId::default(),
             transient_storage: HashMap::new(),
             is_revert: false,
             stack: Stack::new(1024),
             memory: Memory::new(1024),
+            gas: Gas::new(30_000_000),
             call_stack: Vec::new(),
             memory_stack: Vec::new(),
             evm_stack: Vec::new(),
+            gas_stack: Vec::new(),
+            gas_limit: 30_000_000,
+            access_list: AccessList::default(),
+            accessed_addresses: HashSet::new(),
+            accessed_storage_keys: HashSet::new(),
+            original_storage: HashMap::new(),
+            custom_precompiles: Precompiles::default(),
+            precompiles: Precompiles::new(SpecId::default()),
+            precompiles_spec: SpecId::default(),
             call_depth: 0,
             function_stack: Vec::<(H160, Selector)>::new(),
             pc: 0,
@@ -69,8 +116,11 @@ impl EVM {
             world_state,
             block: None,
             transaction: None,
-            before_world_state: WorldState::default(),
+            blob_hashes: Vec::new(),
             return_data: None,
+            jumpdests: Arc::default(),
+            jumpdest_cache: HashMap::new(),
+            logs: Vec::new(),
             // 是否是部署合约交易
             is_constructor: false,
         }
@@ -82,26 +132,209 @@ impl EVM {
         match self.bytecode.clone() {
             None => {}
             Some(code) => {
+                // 子调用返回后恢复当前调用帧的跳转目标
+                let jumpdests = self.analyze_jumpdests(&code);
+                let parent_jumpdests = std::mem::replace(&mut self.jumpdests, jumpdests);
                 while self.pc < code.len() {
-                    // 如果当前字节码存在于opcode表，即从对应的opcode表获取其对应的操作码，否则默认为INVALID
-                    let op = map_op(code[self.pc]).unwrap_or_else(|| Opcode::INVALID);
-                    println!("current op is {}: {}",self.pc, op);
-                    println!("stack is {}", self.stack);
-                    println!("memory is {}", self.memory);
-                    match self.interepter_op_code(op) {
-                        Ok(_) => {}
-                        Err(_) => {
-                            self.pc += 1;
-                        }
-                    };
+                    // 如果当前字节码存在于当前硬分叉的opcode表，即从对应的opcode表获取其对应的操作码，否则默认为INVALID
+                    let op = map_op(code[self.pc], self.spec_id).unwrap_or(Opcode::INVALID);
+                    // 先收取操作码的基础gas，再执行操作码
+                    let result = self.gas.record_cost(op.gas_cost(self.spec_id)).and_then(|_| self.interepter_op_code(op));
+                    if let Err(e) = result {
+                        // 执行出现任何错误时当前调用帧立即中止，并消耗掉全部剩余gas
+                        self.gas.spend_all();
+                        self.pc = 0;
+                        self.jumpdests = parent_jumpdests;
+                        return Err(e);
+                    }
                 }
                 self.pc = 0;
+                self.jumpdests = parent_jumpdests;
             }
         }
         Ok(())
 
     }
 
+    /// 获取bytecode的跳转目标分析结果，优先使用缓存
+    pub fn analyze_jumpdests(&mut self, code: &Bytes) -> Arc<JumpDestMap> {
+        let code_hash = H256::from(ethers_keccak256(code));
+        self.jumpdest_cache
+            .entry(code_hash)
+            .or_insert_with(|| Arc::new(JumpDestMap::analyze(code)))
+            .clone()
+    }
+
+    /// EIP-2929: 交易开始时重置warm集合，预热交易发起者、接收者、预编译合约以及访问列表中的地址和存储槽
+    pub fn warm_transaction_accesses(&mut self, to: Option<H160>) {
+        self.refresh_precompiles();
+        self.accessed_addresses = HashSet::new();
+        self.accessed_storage_keys = HashSet::new();
+        self.accessed_addresses.insert(self.origin);
+        if let Some(to) = to {
+            self.accessed_addresses.insert(to);
+        }
+        self.accessed_addresses.extend(self.precompiles.addresses());
+        // EIP-3651: Shanghai开始coinbase地址在交易开始时即为warm
+        if self.spec_id.is_enabled_in(SpecId::Shanghai) {
+            if let Some(block) = &self.block {
+                self.accessed_addresses.insert(block.coinbase);
+            }
+        }
+        for item in self.access_list.0.clone() {
+            self.accessed_addresses.insert(item.address);
+            for key in item.storage_keys {
+                self.accessed_storage_keys.insert((item.address, key));
+            }
+        }
+    }
+
+    /// 调用帧开始时记录状态修改日志的位置
+    pub fn checkpoint(&self) -> JournalCheckpoint {
+        self.world_state.checkpoint()
+    }
+
+    /// 调用帧revert或异常中止时，撤销checkpoint之后对全局状态以及瞬态存储的全部修改
+    pub fn revert_to(&mut self, checkpoint: JournalCheckpoint) {
+        for entry in self.world_state.take_journal(checkpoint).into_iter().rev() {
+            entry.revert(
+                &mut self.world_state,
+                &mut self.transient_storage,
+                &mut self.logs,
+                &mut self.accessed_addresses,
+                &mut self.accessed_storage_keys,
+            );
+        }
+    }
+
+    /// EIP-1153: 写入瞬态存储并记录到状态修改日志中
+    pub fn set_transient_storage(&mut self, address: H160, key: H256, value: H256) {
+        let previous = self.transient_storage.entry(address).or_default().insert(key, value);
+        self.world_state.journal.push(JournalEntry::TransientStorageChange { address, key, previous });
+    }
+
+    /// 记录日志，调用帧回滚时一并撤销
+    pub fn push_log(&mut self, log: Log) {
+        self.logs.push(log);
+        self.world_state.journal.push(JournalEntry::Log);
+    }
+
+    /// 注册自定义预编译合约
+    pub fn register_precompile<P: Precompile + 'static>(&mut self, address: H160, precompile: P) {
+        self.custom_precompiles.register(address, precompile);
+        self.rebuild_precompiles();
+    }
+
+    /// 切换硬分叉并重新构建预编译合约注册表
+    pub fn set_spec_id(&mut self, spec_id: SpecId) {
+        self.spec_id = spec_id;
+        self.refresh_precompiles();
+    }
+
+    /// 当前可用的预编译合约：当前硬分叉的标准预编译合约以及自定义预编译合约
+    /// 直接修改spec_id之后，注册表在下一笔交易开始时才会更新
+    pub fn precompiles(&self) -> &Precompiles {
+        &self.precompiles
+    }
+
+    /// spec_id被修改之后重新构建预编译合约注册表
+    pub fn refresh_precompiles(&mut self) {
+        if self.precompiles_spec != self.spec_id {
+            self.rebuild_precompiles();
+        }
+    }
+
+    fn rebuild_precompiles(&mut self) {
+        let mut precompiles = Precompiles::new(self.spec_id);
+        precompiles.extend(&self.custom_precompiles);
+        self.precompiles = precompiles;
+        self.precompiles_spec = self.spec_id;
+    }
+
+    /// 将地址加入warm集合，返回该地址在本次访问之前是否为cold
+    /// 调用帧revert或异常中止时，其中第一次访问的地址恢复为cold
+    pub fn access_address(&mut self, address: H160) -> bool {
+        let is_cold = self.accessed_addresses.insert(address);
+        if is_cold {
+            self.world_state.journal.push(JournalEntry::AccountWarmed { address });
+        }
+        is_cold
+    }
+
+    /// 将存储槽加入warm集合，返回该存储槽在本次访问之前是否为cold
+    pub fn access_storage_key(&mut self, address: H160, key: H256) -> bool {
+        let is_cold = self.accessed_storage_keys.insert((address, key));
+        if is_cold {
+            self.world_state.journal.push(JournalEntry::StorageWarmed { address, key });
+        }
+        is_cold
+    }
+
+    /// 当前是否处于STATICCALL发起的调用中，此时不允许修改状态
+    pub fn is_static(&self) -> bool {
+        self.call_stack.last().is_some_and(|call| call.is_static)
+    }
+
+    /// 在STATICCALL中执行修改状态的操作码时当前调用帧异常中止
+    pub fn ensure_not_static(&self) -> Result<(), Box<dyn ExitError>> {
+        if self.is_static() {
+            return Err(Box::new(OpcodeExecutionError::StaticStateChange));
+        }
+        Ok(())
+    }
+
+    /// 访问账户并收取cold访问的额外费用（warm访问费用已经在opcode表中收取），Berlin之前不区分warm/cold
+    pub fn record_account_access(&mut self, address: H160) -> Result<(), Box<dyn ExitError>> {
+        if self.access_address(address) && self.spec_id.is_enabled_in(SpecId::Berlin) {
+            self.gas.record_cost(COLD_ACCOUNT_ACCESS - WARM_STORAGE_READ)?;
+        }
+        Ok(())
+    }
+
+    /// 访问memory中[offset, offset + size)区间前调用：收取memory扩展费用并扩展memory
+    pub fn record_memory_cost(&mut self, offset: U256, size: U256) -> Result<(), Box<dyn ExitError>> {
+        let cost = memory_expansion_cost(self.memory.effective_len(), offset, size)?;
+        self.gas.record_cost(cost)?;
+        self.memory.resize_offset(offset, size)
+    }
+
+    /// 扣除转发给子调用的gas并返回子调用的gas上限
+    /// EIP-150之后最多转发剩余gas的63/64，之前请求的gas超过剩余gas时直接OutOfGas
+    pub fn record_forwarded_gas(&mut self, requested: u64) -> Result<u64, Box<dyn ExitError>> {
+        let available = self.gas.remaining();
+        let gas_limit = if self.spec_id.is_enabled_in(SpecId::TangerineWhistle) {
+            requested.min(available - available / 64)
+        } else {
+            requested
+        };
+        self.gas.record_cost(gas_limit)?;
+        Ok(gas_limit)
+    }
+
+    /// EIP-7702: 委托账户的code为委托标识，执行时执行被委托地址的code
+    pub fn resolve_delegation(&self, code: Bytes) -> Bytes {
+        match delegation_address(&code) {
+            Some(delegate) => self.world_state.get_code(delegate).unwrap_or_default(),
+            None => code,
+        }
+    }
+
+    /// EIP-170: 检查部署的runtime code是否超出长度上限
+    pub fn check_code_size(&self, runtime_code: &[u8]) -> Result<(), Box<dyn ExitError>> {
+        match self.spec_id.max_code_size() {
+            Some(limit) if runtime_code.len() > limit => Err(Box::new(OpcodeExecutionError::CreateContractLimit)),
+            _ => Ok(()),
+        }
+    }
+
+    /// EIP-3860: 检查init code是否超出长度上限
+    pub fn check_initcode_size(&self, init_code: &[u8]) -> Result<(), Box<dyn ExitError>> {
+        match self.spec_id.max_initcode_size() {
+            Some(limit) if init_code.len() > limit => Err(Box::new(OpcodeExecutionError::CreateContractLimit)),
+            _ => Ok(()),
+        }
+    }
+
     /// 描述：该函数用户创建合约
     /// 注意：调用该函数前需要构建好Call
     /// 该函数能以主动调用的形式触发，同时也由 is_constructor == true && to.is_none() 条件被动触发
@@ -114,6 +347,7 @@ impl EVM {
                 return Err(Box::new(EVMError::DeployContractFailed))
             }
         };
+        self.check_initcode_size(&creation_code)?;
         // 创建地址
         let nonce = match self.world_state.get_nonce(caller) {
             Ok(nonce) => nonce,
@@ -122,33 +356,47 @@ impl EVM {
         let create_address = Address::from_slice(caller.as_ref()).create(nonce as u64);
         let contract_address = address_to_h160(create_address);
 
-        let account_state = AccountState::new_contract(0, value, H256::default(), Default::default(),  creation_code);
-        self.world_state.new_account(contract_address, account_state.clone());
+        // 部署失败时撤销新建的合约账户
+        let checkpoint = self.checkpoint();
+        let nonce = if self.spec_id.is_enabled_in(SpecId::SpuriousDragon) { 1 } else { 0 };
+        let account_state = AccountState::new_contract(nonce, U256::zero(), H256::default(), Default::default(), Bytes::new());
+        self.world_state.new_account(contract_address, account_state);
+        self.world_state.mark_created(contract_address);
+        // 部署者余额不足以支付value时部署失败
+        if self.world_state.transfer(caller, contract_address, value).is_err() {
+            self.revert_to(checkpoint);
+            return Err(Box::new(EVMError::DeployContractFailed));
+        }
 
         let deploy_call = Call{
-            from: Default::default(),
-            to: None,
+            from: caller,
+            to: Some(contract_address),
             caller,
-            address: None,
+            address: Some(contract_address),
             value,
             call_data: Default::default(),
-            call_type: Default::default(),
+            call_type: CallType::Create,
             call_depth: 0,
             pc: 0,
-            world_state: self.world_state.clone()
+            is_static: false,
         };
         self.call_stack.push(deploy_call);
+        self.gas = Gas::new(self.gas_limit);
 
-        let call_result = self.interepter();
+        let call_result = self.interepter().and_then(|_| {
+            if self.is_revert {
+                return Err(Box::new(EVMError::DeployContractFailed) as Box<dyn ExitError>);
+            }
+            let runtime_code = Bytes::from(self.return_data.clone().unwrap_or_default());
+            self.deposit_code(contract_address, runtime_code)
+        });
         if call_result.is_err() {
+            self.revert_to(checkpoint);
+            self.call_stack.pop();
             return Err(Box::new(EVMError::DeployContractFailed));
-        };
-
-        let runtime_code = Bytes::from(self.return_data.clone().unwrap());
-        let code_hash:H256 = H256::from(ethers_keccak256(&runtime_code));
-        self.world_state.insert_code(contract_address, runtime_code);
-        println!("accountState is {:?}", self.world_state.state.get(&contract_address));
-        self.world_state.insert_codehash(contract_address, code_hash);
+        }
+        // 构造函数中执行过SELFDESTRUCT的合约在部署结束时删除
+        self.world_state.destroy_selfdestructs();
 
         self.call_stack.pop();
         Ok(contract_address)
@@ -208,6 +456,8 @@ impl EVM {
             Opcode::CHAINID => {chainid(self)}
             Opcode::SELFBALANCE => {selfbalance(self)}
             Opcode::BASEFEE => {basefee(self)}
+            Opcode::BLOBHASH => {blobhash(self)}
+            Opcode::BLOBBASEFEE => {blobbasefee(self)}
             Opcode::POP => {pop(self)}
             Opcode::MLOAD => {mload(self)}
             Opcode::MSTORE => {msotre(self)}
@@ -220,6 +470,8 @@ impl EVM {
             Opcode::MSIZE => {msize(self)}
             Opcode::GAS => {gas(self)}
             Opcode::JUMPDEST => {jumpdest(self)}
+            Opcode::TLOAD => {tload(self)}
+            Opcode::TSTORE => {tstore(self)}
             Opcode::MCOPY => {mcopy(self)}
             Opcode::PUSH0 => {push0(self)}
             Opcode::PUSH1 => {push1(self)}
@@ -304,90 +556,120 @@ impl EVM {
         }
     }
 
-    // 该调用操作，区别于call类型字节码实现的操作 主要用于create操作码在创造合约之后调用该合约
-    pub fn call(
+    /// CREATE/CREATE2创建合约：检查调用深度、余额以及地址冲突，执行init code并将返回的runtime code部署到新地址
+    /// 创建成功时返回合约地址，失败时返回None，init code执行过程中的异常不会导致当前调用帧中止
+    pub fn create(
         &mut self,
-        to: H160,
+        create_address: H160,
         value: U256,
-        gas:usize,
+        init_code: Bytes,
         call_type: CallType
-    ) -> Result<(), Box<dyn ExitError>> {
+    ) -> Result<Option<H160>, Box<dyn ExitError>> {
+        let creator = self.call_stack.last().unwrap().address.unwrap();
+        self.sub_return_data = None;
+        // 超出调用深度限制、余额不足或者nonce达到上限时创建直接失败，不消耗转发的gas
+        let nonce = self.world_state.get_nonce(creator)?;
+        if self.call_depth >= 1024 || self.world_state.get_balance(creator)? < value || nonce as u64 == u64::MAX {
+            return Ok(None);
+        }
+        self.world_state.increment_nonce(creator)?;
+        // EIP-2929: 新合约地址在地址冲突检查之前即加入warm集合
+        self.access_address(create_address);
+        // 创建合约时最多转发当前剩余gas的63/64
+        let gas_limit = self.record_forwarded_gas(self.gas.remaining())?;
+        // 目标地址已经存在code或者nonce不为0时地址冲突，转发的gas全部消耗
+        let has_code = self.world_state.get_code(create_address).is_ok_and(|code| !code.is_empty());
+        if has_code || self.world_state.get_nonce(create_address).unwrap_or_default() != 0 {
+            return Ok(None);
+        }
 
-        let before_code = self.bytecode.clone();
-        //将当前call组成Call压入栈中
-        let now_call = self.call_stack.last().unwrap();
-        // TODO!:这里需要注意内层和外层创建合约的区别，如果是在当前调用的合约内通过create类字节码创建则需要一个新的Call，如果是在最外层直接create合约，应该是不需要Call
+        // 创建失败时撤销新建的账户以及init code对状态的全部修改
+        let checkpoint = self.checkpoint();
+        // 地址上原有的余额保留，EIP-161之后新合约的nonce从1开始
+        let balance = self.world_state.get_balance(create_address).unwrap_or_default();
+        let nonce = if self.spec_id.is_enabled_in(SpecId::SpuriousDragon) { 1 } else { 0 };
+        self.world_state.new_account(create_address, AccountState::new_contract(nonce, balance, H256::default(), Default::default(), Bytes::new()));
+        self.world_state.mark_created(create_address);
+        self.world_state.transfer(creator, create_address, value)?;
         let _call = Call {
-            from: now_call.to.unwrap(),
-            to: Some(to),
+            from: creator,
+            to: Some(create_address),
+            caller: creator,
+            address: Some(create_address),
             value,
-            // create执行调用时并不会携带calldata去执行合约函数
+            // create执行init code时不携带calldata
             call_data: Bytes::new(),
-            caller: if call_type == CallType::DelegateCall{
-                now_call.caller
-            } else {
-                now_call.address.unwrap()
-            },
-            // 这里的is_err操作难道是如果目标地址没有code，则重新回到当前地址执行？
-            address: if call_type == CallType::DelegateCall{
-                now_call.address
-            } else {
-                Some(to)
-            },
             call_type,
             call_depth: self.call_depth,
             pc: self.pc,
-            world_state: self.world_state.clone(),
+            is_static: false,
         };
-        self.evm_stack.push(self.stack.clone());
-        self.memory_stack.push(self.memory.clone());
+        // 保存上下文
+        let before_code = self.bytecode.replace(init_code);
+        self.evm_stack.push(std::mem::replace(&mut self.stack, Stack::new(1024)));
+        self.memory_stack.push(std::mem::replace(&mut self.memory, Memory::new(1024)));
+        self.gas_stack.push(std::mem::replace(&mut self.gas, Gas::new(gas_limit)));
         self.call_stack.push(_call.clone());
         self.call_depth += 1;
+        self.pc = 0;
 
-        // 执行call操作
-        match self.world_state.get_code(to) {
-            Ok(code) => {
-                self.bytecode = Some(code);
-                let interpret_result = self.interepter();
-                if interpret_result.is_err() {
-                    return Err(interpret_result.err().unwrap());
-                }
-            },
-            Err(e) => return Err(e)
-        };
+        // init code执行成功后将return的数据作为runtime code部署，部署费用由子调用的gas支付
+        let result = self.interepter().and_then(|_| {
+            if self.is_revert {
+                return Ok(());
+            }
+            let runtime_code = Bytes::from(self.return_data.clone().unwrap_or_default());
+            self.deposit_code(create_address, runtime_code)
+        });
+        let reverted = self.is_revert;
+        let output = self.return_data.take();
+        self.is_revert = false;
 
+        // 恢复上下文
         self.bytecode = before_code;
-        self.pc = _call.pc + 1;
+        self.pc = _call.pc;
         self.call_depth -= 1;
         self.stack = self.evm_stack.pop().unwrap();
         self.memory = self.memory_stack.pop().unwrap();
+        let child_gas = std::mem::replace(&mut self.gas, self.gas_stack.pop().unwrap());
         self.call_stack.pop();
-
-        // create进入创建合约的子调用之后会将创建合约的runtime_code作为return的数据存放在return_data中
-        self.sub_return_data = match self.return_data.clone(){
-            None => {None}
-            Some(ret_data) => {
-                Some(ret_data)
+        match result {
+            Ok(_) if !reverted => {
+                self.gas.erase_cost(child_gas.remaining());
+                self.gas.record_refund(child_gas.refunded());
+                Ok(Some(create_address))
             }
-        };
-        let runtime_code:Bytes = match &self.sub_return_data{
-            None => { Bytes::new() }
-            Some(data) => {  Bytes::from(data.clone()) }
-        };
-        // 更新对应地址的code
-        self.world_state.insert_code(to, runtime_code);
+            // revert时退还剩余gas，revert的数据作为returndata
+            Ok(_) => {
+                self.gas.erase_cost(child_gas.remaining());
+                self.revert_to(checkpoint);
+                self.sub_return_data = output;
+                Ok(None)
+            }
+            // 异常中止或部署code失败时消耗全部转发的gas
+            Err(_) => {
+                self.revert_to(checkpoint);
+                Ok(None)
+            }
+        }
+    }
 
-        self.return_data = None;
-        if _call.call_type.eq(&CallType::StaticCall) {
-            // 恢复上下文
-            self.world_state = _call.world_state;
+    /// 部署runtime code：EIP-3541禁止0xEF开头的code，EIP-170限制code长度，并按字节收取部署费用
+    pub fn deposit_code(&mut self, address: H160, runtime_code: Bytes) -> Result<(), Box<dyn ExitError>> {
+        if self.spec_id.is_enabled_in(SpecId::London) && runtime_code.first() == Some(&0xEF) {
+            return Err(Box::new(OpcodeExecutionError::CreateContractStartingWithEF));
         }
-        if self.is_revert {
-            self.stack.push(U256::zero())?;
-        } else {
-            self.stack.push(U256::one())?;
-        };
-        increment_nonce(self, self.call_stack.last().unwrap().address.unwrap())?;
+        self.check_code_size(&runtime_code)?;
+        // Homestead之前gas不足以支付部署费用时创建依然成功，只是不部署code
+        if let Err(e) = self.gas.record_cost(CODEDEPOSIT * runtime_code.len() as u64) {
+            if self.spec_id.is_enabled_in(SpecId::Homestead) {
+                return Err(e);
+            }
+            return Ok(());
+        }
+        let code_hash = H256::from(ethers_keccak256(&runtime_code));
+        self.world_state.insert_code(address, runtime_code);
+        self.world_state.insert_codehash(address, code_hash);
         Ok(())
     }
 }
@@ -404,4 +686,810 @@ impl fmt::Display for EVM {
         let return_data= self.return_data.clone();
         write!(f, "excution returndata is:{:?}\n", return_data)
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::types::transaction::eip2930::AccessListItem;
+    use crate::external_call;
+    use crate::result::ExecutionResult;
+    use std::str::FromStr;
+
+    /// 测试中使用的调用者以及合约地址
+    fn test_accounts() -> (H160, H160) {
+        let caller: H160 = "0xbCDF0E814b7c65B238E2815289aCc05D3B933624".parse().unwrap();
+        let contract: H160 = "0x1000000000000000000000000000000000000001".parse().unwrap();
+        (caller, contract)
+    }
+
+    /// 被测试合约调用的合约地址
+    fn callee() -> H160 {
+        "0x2000000000000000000000000000000000000002".parse().unwrap()
+    }
+
+    /// MSTORE(0, 栈顶) RETURN(0, 32)：返回栈顶的值
+    const RETURN_TOP: &str = "60005260206000f3";
+
+    /// opcode(gas, to, value, 0, 0, 0, retSize)的字节码，调用结果留在栈顶
+    /// STATICCALL与DELEGATECALL没有value参数
+    fn call_op(opcode: u8, to: H160, value: u8, ret_size: u8) -> String {
+        let value = match opcode {
+            0xf1 | 0xf2 => format!("60{:02x}", value),
+            _ => String::new(),
+        };
+        format!("60{:02x}600060006000{}73{:x}5a{:02x}", ret_size, value, to, opcode)
+    }
+
+    /// 在新的EVM中部署runtime code，返回EVM以及EOA对该合约的调用
+    fn setup(code: &str, gas_limit: u64) -> (EVM, Call) {
+        let (caller, contract) = test_accounts();
+        let mut world_state = WorldState::default();
+        world_state.new_account(caller, AccountState::new_eoa(0, U256::from(10)));
+        world_state.new_account(contract, AccountState::new_contract(1, U256::zero(), H256::default(), Default::default(), code.parse().unwrap()));
+
+        let mut evm = EVM::new(world_state);
+        evm.gas_limit = gas_limit;
+        let call = Call {
+            from: caller,
+            to: Some(contract),
+            caller,
+            address: Some(contract),
+            value: U256::zero(),
+            call_data: Bytes::new(),
+            call_type: CallType::Call,
+            call_depth: 0,
+            pc: 0,
+            is_static: false,
+        };
+        (evm, call)
+    }
+
+    fn execute(code: &str, gas_limit: u64) -> (EVM, ExecutionResult) {
+        let (mut evm, call) = setup(code, gas_limit);
+        let result = external_call(&mut evm, call).unwrap();
+        (evm, result)
+    }
+
+    /// 在指定硬分叉下执行runtime code
+    fn execute_with_spec(code: &str, gas_limit: u64, spec_id: SpecId) -> (EVM, ExecutionResult) {
+        let (mut evm, call) = setup(code, gas_limit);
+        evm.spec_id = spec_id;
+        let result = external_call(&mut evm, call).unwrap();
+        (evm, result)
+    }
+
+    /// 在已有的EVM中添加一个合约账户
+    fn insert_contract(evm: &mut EVM, address: H160, code: &str) {
+        evm.world_state.new_account(address, AccountState::new_contract(1, U256::zero(), H256::default(), Default::default(), code.parse().unwrap()));
+    }
+
+    /// 执行runtime code，callee的code为callee_code
+    fn execute_with_callee(code: &str, callee_code: &str) -> (EVM, ExecutionResult) {
+        let (mut evm, call) = setup(code, 1_000_000);
+        insert_contract(&mut evm, callee(), callee_code);
+        let result = external_call(&mut evm, call).unwrap();
+        (evm, result)
+    }
+
+    /// gas计量
+    mod gas {
+        use super::*;
+
+        #[test]
+        fn test_static_gas() {
+            // PUSH1 1 PUSH1 2 ADD STOP
+            let (evm, result) = execute("0x600160020100", 100);
+            assert!(result.is_success());
+            assert_eq!(evm.gas.spent(), 9);
+        }
+
+        #[test]
+        fn test_memory_expansion_gas() {
+            // PUSH1 0x2a PUSH1 0 MSTORE PUSH1 0x2a PUSH1 0x20 MSTORE STOP
+            let (evm, _) = execute("0x602a600052602a60205200", 100);
+            // 4个push + 2个mstore + 两次各扩展一个字
+            assert_eq!(evm.gas.spent(), 4 * 3 + 2 * 3 + 3 + 3);
+        }
+
+        #[test]
+        fn test_gas_opcode() {
+            // GAS PUSH1 0 MSTORE PUSH1 32 PUSH1 0 RETURN
+            let (_, result) = execute("0x5a60005260206000f3", 1000);
+            let output = result.output().unwrap();
+            assert_eq!(U256::from_big_endian(&output), U256::from(998));
+        }
+
+        #[test]
+        fn test_out_of_gas() {
+            // JUMPDEST PUSH1 0 JUMP：死循环直到gas耗尽
+            let (evm, result) = execute("0x5b600056", 100);
+            assert_eq!(result, ExecutionResult::Halt { reason: OpcodeExecutionError::OutOfGas, gas_used: 100 });
+            assert_eq!(evm.gas.remaining(), 0);
+        }
+
+        #[test]
+        fn test_copy_out_of_gas() {
+            // 长度为0xff..ff的CALLDATACOPY、CODECOPY、RETURNDATACOPY以及EXTCODECOPY在复制之前OutOfGas
+            let size = format!("7f{}", "ff".repeat(32));
+            for copy in ["6000600037", "6000600039", "600060003e", "600060007310000000000000000000000000000000000000013c"] {
+                let (_, result) = execute(&format!("0x{}{}", size, copy), 100_000);
+                assert_eq!(result, ExecutionResult::Halt { reason: OpcodeExecutionError::OutOfGas, gas_used: 100_000 }, "{}", copy);
+            }
+        }
+
+        #[test]
+        fn test_sstore_net_gas_metering() {
+            // EIP-3529中的测试用例：(code, 原始值, 消耗的gas, 退款)，存储槽已通过访问列表预热
+            let cases = [
+                ("0x60006000556000600055", 0, 212, 0),
+                ("0x60006000556001600055", 0, 20112, 0),
+                ("0x60016000556000600055", 0, 20112, 19900),
+                ("0x60016000556002600055", 0, 20112, 0),
+                ("0x60016000556001600055", 0, 20112, 0),
+                ("0x60006000556000600055", 1, 3012, 4800),
+                ("0x60006000556001600055", 1, 3012, 2800),
+                ("0x60006000556002600055", 1, 3012, 0),
+                ("0x60026000556000600055", 1, 3012, 4800),
+                ("0x60026000556003600055", 1, 3012, 0),
+                ("0x60026000556001600055", 1, 3012, 2800),
+                ("0x60026000556002600055", 1, 3012, 0),
+                ("0x60016000556000600055", 1, 3012, 4800),
+                ("0x60016000556002600055", 1, 3012, 0),
+                ("0x60016000556001600055", 1, 212, 0),
+                ("0x600160005560006000556001600055", 0, 40118, 19900),
+                ("0x600060005560016000556000600055", 1, 5918, 7600),
+            ];
+            let (_, contract) = test_accounts();
+            for (code, original, gas_used, refund) in cases {
+                let (mut evm, call) = setup(code, 100_000);
+                evm.world_state.insert_storage_value(contract, H256::zero(), H256::from_low_u64_be(original)).unwrap();
+                evm.access_list = AccessList(vec![AccessListItem {
+                    address: contract,
+                    storage_keys: vec![H256::zero()],
+                }]);
+                external_call(&mut evm, call).unwrap();
+                assert_eq!(evm.gas.spent(), gas_used, "code {}", code);
+                assert_eq!(evm.gas.refunded(), refund.min(gas_used / 5) as i64, "code {}", code);
+            }
+        }
+
+        #[test]
+        fn test_sstore_sentry() {
+            // gas不超过2300时sstore直接失败：PUSH1 1 PUSH1 0 SSTORE
+            let (evm, result) = execute("0x6001600055", 2306);
+            assert!(matches!(result, ExecutionResult::Halt { reason: OpcodeExecutionError::OutOfGas, .. }));
+            assert_eq!(evm.gas.remaining(), 0);
+        }
+
+        #[test]
+        fn test_sload_cost_by_spec() {
+            // PUSH1 0 SLOAD STOP
+            let (evm, _) = execute_with_spec("0x60005400", 10000, SpecId::Homestead);
+            assert_eq!(evm.gas.spent(), 3 + 50);
+            let (evm, _) = execute_with_spec("0x60005400", 10000, SpecId::Byzantium);
+            assert_eq!(evm.gas.spent(), 3 + 200);
+            let (evm, _) = execute_with_spec("0x60005400", 10000, SpecId::Istanbul);
+            assert_eq!(evm.gas.spent(), 3 + 800);
+            let (evm, _) = execute_with_spec("0x60005400", 10000, SpecId::Berlin);
+            assert_eq!(evm.gas.spent(), 3 + 2100);
+        }
+    }
+
+    /// EIP-2929/2930：warm与cold访问
+    mod access_list {
+        use super::*;
+
+        #[test]
+        fn test_cold_and_warm_sload() {
+            // PUSH1 0 SLOAD PUSH1 0 SLOAD STOP：第一次访问为cold，第二次为warm
+            let (evm, _) = execute("0x60005460005400", 10000);
+            assert_eq!(evm.gas.spent(), 3 + 2100 + 3 + 100);
+        }
+
+        #[test]
+        fn test_cold_and_warm_account_access() {
+            // PUSH20 addr BALANCE PUSH20 addr BALANCE STOP
+            let code = "0x73aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa3173aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa3100";
+            let (evm, _) = execute(code, 10000);
+            assert_eq!(evm.gas.spent(), 3 + 2600 + 3 + 100);
+
+            // 调用者以及合约自身在交易开始时就是warm的：ADDRESS BALANCE CALLER BALANCE STOP
+            let (evm, _) = execute("0x3031333100", 10000);
+            assert_eq!(evm.gas.spent(), 2 + 100 + 2 + 100);
+        }
+
+        #[test]
+        fn test_revert_restores_cold_access() {
+            // callee测量SLOAD(0)消耗的gas之后REVERT：GAS PUSH1 0 SLOAD POP GAS SWAP1 SUB，将差值作为revert的数据返回
+            let callee_code = "0x5a600054505a900360005260206000fd";
+            // 调用callee两次，返回第二次调用的returndata
+            let call_callee = format!("{}50", call_op(0xf1, callee(), 0, 0x20));
+            let code = format!("0x{}{}60206000f3", call_callee, call_callee);
+            let (_, result) = execute_with_callee(&code, callee_code);
+            // 第一次调用中预热的存储槽随revert恢复为cold：PUSH1 + cold SLOAD + POP + GAS
+            assert_eq!(U256::from_big_endian(result.output().unwrap()), U256::from(3 + 2100 + 2 + 2));
+        }
+
+        #[test]
+        fn test_access_list_prewarm() {
+            let (_, contract) = test_accounts();
+            let (mut evm, call) = setup("0x60005400", 10000);
+            evm.access_list = AccessList(vec![AccessListItem {
+                address: contract,
+                storage_keys: vec![H256::zero()],
+            }]);
+            external_call(&mut evm, call).unwrap();
+            assert_eq!(evm.gas.spent(), 3 + 100);
+        }
+    }
+
+    /// 单个调用帧内的执行结果
+    mod execution {
+        use super::*;
+
+        #[test]
+        fn test_copy_zero_padding() {
+            let ones = format!("7f{}", "ff".repeat(32));
+            // 先用MSTORE(0, 0xff..ff)填充memory，再将超出数据范围的部分复制到memory，超出的部分补0：
+            // CALLDATACOPY(0, 0, 32)、CALLDATALOAD(0xff..ff)、CODECOPY(0, 0xff..ff, 32)
+            for copy in ["60206000600037".to_string(), format!("{}35600052", ones), format!("6020{}600039", ones)] {
+                // 返回memory中的第一个字
+                let code = format!("0x{}600052{}60206000f3", ones, copy);
+                let (_, result) = execute(&code, 100_000);
+                assert_eq!(result.output().unwrap(), &[0u8; 32][..], "{}", copy);
+            }
+            // 字节码末尾被截断的PUSH2
+            let (_, result) = execute("0x61ff", 100_000);
+            assert!(result.is_success());
+        }
+
+        #[test]
+        fn test_zero_length_memory_access() {
+            // 长度为0时offset超出u64范围也不访问memory：offset = 2^64
+            let offset = "68010000000000000000";
+            // RETURN(offset, 0)、REVERT(offset, 0)
+            let (_, result) = execute(&format!("0x6000{}f3", offset), 100_000);
+            assert!(result.output().unwrap().is_empty());
+            let (_, result) = execute(&format!("0x6000{}fd", offset), 100_000);
+            assert_eq!(result, ExecutionResult::Revert { output: Vec::new(), gas_used: 3 + 3 });
+            // KECCAK256(offset, 0)为空数据的哈希
+            let (_, result) = execute(&format!("0x6000{}20{}", offset, RETURN_TOP), 100_000);
+            assert_eq!(result.output().unwrap(), &ethers_keccak256([0u8; 0])[..]);
+            // CREATE(0, offset, 0)、CREATE2(0, offset, 0, 0)创建code为空的合约
+            for create in [format!("6000{}6000f0", offset), format!("60006000{}6000f5", offset)] {
+                let (_, result) = execute(&format!("0x{}{}", create, RETURN_TOP), 1_000_000);
+                assert!(!U256::from_big_endian(result.output().unwrap()).is_zero(), "{}", create);
+            }
+            // CALL(gas, callee, 0, offset, 0, offset, 0)
+            let code = format!("0x6000{}6000{}600073{:x}5af1{}", offset, offset, callee(), RETURN_TOP);
+            let (_, result) = execute_with_callee(&code, "0x00");
+            assert_eq!(result.output().unwrap()[31], 1);
+        }
+
+        #[test]
+        fn test_invalid_jump() {
+            // PUSH1 4 JUMP INVALID JUMPDEST STOP
+            let (_, result) = execute("0x600456fe5b00", 1000);
+            assert!(result.is_success());
+            // 跳转到PUSH1的立即数0x5b：PUSH1 3 JUMP PUSH1 0x5b STOP
+            let (_, result) = execute("0x600356605b00", 1000);
+            assert_eq!(result, ExecutionResult::Halt { reason: OpcodeExecutionError::InvalidJump, gas_used: 1000 });
+            // 跳转到字节码范围之外：PUSH32 0xff..ff JUMP
+            let (_, result) = execute(&format!("0x7f{}56", "ff".repeat(32)), 1000);
+            assert_eq!(result, ExecutionResult::Halt { reason: OpcodeExecutionError::InvalidJump, gas_used: 1000 });
+            // 条件不满足时不检查跳转目标：PUSH1 0 PUSH1 1 JUMPI STOP
+            let (evm, result) = execute("0x600060015700", 1000);
+            assert!(result.is_success());
+            assert_eq!(evm.jumpdest_cache.len(), 1);
+        }
+
+        #[test]
+        fn test_execution_result() {
+            // PUSH1 0x2a PUSH1 0 MSTORE PUSH1 32 PUSH1 0 REVERT
+            let (_, result) = execute("0x602a60005260206000fd", 1000);
+            let mut output = vec![0u8; 32];
+            output[31] = 0x2a;
+            assert_eq!(result, ExecutionResult::Revert { output, gas_used: 3 * 4 + 3 + 3 });
+            // 栈下溢不再被跳过，交易异常中止并消耗全部gas：ADD
+            let (_, result) = execute("0x01", 1000);
+            assert_eq!(result, ExecutionResult::Halt { reason: OpcodeExecutionError::StackUnderflow, gas_used: 1000 });
+            // 接收者没有code时交易无法执行
+            let (caller, _) = test_accounts();
+            let (mut evm, mut call) = setup("0x00", 1000);
+            call.to = Some(caller);
+            assert!(external_call(&mut evm, call).is_err());
+        }
+
+        #[test]
+        fn test_repeated_external_call() {
+            // SSTORE(0, SLOAD(0) + 1)，返回写入的值
+            let (mut evm, call) = setup(&format!("0x60005460010180600055{}", RETURN_TOP), 100_000);
+            // 同一个EVM可以连续执行多笔交易，状态在交易之间保留
+            for expected in 1..=2 {
+                let result = external_call(&mut evm, call.clone()).unwrap();
+                assert_eq!(U256::from_big_endian(result.output().unwrap()), U256::from(expected));
+                assert!(evm.call_stack.is_empty());
+            }
+        }
+
+        #[test]
+        fn test_revert_discards_transaction_state() {
+            let (_, contract) = test_accounts();
+            // PUSH1 1 PUSH1 0 SSTORE PUSH1 0 PUSH1 0 REVERT
+            let (evm, _) = execute("0x600160005560006000fd", 100_000);
+            assert_eq!(evm.world_state.get_storage_value(contract, H256::zero()).unwrap(), H256::zero());
+            // 异常中止同样撤销全部修改：PUSH1 1 PUSH1 0 SSTORE INVALID
+            let (evm, result) = execute("0x6001600055fe", 100_000);
+            assert!(result.is_halt());
+            assert_eq!(evm.world_state.get_storage_value(contract, H256::zero()).unwrap(), H256::zero());
+            assert!(evm.world_state.journal.is_empty());
+        }
+
+        #[test]
+        fn test_opcode_availability_by_spec() {
+            // PUSH0 STOP：Shanghai之前PUSH0为无效操作码，执行后消耗全部gas
+            let (evm, result) = execute_with_spec("0x5f00", 1000, SpecId::Shanghai);
+            assert!(result.is_success());
+            assert_eq!(evm.gas.spent(), 2);
+            let (evm, result) = execute_with_spec("0x5f00", 1000, SpecId::London);
+            assert!(result.is_halt());
+            assert_eq!(evm.gas.spent(), 1000);
+        }
+
+        #[test]
+        fn test_transient_storage() {
+            // PUSH1 7 PUSH1 0 TSTORE PUSH1 0 TLOAD PUSH1 0 MSTORE PUSH1 32 PUSH1 0 RETURN
+            let (evm, result) = execute("0x600760005d60005c60005260206000f3", 10000);
+            assert_eq!(result.output().unwrap()[31], 7);
+            // 交易结束后瞬态存储被清空
+            assert!(evm.transient_storage.is_empty());
+            // Cancun之前TLOAD/TSTORE为无效操作码
+            let (_, result) = execute_with_spec("0x600760005d00", 10000, SpecId::Shanghai);
+            assert!(result.is_halt());
+        }
+
+        #[test]
+        fn test_blob_opcodes() {
+            // PUSH1 1 BLOBHASH PUSH1 0 MSTORE PUSH1 5 BLOBHASH PUSH1 32 MSTORE BLOBBASEFEE PUSH1 64 MSTORE PUSH1 96 PUSH1 0 RETURN
+            let (mut evm, call) = setup("0x6001496000526005496020524a60405260606000f3", 10000);
+            evm.blob_hashes = vec![H256::repeat_byte(0x01), H256::repeat_byte(0x02)];
+            evm.block = Some(Block {
+                blockhash: H256::zero(),
+                coinbase: H160::zero(),
+                timestamp: 0,
+                number: 0,
+                prevrandao: H256::zero(),
+                gas_limit: U256::zero(),
+                chainid: 1,
+                basefee: 0,
+                // 约为更新系数的10倍，blob base fee为 e^10 ≈ 22026
+                excess_blob_gas: Some(10 * 3_338_477),
+            });
+            let result = external_call(&mut evm, call).unwrap().output().unwrap().to_vec();
+            assert_eq!(&result[..32], H256::repeat_byte(0x02).as_bytes());
+            // 越界的index返回0
+            assert_eq!(&result[32..64], H256::zero().as_bytes());
+            assert_eq!(U256::from_big_endian(&result[64..96]), U256::from(22026));
+        }
+    }
+
+    /// CALL、CALLCODE、DELEGATECALL以及STATICCALL的语义
+    mod call {
+        use super::*;
+
+        #[test]
+        fn test_revert_discards_subcall_state() {
+            // CALL(gas, callee, 0, 0, 0, 0, 0) STOP
+            let code = format!("0x{}00", call_op(0xf1, callee(), 0, 0));
+            // PUSH1 1 PUSH1 0 SSTORE 之后 REVERT(0, 0) 或 STOP
+            for (callee_code, expected) in [("0x600160005560006000fd", 0), ("0x600160005500", 1)] {
+                let (evm, _) = execute_with_callee(&code, callee_code);
+                let value = evm.world_state.get_storage_value(callee(), H256::zero()).unwrap();
+                assert_eq!(value, H256::from_low_u64_be(expected), "callee {}", callee_code);
+            }
+        }
+
+        #[test]
+        fn test_transient_storage_revert() {
+            // 调用callee两次并返回第二次调用的returndata
+            let call_callee = format!("{}50", call_op(0xf1, callee(), 0, 0x20));
+            let code = format!("0x{}{}60206000f3", call_callee, call_callee);
+            // callee返回TLOAD(0)的旧值，写入TLOAD(0) = 1之后revert
+            let (_, result) = execute_with_callee(&code, "0x60005c600052600160005d60206000fd");
+            // 第一次调用的写入随revert回滚，第二次调用读取到的仍然是0
+            assert_eq!(result.output().unwrap(), &[0u8; 32][..]);
+        }
+
+        #[test]
+        fn test_tstore_in_static_call() {
+            // STATICCALL callee，返回调用结果；callee执行PUSH1 1 PUSH1 0 TSTORE STOP
+            let code = format!("0x{}{}", call_op(0xfa, callee(), 0, 0x20), RETURN_TOP);
+            let (_, result) = execute_with_callee(&code, "0x600160005d00");
+            assert_eq!(result.output().unwrap(), &[0u8; 32][..]);
+        }
+
+        #[test]
+        fn test_logs() {
+            let (_, contract) = test_accounts();
+            // MSTORE(0, 0x2a) LOG2(0, 32, 1, 2) CALL(gas, callee, 0, 0, 0, 0, 0) STOP
+            let code = format!("0x602a6000526002600160206000a2{}00", call_op(0xf1, callee(), 0, 0));
+            let mut data = vec![0u8; 32];
+            data[31] = 0x2a;
+            let log = Log { address: contract, topics: vec![U256::from(1), U256::from(2)], data };
+            // callee执行LOG0(0, 0)之后REVERT，其日志被撤销
+            let (_, result) = execute_with_callee(&code, "0x60006000a060006000fd");
+            assert_eq!(result.logs(), &[log.clone()]);
+            // callee执行LOG0(0, 0)之后STOP，日志地址为callee
+            let (_, result) = execute_with_callee(&code, "0x60006000a000");
+            assert_eq!(result.logs(), &[log, Log { address: callee(), topics: Vec::new(), data: Vec::new() }]);
+            // 被revert的交易不产生日志：LOG0(0, 0) REVERT(0, 0)
+            let (evm, result) = execute("0x60006000a060006000fd", 100_000);
+            assert!(result.logs().is_empty());
+            assert!(evm.logs.is_empty());
+        }
+
+        #[test]
+        fn test_call_return_size() {
+            // MSTORE(0, 0xff..ff) CALL(gas, callee, 0, 0, 0, 0, retSize) RETURN(0, 32)
+            let code = |ret_size: u8| format!("0x7f{}600052{}5060206000f3", "ff".repeat(32), call_op(0xf1, callee(), 0, ret_size));
+            for (ret_size, expected) in [(0, [0xff; 32]), (16, { let mut word = [0u8; 32]; word[16..].fill(0xff); word })] {
+                // callee返回32字节的0x2a
+                let (_, result) = execute_with_callee(&code(ret_size), "0x602a60005260206000f3");
+                assert_eq!(result.output().unwrap(), &expected[..], "retSize {}", ret_size);
+            }
+        }
+
+        #[test]
+        fn test_returndatacopy() {
+            // CALL(gas, callee, 0, 0, 0, 0, 0) RETURNDATACOPY(0x20, 0, size) RETURN(0x20, 32)
+            let code = |size: u8| format!("0x{}5060{:02x}600060203e60206020f3", call_op(0xf1, callee(), 0, 0), size);
+            // callee返回32字节的0x2a
+            let (_, result) = execute_with_callee(&code(32), "0x602a60005260206000f3");
+            assert_eq!(U256::from_big_endian(result.output().unwrap()), U256::from(0x2a));
+            // 读取超出returndata的范围
+            let (_, result) = execute_with_callee(&code(33), "0x602a60005260206000f3");
+            assert_eq!(result, ExecutionResult::Halt { reason: OpcodeExecutionError::OutOfOffset, gas_used: 1_000_000 });
+            // 没有returndata时读取任意字节均异常中止，长度为0时不受影响：RETURNDATACOPY(0, 0, size) STOP
+            let (_, result) = execute("0x6001600060003e00", 100_000);
+            assert_eq!(result, ExecutionResult::Halt { reason: OpcodeExecutionError::OutOfOffset, gas_used: 100_000 });
+            let (_, result) = execute("0x6000600060003e00", 100_000);
+            assert!(result.is_success());
+        }
+
+        #[test]
+        fn test_returndata_cleared_by_call_without_code() {
+            let (caller, _) = test_accounts();
+            // CALL(gas, callee, 0, 0, 0, 0, 0) CALL(gas, to, 0, 0, 0, 0, 0) RETURNDATASIZE，返回RETURNDATASIZE
+            // callee返回32字节，之后调用没有code的账户清空returndata
+            for to in [H160::from_low_u64_be(0x1234), caller] {
+                let code = format!("0x{}50{}503d{}", call_op(0xf1, callee(), 0, 0), call_op(0xf1, to, 0, 0), RETURN_TOP);
+                let (_, result) = execute_with_callee(&code, "0x602a60005260206000f3");
+                assert_eq!(U256::from_big_endian(result.output().unwrap()), U256::zero(), "{:?}", to);
+            }
+        }
+
+        #[test]
+        fn test_delegatecall_proxy() {
+            let (caller, proxy) = test_accounts();
+            let implementation = callee();
+            let witness: H160 = "0x3000000000000000000000000000000000000003".parse().unwrap();
+            // EIP-1967: 实现合约地址保存在固定的存储槽中
+            let slot: H256 = "0x360894a13ba1a3210667c828492db98dca3e2076cc3735a920a3ca505d382bbc".parse().unwrap();
+            // DELEGATECALL(gas, SLOAD(slot), 0, 0, 0, 0)，返回调用结果
+            let code = format!("0x60006000600060007f{:x}545af4{}", slot, RETURN_TOP);
+            let (mut evm, mut call) = setup(&code, 1_000_000);
+            call.value = U256::from(5);
+            evm.world_state.insert_storage_value(proxy, slot, H256::from(implementation)).unwrap();
+            // SSTORE(0, CALLER) SSTORE(1, CALLVALUE) SSTORE(2, ADDRESS) CALL(gas, witness, 0, 0, 0, 0, 0) STOP
+            insert_contract(&mut evm, implementation, &format!("0x336000553460015530600255{}5000", call_op(0xf1, witness, 0, 0)));
+            // SSTORE(0, CALLER)
+            insert_contract(&mut evm, witness, "0x3360005500");
+            let result = external_call(&mut evm, call).unwrap();
+            assert_eq!(result.output().unwrap()[31], 1);
+            // 实现合约的code在代理合约的上下文中执行，msg.sender与msg.value沿用外层调用
+            let storage = |evm: &EVM, address: H160, key: u64| evm.world_state.get_storage_value(address, H256::from_low_u64_be(key)).unwrap_or_default();
+            assert_eq!(storage(&evm, proxy, 0), H256::from(caller));
+            assert_eq!(storage(&evm, proxy, 1), H256::from_low_u64_be(5));
+            assert_eq!(storage(&evm, proxy, 2), H256::from(proxy));
+            assert_eq!(storage(&evm, implementation, 0), H256::zero());
+            // 实现合约中发起的CALL，msg.sender为代理合约
+            assert_eq!(storage(&evm, witness, 0), H256::from(proxy));
+
+            // CALLCODE(gas, implementation, 0, 0, 0, 0, 0)：msg.sender为当前合约
+            let code = format!("0x{}{}", call_op(0xf2, implementation, 0, 0), RETURN_TOP);
+            let (evm, result) = execute_with_callee(&code, "0x33600055346001553060025500");
+            assert_eq!(result.output().unwrap()[31], 1);
+            assert_eq!(storage(&evm, proxy, 0), H256::from(proxy));
+            assert_eq!(storage(&evm, proxy, 1), H256::zero());
+            assert_eq!(storage(&evm, proxy, 2), H256::from(proxy));
+            assert_eq!(storage(&evm, implementation, 0), H256::zero());
+        }
+
+        #[test]
+        fn test_value_transfer() {
+            let (_, contract) = test_accounts();
+            // CALL(gas, to, value, 0, 0, 0, 0)，返回调用结果；suffix为返回之前执行的操作码
+            let call_with_value = |to: H160, value: u8, callee_code: Option<&str>, suffix: &str| {
+                let code = format!("0x{}600052{}60206000f3", call_op(0xf1, to, value, 0), suffix);
+                let (mut evm, call) = setup(&code, 1_000_000);
+                evm.world_state.set_balance(contract, U256::from(100)).unwrap();
+                if let Some(callee_code) = callee_code {
+                    insert_contract(&mut evm, callee(), callee_code);
+                }
+                let result = external_call(&mut evm, call).unwrap();
+                (evm, result)
+            };
+            let balance = |evm: &EVM, address: H160| evm.world_state.get_balance(address).unwrap_or_default();
+
+            // 向不存在的账户转账时创建该账户
+            let eoa = H160::from_low_u64_be(0x1234);
+            let (evm, result) = call_with_value(eoa, 30, None, "");
+            assert_eq!(result.output().unwrap()[31], 1);
+            assert_eq!(balance(&evm, eoa), U256::from(30));
+            assert_eq!(balance(&evm, contract), U256::from(70));
+            // 余额不足时调用失败，且不执行目标合约：SSTORE(0, 1)
+            let (evm, result) = call_with_value(callee(), 200, Some("0x600160005500"), "");
+            assert_eq!(result.output().unwrap()[31], 0);
+            assert_eq!(balance(&evm, contract), U256::from(100));
+            assert_eq!(evm.world_state.get_storage_value(callee(), H256::zero()).unwrap_or_default(), H256::zero());
+            // 目标合约REVERT时撤销转账
+            let (evm, result) = call_with_value(callee(), 30, Some("0x60006000fd"), "");
+            assert_eq!(result.output().unwrap()[31], 0);
+            assert_eq!(balance(&evm, callee()), U256::zero());
+            assert_eq!(balance(&evm, contract), U256::from(100));
+            // 交易REVERT时同样撤销转账
+            let (evm, result) = call_with_value(callee(), 30, Some("0x00"), "60206000fd");
+            assert!(!result.is_success());
+            assert_eq!(balance(&evm, callee()), U256::zero());
+            assert_eq!(balance(&evm, contract), U256::from(100));
+            // 向预编译合约转账
+            let identity = H160::from_low_u64_be(4);
+            let (evm, result) = call_with_value(identity, 10, None, "");
+            assert_eq!(result.output().unwrap()[31], 1);
+            assert_eq!(balance(&evm, identity), U256::from(10));
+        }
+
+        #[test]
+        fn test_failed_transfer_returns_stipend() {
+            // 合约余额为0：GAS CALL(gas, callee, 1, 0, 0, 0, 0) POP GAS SWAP1 SUB，返回CALL消耗的gas
+            let code = format!("0x5a{}505a9003{}", call_op(0xf1, callee(), 1, 0), RETURN_TOP);
+            let (_, result) = execute_with_callee(&code, "0x00");
+            // 6个PUSH + GAS + cold访问 + 转账费用 - 退还的津贴 + POP + GAS
+            assert_eq!(U256::from_big_endian(result.output().unwrap()), U256::from(6 * 3 + 2 + 2600 + 9000 - 2300 + 2 + 2));
+        }
+
+        #[test]
+        fn test_static_call_violations() {
+            let inner: H160 = "0x3000000000000000000000000000000000000003".parse().unwrap();
+            // STATICCALL callee，返回调用结果
+            let code = format!("0x{}{}", call_op(0xfa, callee(), 0, 0x20), RETURN_TOP);
+            let static_call = |callee_code: &str| {
+                let (mut evm, call) = setup(&code, 1_000_000);
+                insert_contract(&mut evm, callee(), callee_code);
+                insert_contract(&mut evm, inner, "0x600160005500");
+                let result = external_call(&mut evm, call).unwrap();
+                let success = result.output().unwrap()[31];
+                (evm, success)
+            };
+            // SSTORE、LOG0、CREATE、SELFDESTRUCT以及携带value的CALL均导致子调用失败
+            assert_eq!(static_call("0x600160005500").1, 0);
+            assert_eq!(static_call("0x60006000a000").1, 0);
+            assert_eq!(static_call("0x600060006000f000").1, 0);
+            assert_eq!(static_call("0x6000ff").1, 0);
+            assert_eq!(static_call("0x6000600060006000600160005af100").1, 0);
+            // 只读操作以及不携带value的CALL不受影响
+            assert_eq!(static_call("0x60005400").1, 1);
+            assert_eq!(static_call("0x6000600060006000600060005af100").1, 1);
+            // static模式会被子调用继承：callee CALL inner，inner执行SSTORE失败
+            let (evm, success) = static_call(&format!("0x{}00", call_op(0xf1, inner, 0, 0)));
+            assert_eq!(success, 1);
+            assert_eq!(evm.world_state.get_storage_value(inner, H256::zero()).unwrap_or_default(), H256::zero());
+        }
+    }
+
+    /// CREATE、CREATE2以及SELFDESTRUCT
+    mod create {
+        use super::*;
+
+        /// 将init code(不超过32字节)写入memory，执行CREATE并返回创建结果；salt不为None时执行CREATE2
+        fn create_code(init_code: &str, salt: Option<u8>) -> String {
+            let len = init_code.len() / 2;
+            let create = match salt {
+                None => format!("60{:02x}60{:02x}6000f0", len, 32 - len),
+                Some(salt) => format!("60{:02x}60{:02x}60{:02x}6000f5", salt, len, 32 - len),
+            };
+            format!("0x{:02x}{}600052{}{}", 0x5f + len, init_code, create, RETURN_TOP)
+        }
+
+        #[test]
+        fn test_create() {
+            let (_, contract) = test_accounts();
+            // init code返回1字节的runtime code 0x2a
+            let init_code = "602a6000526001601ff3";
+            let (evm, result) = execute(&create_code(init_code, None), 1_000_000);
+            // 新合约地址由当前执行合约的地址以及nonce决定
+            let expected = address_to_h160(Address::from_slice(contract.as_bytes()).create(1));
+            assert_eq!(&result.output().unwrap()[12..], expected.as_bytes());
+            assert_eq!(evm.world_state.get_code(expected).unwrap().to_vec(), vec![0x2a]);
+            assert_eq!(evm.world_state.get_nonce(expected).unwrap(), 1);
+            assert_eq!(evm.world_state.get_nonce(contract).unwrap(), 2);
+            // 32字节地址 + 1字节code的部署费用
+            assert!(evm.gas.spent() > CODEDEPOSIT);
+
+            // CREATE2的地址由salt以及init code的哈希决定
+            let (_, result) = execute(&create_code(init_code, Some(1)), 1_000_000);
+            let mut salt = [0u8; 32];
+            salt[31] = 1;
+            let init_code_hash = ethers_keccak256(Bytes::from_str(init_code).unwrap());
+            let expected = address_to_h160(Address::from_slice(contract.as_bytes()).create2(salt, init_code_hash));
+            assert_eq!(&result.output().unwrap()[12..], expected.as_bytes());
+
+            // 同一个salt第二次CREATE2时地址冲突，创建失败但nonce依然增加
+            let create2 = "6001600a60166000f5";
+            let code = format!("0x69{}600052{}50{}{}", init_code, create2, create2, RETURN_TOP);
+            let (evm, result) = execute(&code, 1_000_000);
+            assert_eq!(result.output().unwrap(), &[0u8; 32]);
+            assert_eq!(evm.world_state.get_nonce(contract).unwrap(), 3);
+        }
+
+        #[test]
+        fn test_create_failures() {
+            let (_, contract) = test_accounts();
+            let create = |init_code: &str, gas_limit: u64| {
+                let (evm, result) = execute(&create_code(init_code, None), gas_limit);
+                assert!(result.is_success());
+                let created = U256::from_big_endian(result.output().unwrap());
+                (evm, created)
+            };
+            // EIP-3541: runtime code不能以0xEF开头
+            let (evm, created) = create("60ef6000526001601ff3", 1_000_000);
+            assert!(created.is_zero());
+            // 创建失败时撤销新合约账户，但当前合约的nonce依然增加
+            let address = address_to_h160(Address::from_slice(contract.as_bytes()).create(1));
+            assert!(!evm.world_state.account_is_exsit(address));
+            assert_eq!(evm.world_state.get_nonce(contract).unwrap(), 2);
+            // init code执行REVERT不影响当前调用帧：REVERT(0, 0)
+            let (_, created) = create("60006000fd", 1_000_000);
+            assert!(created.is_zero());
+            // gas不足以支付512字节runtime code的部署费用：RETURN(0, 0x200)
+            let (_, created) = create("6102006000f3", 100_000);
+            assert!(created.is_zero());
+            let (evm, created) = create("6102006000f3", 1_000_000);
+            assert_eq!(evm.world_state.get_code(u256_to_h160(created)).unwrap().len(), 0x200);
+            // EIP-170: runtime code超出长度上限
+            let (_, created) = create("6160016000f3", 10_000_000);
+            assert!(created.is_zero());
+        }
+
+        #[test]
+        fn test_call_depth_limit() {
+            // MSTORE(0, CALL(gas, callee, 0, 0, 0, 0, 0)) MSTORE(32, CREATE(0, 0, 0)) RETURN(0, 64)
+            let code = format!("0x{}600052600060006000f060205260406000f3", call_op(0xf1, callee(), 0, 0));
+            // 最外层调用帧的深度为0，深度为1024的调用帧中CALL与CREATE均失败
+            for (depth, success) in [(1023, true), (1024, false)] {
+                let (mut evm, call) = setup(&code, 1_000_000);
+                insert_contract(&mut evm, callee(), "0x00");
+                evm.call_depth = depth;
+                let result = external_call(&mut evm, call).unwrap();
+                let output = result.output().unwrap();
+                assert_eq!(output[31] == 1, success, "CALL at depth {}", depth);
+                assert_eq!(output[32..].iter().any(|byte| *byte != 0), success, "CREATE at depth {}", depth);
+            }
+        }
+
+        #[test]
+        fn test_created_address_is_warm() {
+            // PUSH10 init_code PUSH1 0 MSTORE CREATE(0, 22, 10)，之后测量BALANCE(新合约地址)消耗的gas：
+            // GAS SWAP1 BALANCE POP GAS SWAP1 SUB
+            let code = format!("0x69602a6000526001601ff3600052600a60166000f05a9031505a9003{}", RETURN_TOP);
+            let (_, result) = execute(&code, 1_000_000);
+            // SWAP1 + warm BALANCE + POP + GAS
+            assert_eq!(U256::from_big_endian(result.output().unwrap()), U256::from(3 + 100 + 2 + 2));
+        }
+
+        #[test]
+        fn test_selfdestruct() {
+            let (_, contract) = test_accounts();
+            let beneficiary = H160::from_low_u64_be(0x1234);
+            let selfdestruct = |code: &str, spec_id: SpecId| {
+                let (mut evm, call) = setup(code, 1_000_000);
+                evm.spec_id = spec_id;
+                evm.world_state.set_balance(contract, U256::from(100)).unwrap();
+                assert!(external_call(&mut evm, call).unwrap().is_success());
+                evm
+            };
+            let balance = |evm: &EVM, address: H160| evm.world_state.get_balance(address).unwrap_or_default();
+            // SELFDESTRUCT(beneficiary)
+            let code = format!("0x73{:x}ff", beneficiary);
+
+            // EIP-6780: Cancun之后不是当前交易创建的合约只转移余额，不会被删除
+            let evm = selfdestruct(&code, SpecId::Cancun);
+            assert_eq!(balance(&evm, beneficiary), U256::from(100));
+            assert_eq!(balance(&evm, contract), U256::zero());
+            assert!(evm.world_state.get_code(contract).is_ok());
+            // Cancun之前合约在交易结束时删除
+            let evm = selfdestruct(&code, SpecId::Shanghai);
+            assert_eq!(balance(&evm, beneficiary), U256::from(100));
+            assert!(!evm.world_state.account_is_exsit(contract));
+
+            // 受益人为自身：Cancun之后余额不变，之前余额随合约一起销毁
+            let evm = selfdestruct("0x30ff", SpecId::Cancun);
+            assert_eq!(balance(&evm, contract), U256::from(100));
+            let evm = selfdestruct("0x30ff", SpecId::Shanghai);
+            assert!(!evm.world_state.account_is_exsit(contract));
+
+            // Cancun之后当前交易中创建的合约依然会被删除：CREATE执行SELFDESTRUCT(beneficiary)的init code
+            let evm = selfdestruct(&create_code(&code[2..], None), SpecId::Cancun);
+            let created = address_to_h160(Address::from_slice(contract.as_bytes()).create(1));
+            assert!(!evm.world_state.account_is_exsit(created));
+            assert!(evm.world_state.created_contracts.is_empty());
+
+            // 同一交易中两次调用已经SELFDESTRUCT的合约：合约直到交易结束才被删除，第二次转入的value同样转给受益人
+            let code = format!("0x{}50{}5000", call_op(0xf1, callee(), 0, 0), call_op(0xf1, callee(), 7, 0));
+            let (mut evm, call) = setup(&code, 1_000_000);
+            evm.spec_id = SpecId::Shanghai;
+            evm.world_state.set_balance(contract, U256::from(100)).unwrap();
+            insert_contract(&mut evm, callee(), &format!("0x73{:x}ff", beneficiary));
+            evm.world_state.set_balance(callee(), U256::from(50)).unwrap();
+            assert!(external_call(&mut evm, call).unwrap().is_success());
+            assert_eq!(balance(&evm, beneficiary), U256::from(57));
+            assert_eq!(balance(&evm, contract), U256::from(93));
+            assert!(!evm.world_state.account_is_exsit(callee()));
+        }
+    }
+
+    /// 预编译合约
+    mod precompile {
+        use super::*;
+
+        #[test]
+        fn test_call_precompile() {
+            // MSTORE(0, 0x2a) STATICCALL(gas, 0x04, 0, 32, 32, 32) 返回 memory[32..64] 以及调用结果
+            // PUSH1 0x2a PUSH1 0 MSTORE PUSH1 32 PUSH1 32 PUSH1 32 PUSH1 0 PUSH1 4 GAS STATICCALL PUSH1 64 MSTORE PUSH1 64 PUSH1 32 RETURN
+            let (evm, result) = execute("0x602a600052602060206020600060045afa60405260406020f3", 100_000);
+            let result = result.output().unwrap();
+            assert_eq!(result[31], 0x2a);
+            assert_eq!(result[63], 1);
+            // identity的费用为15 + 3 * 1，剩余的gas退还给调用者
+            assert!(evm.gas.spent() < 3000);
+        }
+
+        #[test]
+        fn test_custom_precompile() {
+            use crate::precompile::PrecompileOutput;
+            // STATICCALL(gas, 0x1000, 0, 0, 0, 32) 返回模拟预言机写入memory[0..32]的价格
+            // PUSH1 32 PUSH1 0 PUSH1 0 PUSH1 0 PUSH2 0x1000 GAS STATICCALL POP PUSH1 32 PUSH1 0 RETURN
+            let (mut evm, call) = setup("0x60206000600060006110005afa5060206000f3", 100_000);
+            evm.register_precompile(H160::from_low_u64_be(0x1000), |_: &[u8], gas_limit: u64| {
+                let mut price = vec![0u8; 32];
+                price[31] = 0x64;
+                if gas_limit < 500 {
+                    return Err(PrecompileError::OutOfGas);
+                }
+                Ok(PrecompileOutput::new(500, price))
+            });
+            let result = external_call(&mut evm, call).unwrap().output().unwrap().to_vec();
+            assert_eq!(result[31], 0x64);
+            // 自定义预编译合约地址在交易开始时即为warm
+            assert!(evm.accessed_addresses.contains(&H160::from_low_u64_be(0x1000)));
+        }
+
+        #[test]
+        fn test_precompiles_follow_spec() {
+            let point_evaluation = H160::from_low_u64_be(0x0a);
+            let custom = H160::from_low_u64_be(0x1000);
+            let mut evm = EVM::new(WorldState::default());
+            assert!(evm.precompiles().contains(&point_evaluation));
+            evm.register_precompile(custom, |_: &[u8], _: u64| Err(PrecompileError::OutOfGas));
+            // 切换硬分叉时重新构建，自定义预编译合约保留
+            evm.set_spec_id(SpecId::Shanghai);
+            assert!(!evm.precompiles().contains(&point_evaluation));
+            assert!(evm.precompiles().contains(&custom));
+            // 直接修改spec_id时在交易开始时更新
+            evm.spec_id = SpecId::Cancun;
+            evm.warm_transaction_accesses(None);
+            assert!(evm.precompiles().contains(&point_evaluation));
+            assert!(evm.precompiles().contains(&custom));
+        }
+    }
+}
@@ -1,11 +1,13 @@
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt;
 use std::fmt::{Display, Formatter};
 use std::str::FromStr;
 use ethers::types::{Bytes};
-use primitive_types::{U256, H160, H256};
+use primitive_types::{U256, U512, H160, H256};
 use serde::{Deserialize, Serialize};
 use crate::error::exit::*;
+use crate::spec::SpecId;
+use crate::journal::{JournalCheckpoint, JournalEntry};
 
 #[derive(Debug, Clone)]
 pub struct Block {
@@ -17,6 +19,52 @@ pub struct Block {
     pub gas_limit: U256,
     pub chainid: usize,
     pub basefee: usize,
+    /// EIP-4844: 区块的excess blob gas，Cancun之前的区块为None
+    pub excess_blob_gas: Option<u64>,
+}
+
+/// EIP-4844: blob gas的最低价格
+pub const MIN_BLOB_GASPRICE: u64 = 1;
+/// EIP-4844: blob base fee的更新系数，EIP-7691在Prague中将其调大
+pub const BLOB_BASE_FEE_UPDATE_FRACTION_CANCUN: u64 = 3_338_477;
+pub const BLOB_BASE_FEE_UPDATE_FRACTION_PRAGUE: u64 = 5_007_716;
+
+impl Block {
+    /// EIP-4844: 根据excess blob gas计算blob base fee
+    pub fn blob_basefee(&self, spec: SpecId) -> U256 {
+        let fraction = if spec.is_enabled_in(SpecId::Prague) {
+            BLOB_BASE_FEE_UPDATE_FRACTION_PRAGUE
+        } else {
+            BLOB_BASE_FEE_UPDATE_FRACTION_CANCUN
+        };
+        fake_exponential(
+            U256::from(MIN_BLOB_GASPRICE),
+            U256::from(self.excess_blob_gas.unwrap_or_default()),
+            U256::from(fraction),
+        )
+    }
+}
+
+/// EIP-4844: 使用泰勒展开近似计算 factor * e ** (numerator / denominator)
+/// 中间结果使用U512计算，结果超出U256范围时返回U256::MAX
+pub fn fake_exponential(factor: U256, numerator: U256, denominator: U256) -> U256 {
+    let numerator = U512::from(numerator);
+    let denominator = U512::from(denominator);
+    let mut i = U512::one();
+    let mut output = U512::zero();
+    let mut numerator_accum = U512::from(factor) * denominator;
+    while !numerator_accum.is_zero() {
+        output = match output.checked_add(numerator_accum) {
+            Some(output) => output,
+            None => return U256::MAX,
+        };
+        numerator_accum = match numerator_accum.checked_mul(numerator) {
+            Some(product) => product / (denominator * i),
+            None => return U256::MAX,
+        };
+        i += U512::one();
+    }
+    U256::try_from(output / denominator).unwrap_or(U256::MAX)
 }
 
 #[derive(Debug, Clone)]
@@ -34,7 +82,8 @@ pub struct Call {
     pub call_type: CallType,
     pub call_depth: usize,
     pub pc: usize,
-    pub world_state: WorldState,
+    // 是否处于STATICCALL发起的调用中，子调用继承该标记，此时不允许修改状态
+    pub is_static: bool,
 }
 
 impl fmt::Display for Call {
@@ -81,7 +130,7 @@ pub enum CallType {
     Create2,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct AccountState {
     pub nonce: usize,
     pub balance: U256,
@@ -140,21 +189,78 @@ impl fmt::Display for AccountState {
     }
 }
 
+/// 全局状态，所有通过WorldState方法进行的修改都会记录到状态修改日志中，调用帧revert或异常中止时据此回滚
+/// 直接修改state字段不会被记录
 #[derive(Debug, Clone)]
 pub struct WorldState {
     pub state: HashMap<H160, AccountState>,
+    pub(crate) journal: Vec<JournalEntry>,
+    /// 当前交易中创建的合约，EIP-6780之后只有这些合约会被SELFDESTRUCT删除
+    pub(crate) created_contracts: HashSet<H160>,
+    /// 当前交易中执行过SELFDESTRUCT的合约，交易结束时删除
+    pub(crate) selfdestructs: HashSet<H160>,
 }
 
 impl WorldState {
     pub fn new(state: HashMap<H160,AccountState>) -> Self {
         Self{
-            state
+            state,
+            journal: Vec::new(),
+            created_contracts: HashSet::new(),
+            selfdestructs: HashSet::new(),
         }
     }
 
     pub fn default() -> Self {
         Self{
             state: Default::default(),
+            journal: Vec::new(),
+            created_contracts: HashSet::new(),
+            selfdestructs: HashSet::new(),
+        }
+    }
+
+    /// 当前状态修改日志的位置
+    pub fn checkpoint(&self) -> JournalCheckpoint {
+        JournalCheckpoint(self.journal.len())
+    }
+
+    /// 取出checkpoint之后记录的修改，由调用者按相反顺序撤销
+    pub(crate) fn take_journal(&mut self, checkpoint: JournalCheckpoint) -> Vec<JournalEntry> {
+        self.journal.split_off(checkpoint.0.min(self.journal.len()))
+    }
+
+    /// 交易结束时提交全部修改，之后无法再回滚
+    pub fn commit(&mut self) {
+        self.journal.clear();
+        self.created_contracts.clear();
+        self.selfdestructs.clear();
+    }
+
+    /// 记录当前交易中创建的合约
+    pub fn mark_created(&mut self, address: H160) {
+        if self.created_contracts.insert(address) {
+            self.journal.push(JournalEntry::ContractCreated { address });
+        }
+    }
+
+    pub fn is_created_in_transaction(&self, address: H160) -> bool {
+        self.created_contracts.contains(&address)
+    }
+
+    /// 标记合约执行了SELFDESTRUCT，返回该合约在当前交易中是否已经执行过SELFDESTRUCT
+    pub fn mark_selfdestruct(&mut self, address: H160) -> bool {
+        let inserted = self.selfdestructs.insert(address);
+        if inserted {
+            self.journal.push(JournalEntry::SelfDestruct { address });
+        }
+        !inserted
+    }
+
+    /// 交易结束时删除执行过SELFDESTRUCT的合约
+    pub fn destroy_selfdestructs(&mut self) {
+        for address in std::mem::take(&mut self.selfdestructs) {
+            self.remove_account(address);
         }
     }
 
@@ -226,27 +332,20 @@ impl WorldState {
         let state = self.state.get_mut(&address);
         match state {
             // 如果账户状态存在
-            Some(accountState) => match accountState.storage.as_mut() {
-                // 如果该账户的storage存在
-                Some(storage) => {
-                    storage.insert(key, value);
-                    Ok(())
+            Some(accountState) => {
+                // EOA没有storage，EIP-7702委托账户执行被委托的code第一次写入存储时创建
+                if accountState.storage.is_none() {
+                    accountState.storage = Some(BTreeMap::new());
+                    self.journal.push(JournalEntry::StorageCreated { address });
                 }
-                // storage不存在意味着不是合约
-                None => { Err(Box::new(EVMError::NoContract(address))) }
+                let previous = accountState.storage.as_mut().unwrap().insert(key, value);
+                self.journal.push(JournalEntry::StorageChange { address, key, previous });
+                Ok(())
             }
             None => {
                 // 没有该地址则创建新的账户状态
-                let _ = &self.state.insert(
-                    address,
-                    AccountState {
-                        balance: U256::zero(),
-                        code_hash: Some(H256::default()),
-                        nonce: 0,
-                        storage: Some(BTreeMap::new()),
-                        code: Some(Bytes::new()),
-                    },
-                );
+                self.new_account(address, AccountState::default());
+                self.journal.push(JournalEntry::StorageChange { address, key, previous: None });
                 self.state
                     .get_mut(&address)
                     .unwrap()
@@ -264,8 +363,9 @@ impl WorldState {
         match state {
             Some(accountState) => match accountState.storage.as_ref() {
                 Some(storage) => {
-                    let storage_value = storage.get(&key).unwrap();
-                    Ok(*storage_value)
+                    // 未写入过的存储槽值为0
+                    let storage_value = storage.get(&key).copied().unwrap_or_default();
+                    Ok(storage_value)
                 }
                 // storage不存在意味着不是合约
                 None => { Err(Box::new(EVMError::NoContract(address))) }
@@ -279,7 +379,8 @@ impl WorldState {
         let state = self.state.get_mut(&address);
         match state {
             Some(accountState) => {
-                accountState.balance = value;
+                let previous = std::mem::replace(&mut accountState.balance, value);
+                self.journal.push(JournalEntry::BalanceChange { address, previous });
                 Ok(())
             }
             None => { Err(Box::new(EVMError::AddressNotFound(address))) }
@@ -290,6 +391,7 @@ impl WorldState {
         let state = self.state.get_mut(&address);
         match state {
             Some(accountState) => {
+                self.journal.push(JournalEntry::BalanceChange { address, previous: accountState.balance });
                 accountState.balance += value;
             }
             None => {}
@@ -300,44 +402,77 @@ impl WorldState {
         let state = self.state.get_mut(&address);
         match state {
             Some(accountState) => {
+                self.journal.push(JournalEntry::BalanceChange { address, previous: accountState.balance });
                 accountState.balance -= value;
             }
             None => {}
         }
     }
 
+    /// 转账，余额不足时返回OutOfFund；接收者不存在时创建该账户，所有修改都记录到状态修改日志中
+    pub fn transfer(&mut self, from: H160, to: H160, value: U256) -> Result<(), Box<dyn ExitError>> {
+        if self.get_balance(from)? < value {
+            return Err(Box::new(OpcodeExecutionError::OutOfFund));
+        }
+        if !self.account_is_exsit(to) {
+            self.new_account(to, AccountState::new_eoa(0, U256::zero()));
+        }
+        self.sub_balance(from, value);
+        self.add_balance(to, value);
+        Ok(())
+    }
+
     pub fn account_is_exsit(&self, address: H160) -> bool {
         self.state.get(&address).is_some()
     }
 
+    /// EIP-161: nonce、余额为0且没有code的账户视为空账户，不存在的账户同样视为空账户
+    pub fn account_is_empty(&self, address: H160) -> bool {
+        match self.state.get(&address) {
+            None => true,
+            Some(account) => {
+                account.nonce == 0
+                    && account.balance.is_zero()
+                    && account.code.as_ref().map_or(true, |code| code.is_empty())
+            }
+        }
+    }
+
+    pub fn increment_nonce(&mut self, address: H160) -> Result<(), Box<dyn ExitError>> {
+        match self.state.get_mut(&address) {
+            Some(accountState) => {
+                self.journal.push(JournalEntry::NonceChange { address, previous: accountState.nonce });
+                accountState.nonce += 1;
+                Ok(())
+            }
+            None => { Err(Box::new(EVMError::AddressNotFound(address))) }
+        }
+    }
+
     pub fn default_sender(&mut self) -> H160 {
         let addr = H160::random();
-        self.state.insert(
-            addr,
-            AccountState {
-                balance: U256::zero(),
-                code_hash: Some(H256::default()),
-                nonce: 0,
-                storage: Some(BTreeMap::new()),
-                code: Some(Bytes::new()),
-            },
-        );
+        self.new_account(addr, AccountState::default());
         addr
     }
     pub fn insert_code(&mut self, address: H160, code: Bytes) {
         let account = self.state.get_mut(&address).unwrap();
-        account.code = Some(code);
+        let previous = account.code.replace(code);
+        self.journal.push(JournalEntry::CodeChange { address, previous });
     }
 
     pub fn insert_codehash(&mut self, address: H160, code_hash: H256) {
         let account = self.state.get_mut(&address).unwrap();
-        account.code_hash = Some(code_hash);
+        let previous = account.code_hash.replace(code_hash);
+        self.journal.push(JournalEntry::CodeHashChange { address, previous });
     }
     pub fn new_account(&mut self, address: H160, account: AccountState) {
-        self.state.insert(address, account);
+        let previous = self.state.insert(address, account);
+        self.journal.push(JournalEntry::AccountCreated { address, previous });
     }
     pub fn remove_account(&mut self, address: H160) {
-        self.state.remove(&address);
+        if let Some(account) = self.state.remove(&address) {
+            self.journal.push(JournalEntry::AccountDestroyed { address, account });
+        }
     }
 }
 
@@ -360,6 +495,49 @@ impl fmt::Display for WorldState {
 #[cfg(test)]
 mod tests {
     use super::*;
+    #[test]
+    fn test_fake_exponential() {
+        let cases: [(u64, u64, u64, u64); 8] = [
+            (1, 0, 1, 1),
+            (38493, 0, 1000, 38493),
+            (0, 1234, 2345, 0),
+            (1, 2, 1, 6),
+            (1, 3, 1, 16),
+            (1, 8, 2, 50),
+            (10, 8, 2, 542),
+            (2, 5, 2, 23),
+        ];
+        for (factor, numerator, denominator, expected) in cases {
+            assert_eq!(
+                fake_exponential(U256::from(factor), U256::from(numerator), U256::from(denominator)),
+                U256::from(expected)
+            );
+        }
+    }
+
+    #[test]
+    fn test_fake_exponential_large_excess_blob_gas() {
+        let fraction = U256::from(BLOB_BASE_FEE_UPDATE_FRACTION_CANCUN);
+        // e^170仍在U256范围内，但中间结果超出U256
+        let expected = U256::from_dec_str("67617938104833606573590167707559218112182902966974536048111229090553346521").unwrap();
+        assert_eq!(fake_exponential(U256::one(), fraction * 170, fraction), expected);
+        // 结果超出U256范围时取U256::MAX
+        assert_eq!(fake_exponential(U256::one(), fraction * 178, fraction), U256::MAX);
+        let block = Block {
+            blockhash: H256::zero(),
+            coinbase: H160::zero(),
+            timestamp: 0,
+            number: 0,
+            prevrandao: H256::zero(),
+            gas_limit: U256::zero(),
+            chainid: 1,
+            basefee: 0,
+            excess_blob_gas: Some(u64::MAX),
+        };
+        assert_eq!(block.blob_basefee(SpecId::Cancun), U256::MAX);
+        assert_eq!(block.blob_basefee(SpecId::Prague), U256::MAX);
+    }
+
     #[test]
     fn test_create_account_state() {
         let account = AccountState::default();
@@ -0,0 +1,186 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use ethers::types::Bytes;
+use primitive_types::{H160, H256, U256};
+use crate::globalState::{AccountState, WorldState};
+use crate::opcode::enviroment::Log;
+
+/// 状态修改日志中的一项，记录被修改之前的值，回滚时按照与修改相反的顺序恢复
+#[derive(Debug, Clone, PartialEq)]
+pub enum JournalEntry {
+    /// 创建账户，previous为被覆盖的账户，原本不存在时为None
+    AccountCreated { address: H160, previous: Option<AccountState> },
+    /// 删除账户
+    AccountDestroyed { address: H160, account: AccountState },
+    BalanceChange { address: H160, previous: U256 },
+    NonceChange { address: H160, previous: usize },
+    /// 存储槽修改，previous为None表示该存储槽之前没有写入过
+    StorageChange { address: H160, key: H256, previous: Option<H256> },
+    /// 为没有storage的账户创建storage
+    StorageCreated { address: H160 },
+    CodeChange { address: H160, previous: Option<Bytes> },
+    CodeHashChange { address: H160, previous: Option<H256> },
+    /// EIP-1153: 瞬态存储修改
+    TransientStorageChange { address: H160, key: H256, previous: Option<H256> },
+    /// 记录了一条日志
+    Log,
+    /// 当前交易中创建了合约
+    ContractCreated { address: H160 },
+    /// 合约第一次执行SELFDESTRUCT
+    SelfDestruct { address: H160 },
+    /// EIP-2929: 地址第一次被访问，由cold变为warm
+    AccountWarmed { address: H160 },
+    /// EIP-2929: 存储槽第一次被访问，由cold变为warm
+    StorageWarmed { address: H160, key: H256 },
+}
+
+/// 调用帧开始时状态修改日志的位置，回滚到该位置即撤销调用帧产生的全部修改
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JournalCheckpoint(pub(crate) usize);
+
+impl JournalEntry {
+    /// 撤销该项修改，直接操作底层状态，撤销操作本身不会被记录
+    pub fn revert(
+        self,
+        world_state: &mut WorldState,
+        transient_storage: &mut HashMap<H160, BTreeMap<H256, H256>>,
+        logs: &mut Vec<Log>,
+        accessed_addresses: &mut HashSet<H160>,
+        accessed_storage_keys: &mut HashSet<(H160, H256)>,
+    ) {
+        match self {
+            JournalEntry::AccountCreated { address, previous } => {
+                match previous {
+                    Some(account) => { world_state.state.insert(address, account); }
+                    None => { world_state.state.remove(&address); }
+                }
+            }
+            JournalEntry::AccountDestroyed { address, account } => {
+                world_state.state.insert(address, account);
+            }
+            JournalEntry::BalanceChange { address, previous } => {
+                if let Some(account) = world_state.state.get_mut(&address) {
+                    account.balance = previous;
+                }
+            }
+            JournalEntry::NonceChange { address, previous } => {
+                if let Some(account) = world_state.state.get_mut(&address) {
+                    account.nonce = previous;
+                }
+            }
+            JournalEntry::StorageChange { address, key, previous } => {
+                if let Some(storage) = world_state.state.get_mut(&address).and_then(|account| account.storage.as_mut()) {
+                    match previous {
+                        Some(value) => { storage.insert(key, value); }
+                        None => { storage.remove(&key); }
+                    }
+                }
+            }
+            JournalEntry::StorageCreated { address } => {
+                if let Some(account) = world_state.state.get_mut(&address) {
+                    account.storage = None;
+                }
+            }
+            JournalEntry::CodeChange { address, previous } => {
+                if let Some(account) = world_state.state.get_mut(&address) {
+                    account.code = previous;
+                }
+            }
+            JournalEntry::CodeHashChange { address, previous } => {
+                if let Some(account) = world_state.state.get_mut(&address) {
+                    account.code_hash = previous;
+                }
+            }
+            JournalEntry::TransientStorageChange { address, key, previous } => {
+                let storage = transient_storage.entry(address).or_default();
+                match previous {
+                    Some(value) => { storage.insert(key, value); }
+                    None => { storage.remove(&key); }
+                }
+                if storage.is_empty() {
+                    transient_storage.remove(&address);
+                }
+            }
+            JournalEntry::Log => {
+                logs.pop();
+            }
+            JournalEntry::ContractCreated { address } => {
+                world_state.created_contracts.remove(&address);
+            }
+            JournalEntry::SelfDestruct { address } => {
+                world_state.selfdestructs.remove(&address);
+            }
+            JournalEntry::AccountWarmed { address } => {
+                accessed_addresses.remove(&address);
+            }
+            JournalEntry::StorageWarmed { address, key } => {
+                accessed_storage_keys.remove(&(address, key));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evm::EVM;
+
+    #[test]
+    fn test_revert_to_checkpoint() {
+        let user = H160::from_low_u64_be(1);
+        let contract = H160::from_low_u64_be(2);
+        let mut world_state = WorldState::default();
+        world_state.new_account(user, AccountState::new_eoa(0, U256::from(100)));
+        world_state.new_account(contract, AccountState::new_contract(1, U256::zero(), H256::default(), Default::default(), Bytes::new()));
+        world_state.insert_storage_value(contract, H256::zero(), H256::from_low_u64_be(1)).unwrap();
+        let mut evm = EVM::new(world_state);
+        let before = evm.world_state.state.clone();
+
+        let checkpoint = evm.checkpoint();
+        evm.world_state.sub_balance(user, U256::from(40));
+        evm.world_state.add_balance(contract, U256::from(40));
+        evm.world_state.increment_nonce(user).unwrap();
+        evm.world_state.insert_storage_value(contract, H256::zero(), H256::from_low_u64_be(2)).unwrap();
+        evm.world_state.insert_storage_value(contract, H256::from_low_u64_be(1), H256::from_low_u64_be(3)).unwrap();
+        // EOA第一次写入存储时创建storage
+        evm.world_state.insert_storage_value(user, H256::zero(), H256::from_low_u64_be(4)).unwrap();
+        evm.world_state.insert_code(contract, Bytes::from(vec![0x00]));
+        evm.world_state.new_account(H160::from_low_u64_be(3), AccountState::default());
+        evm.world_state.remove_account(user);
+        evm.set_transient_storage(contract, H256::zero(), H256::from_low_u64_be(7));
+        evm.push_log(Log { address: contract, topics: Vec::new(), data: Vec::new() });
+        evm.world_state.mark_created(H160::from_low_u64_be(3));
+        assert!(!evm.world_state.mark_selfdestruct(contract));
+        assert!(evm.world_state.mark_selfdestruct(contract));
+        assert!(evm.access_address(contract));
+        assert!(evm.access_storage_key(contract, H256::zero()));
+        assert_ne!(evm.world_state.state, before);
+
+        evm.revert_to(checkpoint);
+        assert_eq!(evm.world_state.state, before);
+        assert!(evm.transient_storage.is_empty());
+        assert!(evm.logs.is_empty());
+        assert!(evm.world_state.created_contracts.is_empty());
+        assert!(evm.world_state.selfdestructs.is_empty());
+        // 调用帧中第一次访问的地址和存储槽恢复为cold
+        assert!(!evm.accessed_addresses.contains(&contract));
+        assert!(!evm.accessed_storage_keys.contains(&(contract, H256::zero())));
+    }
+
+    #[test]
+    fn test_nested_checkpoints() {
+        let user = H160::from_low_u64_be(1);
+        let mut world_state = WorldState::default();
+        world_state.new_account(user, AccountState::new_eoa(0, U256::from(100)));
+        let mut evm = EVM::new(world_state);
+
+        let outer = evm.checkpoint();
+        evm.world_state.set_balance(user, U256::from(50)).unwrap();
+        let inner = evm.checkpoint();
+        evm.world_state.set_balance(user, U256::from(10)).unwrap();
+        // 只回滚内层调用帧的修改
+        evm.revert_to(inner);
+        assert_eq!(evm.world_state.get_balance(user).unwrap(), U256::from(50));
+        evm.revert_to(outer);
+        assert_eq!(evm.world_state.get_balance(user).unwrap(), U256::from(100));
+    }
+}
@@ -5,114 +5,401 @@ pub mod globalState;
 pub mod evm;
 pub mod utils;
 pub mod tracer;
+pub mod spec;
+pub mod precompile;
+pub mod journal;
+pub mod result;
+pub mod receipt;
+pub mod transaction;
+pub mod block;
 
-use std::collections::HashMap;
-use std::{env, process};
+use std::collections::{BTreeMap, HashMap};
+use std::env;
 use std::str::FromStr;
 use std::sync::Arc;
 use dotenv::dotenv;
 pub use error::exit::*;
 pub use machine::Stack::Stack;
 pub use machine::Memory::Memory;
+pub use machine::Gas::Gas;
+pub use spec::SpecId;
+pub use precompile::{Precompile, PrecompileOutput, PrecompileResult, Precompiles};
+pub use result::ExecutionResult;
+pub use receipt::Receipt;
+pub use transaction::{Transaction, TxEnv};
+pub use block::{BlockReplay, BlockResult, StateMismatch, TxOutcome, TxReplay};
 pub use globalState::*;
-use ethers::types::{Selector, Bytes, Transaction, TxHash};
+use ethers::types::{Selector, Bytes};
 use primitive_types::{H160, H256, U256};
-use ethers::prelude::{Http, Provider, ProviderExt};
+use ethers::prelude::{Http, Middleware, Provider, ProviderExt};
 use crate::evm::EVM;
-use crate::tracer::getAccountState::{get_accounts_state_tx, ISDiff};
-use crate::tracer::getTransaction::get_transaction_content;
+use crate::tracer::getAccountState::{get_accounts_state_tx, AccountStateEx, ISDiff};
+use crate::block::compare_post_state;
+use crate::tracer::getTransaction::{get_transaction_content, get_transaction_receipt, TransactionEnv};
 use crate::utils::u256_to_h256;
+use crate::journal::JournalCheckpoint;
+use crate::machine::Gas::{intrinsic_gas, PER_AUTH_BASE_COST, PER_EMPTY_ACCOUNT_COST};
+use crate::transaction::{delegation_address, delegation_code, Authorization};
+use ethers::utils::keccak256;
 
-pub fn deploy(evm: &mut EVM, bytecode: Bytes, caller: H160, value: U256) -> H160 {
+/// 部署合约，返回合约地址
+pub fn deploy(evm: &mut EVM, bytecode: Bytes, caller: H160, value: U256) -> Result<H160, Box<dyn ExitError>> {
     // 预备部署状态
+    evm.refresh_precompiles();
     evm.bytecode = Some(bytecode);
     evm.is_constructor = true;
-    let target_address = evm.deploy_contract(caller, value);
-    let to = target_address.unwrap_or_else(|error|{
-        println!("depoly error :{:?}", error);
-        process::exit(1);
-    });
-    to
+    evm.deploy_contract(caller, value)
 }
 
 /// 该函数用于最外层的函数调用(本地交易)
-pub fn external_call(evm: &mut EVM, call:Call) -> Result<Option<Vec<u8>>, Box<dyn ExitError>> {
+/// 交易的执行结果通过ExecutionResult返回，只有交易无法开始执行(如接收者不是合约)时才返回错误
+pub fn external_call(evm: &mut EVM, call:Call) -> Result<ExecutionResult, Box<dyn ExitError>> {
+    if !evm.call_stack.is_empty() {
+        return Err(Box::new(EVMError::Error));
+    }
+    let to = call.to.ok_or(Box::new(EVMError::Error) as Box<dyn ExitError>)?;
+    let bytecode = evm.world_state.get_code(to)?;
     // 更新evm状态
     evm.origin = call.from;
-    let bytecode = match evm.world_state.get_code(call.to.unwrap()){
-        Ok(bytecode) => bytecode,
-        Err(e) => {
-            println!("encounter error: {:?}", e);
-            process::exit(1);
-        }
-    };
-    evm.pc = 0;
     evm.bytecode = Some(bytecode);
+    reset_transaction(evm, call.to);
+    let checkpoint = evm.checkpoint();
+    evm.call_stack.push(call);
+
+    // 执行
+    let result = evm.interepter();
+    evm.call_stack.clear();
+    Ok(finish_transaction(evm, result, checkpoint, 0))
+}
+
+/// 交易开始执行之前重置EVM中交易级别的状态
+fn reset_transaction(evm: &mut EVM, to: Option<H160>) {
+    evm.pc = 0;
     evm.is_constructor = false;
+    evm.is_revert = false;
+    evm.return_data = None;
     evm.sub_return_data = None;
+    evm.logs = Vec::new();
     evm.transient_storage = HashMap::new();
+    evm.original_storage = HashMap::new();
     evm.stack = Stack::new(1024);
     evm.memory = Memory::new(1024);
-    evm.call_stack.push(call);
+    evm.gas = Gas::new(evm.gas_limit);
+    evm.gas_stack = Vec::new();
+    evm.warm_transaction_accesses(to);
+    evm.world_state.commit();
+}
+
+/// 交易执行结束：撤销revert或异常中止的交易对全局状态的修改，删除执行过SELFDESTRUCT的合约并提交，根据执行结果计算gas消耗
+/// authorization_refund为EIP-7702授权产生的退款，与执行结果无关
+fn finish_transaction(evm: &mut EVM, result: Result<(), Box<dyn ExitError>>, checkpoint: JournalCheckpoint, authorization_refund: i64) -> ExecutionResult {
+    // 交易revert或异常中止时撤销其对全局状态的全部修改
+    if result.is_err() || evm.is_revert {
+        evm.revert_to(checkpoint);
+    }
+    // 执行过SELFDESTRUCT的合约在交易结束时删除
+    evm.world_state.destroy_selfdestructs();
+    evm.world_state.commit();
+    // EIP-1153: 瞬态存储在交易结束时清空
+    evm.transient_storage = HashMap::new();
+    let output = evm.return_data.clone().unwrap_or_default();
+    // 被revert或异常中止的交易不会获得执行中产生的退款，授权的退款依然保留
+    if result.is_err() || evm.is_revert {
+        evm.gas.clear_refund();
+    }
+    evm.gas.record_refund(authorization_refund);
+    // 交易结束时按照当前硬分叉的退款上限计算最终退款
+    evm.gas.set_final_refund(evm.spec_id.max_refund_quotient());
+    let gas_used = evm.gas.spent_sub_refunded();
+    match result {
+        Ok(_) if evm.is_revert => ExecutionResult::Revert { output, gas_used },
+        Ok(_) => ExecutionResult::Success { output, gas_used, logs: evm.logs.clone() },
+        // gas耗尽等异常中止
+        Err(e) => ExecutionResult::halt(e, gas_used),
+    }
+}
 
-    if evm.call_stack.len() != 1 {
-        println!("call stack error");
-        process::exit(1);
+/// 执行一笔完整的交易：校验发送者的nonce和余额，预先购买gas并收取固有gas，执行调用或者创建合约，
+/// 结束后退还剩余gas，将小费支付给区块的coinbase并销毁base fee
+/// 交易校验失败时返回InvalidTransaction，且不修改任何状态
+pub fn transact(evm: &mut EVM, tx: TxEnv) -> Result<ExecutionResult, Box<dyn ExitError>> {
+    if !evm.call_stack.is_empty() {
+        return Err(Box::new(EVMError::Error));
     }
+    let transaction = &tx.transaction;
+    let basefee = evm.block.as_ref().map_or(U256::zero(), |block| U256::from(block.basefee));
+    let blob_basefee = evm.block.as_ref().map_or(U256::from(MIN_BLOB_GASPRICE), |block| block.blob_basefee(evm.spec_id));
+    let intrinsic = validate_transaction(evm, &tx, basefee, blob_basefee)?;
+    let gas_price = transaction.effective_gas_price(basefee);
+    let gas_limit = transaction.gas_limit();
+
+    // 预先购买gas并增加发送者nonce，这些修改不会因为交易revert而撤销
+    evm.world_state.commit();
+    let gas_cost = U256::from(gas_limit) * gas_price + U256::from(transaction.blob_gas()) * blob_basefee;
+    evm.world_state.sub_balance(tx.caller, gas_cost);
+    evm.world_state.increment_nonce(tx.caller)?;
+    let create_address = tx.create_address();
+
+    // 更新evm状态，固有gas在执行之前从gas_limit中扣除
+    evm.origin = tx.caller;
+    evm.gas_limit = gas_limit;
+    evm.access_list = transaction.access_list().clone();
+    evm.blob_hashes = transaction.blob_versioned_hashes().to_vec();
+    evm.transaction = Some(transaction.clone());
+    evm.bytecode = None;
+    reset_transaction(evm, transaction.to().or(create_address));
+    evm.gas.record_cost(intrinsic)?;
+    let authorization_refund = apply_authorizations(evm, transaction.authorization_list())?;
+    let checkpoint = evm.checkpoint();
+
     // 执行
-    match evm.interepter(){
-        Ok(_) => {
-            if evm.return_data.is_some() {
-                Ok(evm.return_data.clone())
-            }
-            else {
-                Ok(None)
+    let result = match create_address {
+        Some(address) => transact_create(evm, &tx, address),
+        None => transact_call(evm, &tx),
+    };
+    evm.call_stack.clear();
+    let execution_result = finish_transaction(evm, result, checkpoint, authorization_refund);
+
+    // 退还剩余gas，London之后base fee被销毁，coinbase只获得小费
+    let gas_used = execution_result.gas_used();
+    evm.world_state.add_balance(tx.caller, U256::from(gas_limit - gas_used) * gas_price);
+    let coinbase_gas_price = if evm.spec_id.is_enabled_in(SpecId::London) { gas_price - basefee } else { gas_price };
+    let reward = U256::from(gas_used) * coinbase_gas_price;
+    if let Some(block) = &evm.block {
+        // EIP-161: 不为空账户创建状态
+        if !reward.is_zero() {
+            let coinbase = block.coinbase;
+            if !evm.world_state.account_is_exsit(coinbase) {
+                evm.world_state.new_account(coinbase, AccountState::new_eoa(0, U256::zero()));
             }
+            evm.world_state.add_balance(coinbase, reward);
+        }
+    }
+    evm.world_state.commit();
+    Ok(execution_result)
+}
+
+/// 在同一个全局状态上按顺序执行区块中的交易，返回每笔交易的执行结果与收据以及执行后的全局状态
+/// 校验失败的交易被跳过，不影响后续交易的执行
+pub fn execute_block(evm: &mut EVM, block: Block, transactions: Vec<TxEnv>) -> BlockResult {
+    evm.block = Some(block);
+    let mut gas_used = 0;
+    let outcomes = transactions.into_iter()
+        .map(|tx| execute_block_transaction(evm, tx, &mut gas_used))
+        .collect();
+    BlockResult { transactions: outcomes, gas_used, post_state: evm.world_state.state.clone() }
+}
+
+/// 执行区块中的一笔交易并生成收据，gas_used为区块中之前的交易累计消耗的gas，交易执行后更新
+fn execute_block_transaction(evm: &mut EVM, tx: TxEnv, gas_used: &mut u64) -> Result<TxOutcome, Box<dyn ExitError>> {
+    let block_gas_limit = evm.block.as_ref().map_or(U256::MAX, |block| block.gas_limit);
+    // 区块剩余的gas不足以支付交易的gas上限
+    if U256::from(*gas_used) + U256::from(tx.transaction.gas_limit()) > block_gas_limit {
+        return Err(Box::new(InvalidTransaction::CallerGasLimitMoreThanBlock));
+    }
+    let tx_type = tx.transaction.tx_type();
+    let contract_address = tx.create_address();
+    let result = transact(evm, tx)?;
+    let receipt = Receipt::new(tx_type, &result, *gas_used, contract_address);
+    *gas_used = receipt.cumulative_gas_used;
+    Ok(TxOutcome { result, receipt })
+}
+
+/// 交易执行之前的校验，返回交易的固有gas
+fn validate_transaction(evm: &EVM, tx: &TxEnv, basefee: U256, blob_basefee: U256) -> Result<u64, Box<dyn ExitError>> {
+    let transaction = &tx.transaction;
+    let chain_id = evm.block.as_ref().map(|block| block.chainid as u64);
+    transaction.validate(evm.spec_id, chain_id, basefee, blob_basefee).map_err(|e| Box::new(e) as Box<dyn ExitError>)?;
+    let gas_limit = transaction.gas_limit();
+    if let Some(block) = &evm.block {
+        if U256::from(gas_limit) > block.gas_limit {
+            return Err(Box::new(InvalidTransaction::CallerGasLimitMoreThanBlock));
+        }
+    }
+    let is_create = transaction.to().is_none();
+    if is_create && evm.check_initcode_size(transaction.data()).is_err() {
+        return Err(Box::new(InvalidTransaction::CreateInitCodeSizeLimit));
+    }
+    let authorization_count = transaction.authorization_list().len() as u64;
+    let intrinsic = intrinsic_gas(transaction.data(), is_create, transaction.access_list(), authorization_count, evm.spec_id);
+    if intrinsic > gas_limit {
+        return Err(Box::new(InvalidTransaction::CallGasCostMoreThanGasLimit { intrinsic, gas_limit }));
+    }
+
+    // EIP-3607: 拒绝由部署了code的账户发起的交易，EIP-7702的委托账户除外
+    if evm.world_state.get_code(tx.caller).is_ok_and(|code| !code.is_empty() && delegation_address(&code).is_none()) {
+        return Err(Box::new(InvalidTransaction::RejectCallerWithCode));
+    }
+    let state_nonce = evm.world_state.get_nonce(tx.caller).unwrap_or_default() as u64;
+    if transaction.nonce() < state_nonce {
+        return Err(Box::new(InvalidTransaction::NonceTooLow { tx: transaction.nonce(), state: state_nonce }));
+    }
+    if transaction.nonce() > state_nonce {
+        return Err(Box::new(InvalidTransaction::NonceTooHigh { tx: transaction.nonce(), state: state_nonce }));
+    }
+    // 发送者余额需要足以按照gas价格上限支付全部gas以及转账金额
+    let max_blob_fee = U256::from(transaction.blob_gas()).saturating_mul(transaction.max_fee_per_blob_gas());
+    let fee = U256::from(gas_limit)
+        .saturating_mul(transaction.max_fee_per_gas())
+        .saturating_add(max_blob_fee)
+        .saturating_add(transaction.value());
+    let balance = evm.world_state.get_balance(tx.caller).unwrap_or_default();
+    if balance < fee {
+        return Err(Box::new(InvalidTransaction::LackOfFundForMaxFee { fee, balance }));
+    }
+    Ok(intrinsic)
+}
+
+/// EIP-7702: 依次处理授权列表，为授权账户设置委托code并增加其nonce，返回授权账户已经存在时的退款
+/// 无效的授权直接跳过，授权的修改以及退款不会因为交易revert而撤销
+fn apply_authorizations(evm: &mut EVM, authorization_list: &[Authorization]) -> Result<i64, Box<dyn ExitError>> {
+    let chain_id = evm.block.as_ref().map_or(U256::zero(), |block| U256::from(block.chainid));
+    let mut refund = 0;
+    for authorization in authorization_list {
+        if !authorization.chain_id.is_zero() && authorization.chain_id != chain_id {
+            continue;
         }
-        Err(e) => {
-            println!("external call error:{:?}", e);
-            process::exit(1);
+        if authorization.nonce.as_u64() == u64::MAX {
+            continue;
+        }
+        let Some(authority) = authorization.authority() else {
+            continue;
+        };
+        evm.access_address(authority);
+        // 授权账户只能是EOA或者已经委托过的账户
+        let code = evm.world_state.get_code(authority).unwrap_or_default();
+        if !code.is_empty() && delegation_address(&code).is_none() {
+            continue;
         }
+        if evm.world_state.get_nonce(authority).unwrap_or_default() as u64 != authorization.nonce.as_u64() {
+            continue;
+        }
+        // 授权账户已经存在时退还多收取的新账户费用
+        if evm.world_state.account_is_exsit(authority) {
+            refund += (PER_EMPTY_ACCOUNT_COST - PER_AUTH_BASE_COST) as i64;
+        } else {
+            evm.world_state.new_account(authority, AccountState::new_eoa(0, U256::zero()));
+        }
+        // 委托给0地址时清除委托
+        let code = if authorization.address.is_zero() { Bytes::new() } else { delegation_code(authorization.address) };
+        let code_hash = H256::from(keccak256(&code));
+        evm.world_state.insert_code(authority, code);
+        evm.world_state.insert_codehash(authority, code_hash);
+        evm.world_state.increment_nonce(authority)?;
     }
+    Ok(refund)
+}
+
+/// 执行消息调用交易，接收者没有code时只进行转账
+fn transact_call(evm: &mut EVM, tx: &TxEnv) -> Result<(), Box<dyn ExitError>> {
+    let transaction = &tx.transaction;
+    let to = transaction.to().unwrap();
+    evm.world_state.transfer(tx.caller, to, transaction.value())?;
+    if let Some(precompile) = evm.precompiles().get(&to) {
+        let output = precompile.run(transaction.data(), evm.gas.remaining());
+        return match output.map_err(|e| Box::new(e) as Box<dyn ExitError>)
+            .and_then(|output| evm.gas.record_cost(output.gas_used).map(|_| output.output)) {
+            Ok(output) => {
+                evm.return_data = Some(output);
+                Ok(())
+            }
+            Err(e) => {
+                evm.gas.spend_all();
+                Err(e)
+            }
+        };
+    }
+    let code = evm.world_state.get_code(to).unwrap_or_default();
+    // EIP-7702: 接收者为委托账户时执行被委托地址的code
+    if let Some(delegate) = delegation_address(&code) {
+        evm.access_address(delegate);
+    }
+    let code = evm.resolve_delegation(code);
+    if code.is_empty() {
+        return Ok(());
+    }
+    evm.bytecode = Some(code);
+    evm.call_stack.push(Call {
+        from: tx.caller,
+        to: Some(to),
+        caller: tx.caller,
+        address: Some(to),
+        value: transaction.value(),
+        call_data: transaction.data().clone(),
+        call_type: CallType::Call,
+        call_depth: 0,
+        pc: 0,
+        is_static: false,
+    });
+    evm.interepter()
+}
+
+/// 执行创建合约交易，calldata即为init code
+fn transact_create(evm: &mut EVM, tx: &TxEnv, address: H160) -> Result<(), Box<dyn ExitError>> {
+    let transaction = &tx.transaction;
+    // 目标地址已经存在code或者nonce不为0时地址冲突，消耗全部gas
+    let has_code = evm.world_state.get_code(address).is_ok_and(|code| !code.is_empty());
+    if has_code || evm.world_state.get_nonce(address).unwrap_or_default() != 0 {
+        evm.gas.spend_all();
+        return Err(Box::new(EVMError::DeployContractFailed));
+    }
+    // 地址上原有的余额保留，EIP-161之后新合约的nonce从1开始
+    let balance = evm.world_state.get_balance(address).unwrap_or_default();
+    let nonce = if evm.spec_id.is_enabled_in(SpecId::SpuriousDragon) { 1 } else { 0 };
+    evm.world_state.new_account(address, AccountState::new_contract(nonce, balance, H256::default(), Default::default(), Bytes::new()));
+    evm.world_state.mark_created(address);
+    evm.world_state.transfer(tx.caller, address, transaction.value())?;
+    evm.bytecode = Some(transaction.data().clone());
+    evm.call_stack.push(Call {
+        from: tx.caller,
+        to: Some(address),
+        caller: tx.caller,
+        address: Some(address),
+        value: transaction.value(),
+        call_data: Bytes::new(),
+        call_type: CallType::Create,
+        call_depth: 0,
+        pc: 0,
+        is_static: false,
+    });
+    evm.interepter()?;
+    if evm.is_revert {
+        return Ok(());
+    }
+    let runtime_code = Bytes::from(evm.return_data.clone().unwrap_or_default());
+    if let Err(e) = evm.deposit_code(address, runtime_code) {
+        evm.gas.spend_all();
+        return Err(e);
+    }
+    Ok(())
 }
 
 
 /// 本函数负责复现真实链上的交易
-pub async fn external_call_real_network(http_url: String, tx_hash:&str, call_type: Option<CallType>) {
+/// 只执行交易的调用部分，不收取固有gas以及手续费，需要收据时使用replay_transaction_real_network
+pub async fn external_call_real_network(http_url: String, tx_hash:&str, call_type: Option<CallType>) -> Result<ExecutionResult, Box<dyn ExitError>> {
+    let provider_error = |e: &dyn std::fmt::Display| -> Box<dyn ExitError> { Box::new(EVMError::Provider(e.to_string())) };
+    let tx_hash = H256::from_str(tx_hash).map_err(|e| provider_error(&e))?;
     // 1. set provider
     let provider_http_url = http_url;
     let provider = Provider::try_connect(provider_http_url.as_str())
         .await
-        .unwrap();
+        .map_err(|e| provider_error(&e))?;
 
     // 2. Obtain the pre_transaction_account_state
     let accounts_state_pre_tx = get_accounts_state_tx(
         Arc::new(provider.clone()),
-        H256::from_str(tx_hash).unwrap(),
+        tx_hash,
         ISDiff::default(),
-    ).await;
+    ).await.map_err(|e| provider_error(&e))?;
 
     // 3. Obtain the transaction context
-    let transaction = get_transaction_content(provider, TxHash::from_str(tx_hash).unwrap()).await;
-    let transaction_content = if transaction.is_ok() {
-        transaction.unwrap()
-    } else {
-        println!("encounter error:{:?}", transaction);
-        process::exit(1);
-    };
+    let transaction_content = get_transaction_content(provider, tx_hash)
+        .await
+        .map_err(|e| provider_error(&e))?;
 
     // 4.create a evm
-    let mut world_state = WorldState::default();
-    accounts_state_pre_tx.iter().for_each(|(addr, accountStateEx)| {
-        let accountState:AccountState = AccountState{
-            nonce: accountStateEx.clone().nonce,
-            balance: accountStateEx.clone().balance,
-            code_hash: None,
-            storage: accountStateEx.clone().storage,
-            code: accountStateEx.code.clone(),
-        };
-        world_state.new_account(*addr, accountState)
-    });
+    let mut handler = real_network_evm(&accounts_state_pre_tx, &transaction_content);
     // Call
     let from = transaction_content.from;
     let to =  Some(transaction_content.to);
@@ -121,11 +408,9 @@ pub async fn external_call_real_network(http_url: String, tx_hash:&str, call_typ
     let value=  transaction_content.value;
     let call_data = transaction_content.calldata.clone();
     let call_type = call_type.unwrap_or(CallType::Call);
+    let is_static = call_type == CallType::StaticCall;
     let call_depth = 0;
     let pc =  0;
-    println!("call's from address :{:?}", from);
-    println!("call's to address :{:?}", to);
-    println!("calldata is :{:?}", call_data);
     let call:Call = Call{
         from,
         to,
@@ -136,10 +421,28 @@ pub async fn external_call_real_network(http_url: String, tx_hash:&str, call_typ
         call_type,
         call_depth,
         pc,
-        world_state:world_state.clone(),
+        is_static,
     };
-    let bytecode = world_state.get_code(to.unwrap()).unwrap();
-    println!("execute bytecode:{:?}", bytecode);
+
+    // 5.execution
+    external_call(&mut handler, call)
+}
+
+
+/// 根据交易执行之前的账户状态以及交易所在区块的信息构建复现链上交易的EVM
+fn real_network_evm(accounts_state_pre_tx: &BTreeMap<H160, AccountStateEx>, transaction_content: &TransactionEnv) -> EVM {
+    let mut world_state = WorldState::default();
+    accounts_state_pre_tx.iter().for_each(|(addr, accountStateEx)| {
+        let accountState:AccountState = AccountState{
+            nonce: accountStateEx.clone().nonce,
+            balance: accountStateEx.clone().balance,
+            code_hash: None,
+            storage: accountStateEx.clone().storage,
+            code: accountStateEx.code.clone(),
+        };
+        world_state.new_account(*addr, accountState)
+    });
+    let mut handler = EVM::new(world_state);
 
     // Block
     let blockhash = transaction_content.block_hash.clone();
@@ -148,8 +451,8 @@ pub async fn external_call_real_network(http_url: String, tx_hash:&str, call_typ
     let number = transaction_content.block_number;
     let prevrandao = H256::default();
     let gas_limit = transaction_content.gas.clone();
-    let chainid = transaction_content.chain_id.clone().unwrap().as_usize();
-    let basefee = transaction_content.basefee.clone().unwrap().as_usize();
+    let chainid = transaction_content.chain_id.clone().unwrap_or_default().as_usize();
+    let basefee = transaction_content.basefee.clone().unwrap_or_default().as_usize();
 
     let block:Block = Block {
         blockhash,
@@ -160,42 +463,155 @@ pub async fn external_call_real_network(http_url: String, tx_hash:&str, call_typ
         gas_limit,
         chainid,
         basefee,
+        excess_blob_gas: transaction_content.excess_blob_gas.map(|gas| gas.as_u64()),
     };
 
-    let mut handler = EVM::new(world_state);
-    handler.call_stack.push(call);
-    handler.origin = transaction_content.from;
-    handler.bytecode = Some(bytecode);
+    // 主网交易按照所在区块选择硬分叉
+    if chainid == 1 {
+        handler.set_spec_id(SpecId::from_mainnet_block(number as u64, timestamp as u64));
+    }
     handler.block = Some(block);
+    handler.blob_hashes = transaction_content.blob_versioned_hashes.clone();
+    handler.access_list = transaction_content.access_list.clone().unwrap_or_default();
+    handler.transaction = Some(transaction_content.transaction.clone());
 
-    // 5.execution
-    match handler.interepter(){
-        Ok(_) => {
-            // println!("execute successful, current evm is :{:?}", handler);
-        }
-        Err(e) => {
-            println!("execute error: {:?}", e);
-            process::exit(1);
+    handler
+}
+
+/// 以完整交易的方式复现真实链上的交易：校验交易、收取固有gas并支付手续费，返回执行结果以及收据
+/// 单笔交易无法得知区块中之前的交易消耗的gas，收据的累计gas以节点收据中当前交易之前的累计gas为起点
+pub async fn replay_transaction_real_network(http_url: String, tx_hash: &str) -> Result<TxOutcome, Box<dyn ExitError>> {
+    let provider_error = |e: &dyn std::fmt::Display| -> Box<dyn ExitError> { Box::new(EVMError::Provider(e.to_string())) };
+    let tx_hash = H256::from_str(tx_hash).map_err(|e| provider_error(&e))?;
+    let provider = Provider::try_connect(http_url.as_str())
+        .await
+        .map_err(|e| provider_error(&e))?;
+    let accounts_state_pre_tx = get_accounts_state_tx(Arc::new(provider.clone()), tx_hash, ISDiff::default())
+        .await
+        .map_err(|e| provider_error(&e))?;
+    let transaction_content = get_transaction_content(provider.clone(), tx_hash)
+        .await
+        .map_err(|e| provider_error(&e))?;
+    let previous_cumulative_gas_used = get_transaction_receipt(provider, tx_hash)
+        .await
+        .map_err(|e| provider_error(&e))?
+        .map_or(0, |receipt| receipt.cumulative_gas_used - receipt.gas_used);
+
+    let mut evm = real_network_evm(&accounts_state_pre_tx, &transaction_content);
+    let tx = TxEnv { caller: transaction_content.from, transaction: transaction_content.transaction };
+    let tx_type = tx.transaction.tx_type();
+    let contract_address = tx.create_address();
+    let result = transact(&mut evm, tx)?;
+    let receipt = Receipt::new(tx_type, &result, previous_cumulative_gas_used, contract_address);
+    Ok(TxOutcome { result, receipt })
+}
+
+/// 复现真实链上的整个区块：按顺序执行区块中的全部交易，每笔交易执行后与prestateTracer diff模式给出的post state比较
+/// 初始状态为第一笔交易的prestate，其中不包含之后的交易才访问的账户和存储槽，
+/// 因此每笔交易执行前从该交易的prestate中补充本地尚未加载的部分，已加载的部分保持本地执行得到的状态
+pub async fn replay_block_real_network(http_url: String, block_number: u64) -> Result<BlockReplay, Box<dyn ExitError>> {
+    let provider_error = |e: &dyn std::fmt::Display| -> Box<dyn ExitError> { Box::new(EVMError::Provider(e.to_string())) };
+    let provider = Provider::try_connect(http_url.as_str())
+        .await
+        .map_err(|e| provider_error(&e))?;
+    let provider = Arc::new(provider);
+    let block = provider.get_block_with_txs(block_number)
+        .await
+        .map_err(|e| provider_error(&e))?
+        .ok_or_else(|| provider_error(&format!("block {} not found", block_number)))?;
+    let chainid = provider.get_chainid().await.map_err(|e| provider_error(&e))?.as_usize();
+
+    let timestamp = block.timestamp.as_usize();
+    let mut evm = EVM::new(WorldState::default());
+    // 主网区块按照区块高度选择硬分叉
+    if chainid == 1 {
+        evm.set_spec_id(SpecId::from_mainnet_block(block_number, timestamp as u64));
+    }
+    evm.block = Some(Block {
+        blockhash: block.hash.unwrap_or_default(),
+        coinbase: block.author.unwrap_or_default(),
+        timestamp,
+        number: block_number as usize,
+        prevrandao: block.mix_hash.unwrap_or_default(),
+        gas_limit: block.gas_limit,
+        chainid,
+        basefee: block.base_fee_per_gas.unwrap_or_default().as_usize(),
+        excess_blob_gas: block.excess_blob_gas.map(|gas| gas.as_u64()),
+    });
+
+    let mut gas_used = 0;
+    let mut transactions = Vec::with_capacity(block.transactions.len());
+    for transaction in &block.transactions {
+        let pre_state = get_accounts_state_tx(provider.clone(), transaction.hash, ISDiff::default())
+            .await
+            .map_err(|e| provider_error(&e))?;
+        for (address, account) in pre_state {
+            load_missing_state(&mut evm.world_state, address, account);
         }
-    };
+
+        let outcome = execute_block_transaction(&mut evm, TxEnv::from(transaction), &mut gas_used);
+
+        let post_state = get_accounts_state_tx(provider.clone(), transaction.hash, ISDiff::new(true, Some(false)))
+            .await
+            .map_err(|e| provider_error(&e))?;
+        let mismatches = compare_post_state(&evm.world_state.state, &post_state);
+        transactions.push(TxReplay { tx_hash: transaction.hash, outcome, mismatches });
+    }
+    Ok(BlockReplay { transactions })
 }
 
+/// 将链上获取的账户状态中本地尚未加载的账户以及存储槽加入全局状态，不记录到状态修改日志中
+fn load_missing_state(world_state: &mut WorldState, address: H160, account: AccountStateEx) {
+    match world_state.state.get_mut(&address) {
+        Some(local) => {
+            let storage = local.storage.get_or_insert_with(Default::default);
+            for (slot, value) in account.storage.unwrap_or_default() {
+                storage.entry(slot).or_insert(value);
+            }
+        }
+        None => {
+            world_state.state.insert(address, AccountState {
+                nonce: account.nonce,
+                balance: account.balance,
+                code_hash: None,
+                storage: account.storage,
+                code: account.code,
+            });
+        }
+    }
+}
 
 #[tokio::test]
+#[ignore = "需要访问以太坊节点"]
 async fn test_external_call_real_network() {
-    let provider_http_url = String::from("https://lb.nodies.app/v1/181a5ebf4c954f8496ae7cbc1ac8d03b");
+    dotenv().ok();
+    let provider_http_url = env::var("ethereum").expect("ethereum rpc url not set");
     let tx_hash = "0x3ed75df83d907412af874b7998d911fdf990704da87c2b1a8cf95ca5d21504cf";
     let call_type = Some(CallType::Call);
-    external_call_real_network(provider_http_url, tx_hash, call_type).await;
+    let result = external_call_real_network(provider_http_url, tx_hash, call_type).await.unwrap();
+    assert!(result.is_success());
 }
 
 
 
 #[tokio::test]
+#[ignore = "需要访问以太坊节点"]
+async fn test_replay_transaction_real_network() {
+    dotenv().ok();
+    let provider_http_url = env::var("ethereum").expect("ethereum rpc url not set");
+    let tx_hash = "0x3ed75df83d907412af874b7998d911fdf990704da87c2b1a8cf95ca5d21504cf";
+    let outcome = replay_transaction_real_network(provider_http_url.clone(), tx_hash).await.unwrap();
+    let provider = Provider::try_connect(provider_http_url.as_str()).await.unwrap();
+    let node_receipt = get_transaction_receipt(provider, H256::from_str(tx_hash).unwrap()).await.unwrap().unwrap();
+    assert_eq!(outcome.receipt, node_receipt);
+}
+
+#[tokio::test]
+#[ignore = "需要访问以太坊节点"]
 async fn test_execute_on_chain_tx() {
     dotenv().ok();
     // 1. set provider
-    let provider_http_url = env::var("ethereum").unwrap_or_else(|_| String::from("https://lb.nodies.app/v1/181a5ebf4c954f8496ae7cbc1ac8d03b"));
+    let provider_http_url = env::var("ethereum").expect("ethereum rpc url not set");
     let provider = Provider::try_connect(provider_http_url.as_str())
         .await
         .unwrap();
@@ -207,17 +623,11 @@ async fn test_execute_on_chain_tx() {
         Arc::new(provider.clone()),
         H256::from_str(olympus_dao_tx).unwrap(),
         ISDiff::default(),
-    ).await;
+    ).await.unwrap();
 
 
     // 3. Obtain the transaction context
-    let transaction = get_transaction_content(provider, TxHash::from_str(olympus_dao_tx).unwrap()).await;
-    let transaction_content = if transaction.is_ok() {
-        transaction.unwrap()
-    } else {
-        println!("encounter error:{:?}", transaction);
-        process::exit(1);
-    };
+    let transaction_content = get_transaction_content(provider, H256::from_str(olympus_dao_tx).unwrap()).await.unwrap();
 
     // 4.create a evm
     let mut world_state = WorldState::default();
@@ -231,7 +641,6 @@ async fn test_execute_on_chain_tx() {
         };
         world_state.new_account(*addr, accountState)
     });
-    println!("now world_state is :{:?}", world_state);
     // Call
     let from = transaction_content.from;
     let to =  Some(transaction_content.to);
@@ -242,9 +651,6 @@ async fn test_execute_on_chain_tx() {
     let call_type = CallType::Call;
     let call_depth = 0;
     let pc =  0;
-    println!("call's from address :{:?}", from);
-    println!("call's to address :{:?}", to);
-    println!("calldata is :{:?}", call_data);
     let call:Call = Call{
         from,
         to,
@@ -255,10 +661,9 @@ async fn test_execute_on_chain_tx() {
         call_type,
         call_depth,
         pc,
-        world_state:world_state.clone(),
+        is_static: false,
     };
     let bytecode = world_state.get_code(to.unwrap()).unwrap();
-    println!("execute bytecode:{:?}", bytecode);
 
     // Block
     let blockhash = transaction_content.block_hash.clone();
@@ -279,23 +684,34 @@ async fn test_execute_on_chain_tx() {
         gas_limit,
         chainid,
         basefee,
+        excess_blob_gas: transaction_content.excess_blob_gas.map(|gas| gas.as_u64()),
     };
 
     let mut handler = EVM::new(world_state);
+    // 主网交易按照所在区块选择硬分叉
+    if chainid == 1 {
+        handler.set_spec_id(SpecId::from_mainnet_block(number as u64, timestamp as u64));
+    }
     handler.call_stack.push(call);
     handler.origin = transaction_content.from;
     handler.bytecode = Some(bytecode);
     handler.block = Some(block);
+    handler.blob_hashes = transaction_content.blob_versioned_hashes.clone();
 
     // 5.execution
-    match handler.interepter(){
-        Ok(_) => {
-            // println!("execute successful, current evm is :{:?}", handler);
-        }
-        Err(e) => {
-            println!("execute error: {:?}", e);
-            process::exit(1);
-        }
-    };
+    handler.interepter().unwrap();
+
+}
 
-}
\ No newline at end of file
+
+#[tokio::test]
+#[ignore = "需要访问以太坊节点"]
+async fn test_replay_block_real_network() {
+    dotenv().ok();
+    let provider_http_url = env::var("ethereum").expect("ethereum rpc url not set");
+    let replay = replay_block_real_network(provider_http_url, 19_000_000).await.unwrap();
+    let mismatches: Vec<_> = replay.transactions.iter()
+        .flat_map(|tx| tx.mismatches.iter().map(move |mismatch| (tx.tx_hash, mismatch.to_string())))
+        .collect();
+    assert!(replay.is_consistent(), "{:?}", mismatches);
+}
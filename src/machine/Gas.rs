@@ -0,0 +1,395 @@
+use ethers::types::transaction::eip2930::AccessList;
+use primitive_types::{H256, U256};
+use crate::error::exit::*;
+use crate::spec::SpecId;
+
+/// gas计量相关常量
+pub const MEMORY: u64 = 3;
+pub const COPY: u64 = 3;
+pub const KECCAK256WORD: u64 = 6;
+pub const EXP_BYTE: u64 = 50;
+pub const EXP_BYTE_FRONTIER: u64 = 10;
+pub const LOG: u64 = 375;
+pub const LOGTOPIC: u64 = 375;
+pub const LOGDATA: u64 = 8;
+pub const INITCODE_WORD: u64 = 2;
+/// 部署合约时runtime code每字节的费用
+pub const CODEDEPOSIT: u64 = 200;
+pub const CALLVALUE: u64 = 9000;
+pub const NEWACCOUNT: u64 = 25000;
+pub const CALL_STIPEND: u64 = 2300;
+pub const SSTORE_SET: u64 = 20000;
+pub const SSTORE_RESET: u64 = 5000;
+/// EIP-2929: warm/cold访问费用
+pub const WARM_STORAGE_READ: u64 = 100;
+pub const COLD_SLOAD: u64 = 2100;
+pub const COLD_ACCOUNT_ACCESS: u64 = 2600;
+/// EIP-3529: 清空存储槽的退款
+pub const SSTORE_CLEARS_SCHEDULE: u64 = 4800;
+pub const SSTORE_CLEARS_SCHEDULE_LEGACY: u64 = 15000;
+/// EIP-3529: London之前SELFDESTRUCT的退款
+pub const SELFDESTRUCT_REFUND: i64 = 24000;
+/// EIP-1884: Istanbul中SLOAD的费用
+pub const SLOAD_ISTANBUL: u64 = 800;
+/// EIP-3529: 交易结束时退款不超过已消耗gas的1/5
+pub const MAX_REFUND_QUOTIENT: u64 = 5;
+/// 交易的固有费用
+pub const TRANSACTION: u64 = 21000;
+/// Homestead之后创建合约的交易额外收取的费用
+pub const TRANSACTION_CREATE: u64 = 32000;
+pub const TRANSACTION_ZERO_DATA: u64 = 4;
+pub const TRANSACTION_NON_ZERO_DATA_FRONTIER: u64 = 68;
+/// EIP-2028: Istanbul中calldata非零字节的费用
+pub const TRANSACTION_NON_ZERO_DATA: u64 = 16;
+/// EIP-2930: 访问列表中每个地址以及存储槽的费用
+pub const ACCESS_LIST_ADDRESS: u64 = 2400;
+pub const ACCESS_LIST_STORAGE_KEY: u64 = 1900;
+/// EIP-4844: 每个blob消耗的blob gas
+pub const GAS_PER_BLOB: u64 = 131072;
+/// EIP-7702: 每个授权按照授权账户不存在收取的费用，授权账户已经存在时退还其与基础费用的差额
+pub const PER_EMPTY_ACCOUNT_COST: u64 = 25000;
+pub const PER_AUTH_BASE_COST: u64 = 12500;
+
+/// 调用帧的gas计数器，每一个调用帧都拥有独立的Gas，子调用结束后将剩余的gas退还给父调用帧
+#[derive(Debug, Clone)]
+pub struct Gas {
+    limit: u64,
+    remaining: u64,
+    refunded: i64,
+}
+
+impl Gas {
+    pub fn new(limit: u64) -> Self {
+        Self {
+            limit,
+            remaining: limit,
+            refunded: 0,
+        }
+    }
+
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
+
+    pub fn remaining(&self) -> u64 {
+        self.remaining
+    }
+
+    /// 当前调用帧已经消耗的gas
+    pub fn spent(&self) -> u64 {
+        self.limit - self.remaining
+    }
+
+    /// 当前累计的退款，子调用中可能出现负值
+    pub fn refunded(&self) -> i64 {
+        self.refunded
+    }
+
+    /// 扣除退款之后实际消耗的gas
+    pub fn spent_sub_refunded(&self) -> u64 {
+        self.spent().saturating_sub(self.refunded.max(0) as u64)
+    }
+
+    /// 扣除gas，剩余gas不足时返回OutOfGas
+    pub fn record_cost(&mut self, cost: u64) -> Result<(), Box<dyn ExitError>> {
+        if cost > self.remaining {
+            return Err(Box::new(OpcodeExecutionError::OutOfGas));
+        }
+        self.remaining -= cost;
+        Ok(())
+    }
+
+    /// 退还gas，用于子调用结束后返还未使用的gas
+    pub fn erase_cost(&mut self, returned: u64) {
+        self.remaining += returned;
+    }
+
+    /// 异常中止时消耗掉全部剩余gas，该调用帧累计的退款同时作废
+    pub fn spend_all(&mut self) {
+        self.remaining = 0;
+        self.refunded = 0;
+    }
+
+    /// 记录退款，子调用成功结束后其退款会合并到父调用帧
+    pub fn record_refund(&mut self, refund: i64) {
+        self.refunded += refund;
+    }
+
+    /// 清空退款，用于被revert的交易
+    pub fn clear_refund(&mut self) {
+        self.refunded = 0;
+    }
+
+    /// 交易结束时计算最终退款：退款不能超过已消耗gas的1/max_refund_quotient
+    pub fn set_final_refund(&mut self, max_refund_quotient: u64) {
+        let max_refund = self.spent() / max_refund_quotient;
+        self.refunded = (self.refunded.max(0) as u64).min(max_refund) as i64;
+    }
+}
+
+/// 计算字节长度对应的字(32字节)数量，向上取整
+pub fn num_words(len: u64) -> u64 {
+    len.div_ceil(32)
+}
+
+/// 指定字数的memory所对应的总gas: 3 * words + words^2 / 512
+pub fn memory_gas(words: u64) -> u64 {
+    MEMORY * words + words * words / 512
+}
+
+/// 访问memory中[offset, offset + size)区间需要额外支付的扩展费用
+pub fn memory_expansion_cost(current_len: U256, offset: U256, size: U256) -> Result<u64, Box<dyn ExitError>> {
+    if size.is_zero() {
+        return Ok(0);
+    }
+    let end = offset
+        .checked_add(size)
+        .ok_or(Box::new(OpcodeExecutionError::OutOfGas) as Box<dyn ExitError>)?;
+    // 超出该范围的扩展费用不可能被支付
+    if end > U256::from(u32::MAX) {
+        return Err(Box::new(OpcodeExecutionError::OutOfGas));
+    }
+    let new_words = num_words(end.as_u64());
+    let current_words = num_words(current_len.as_u64());
+    if new_words <= current_words {
+        return Ok(0);
+    }
+    Ok(memory_gas(new_words) - memory_gas(current_words))
+}
+
+/// 交易执行之前收取的固有gas：基础费用、calldata费用、创建合约费用、访问列表费用以及授权列表费用
+pub fn intrinsic_gas(data: &[u8], is_create: bool, access_list: &AccessList, authorization_count: u64, spec: SpecId) -> u64 {
+    let non_zero_cost = if spec.is_enabled_in(SpecId::Istanbul) {
+        TRANSACTION_NON_ZERO_DATA
+    } else {
+        TRANSACTION_NON_ZERO_DATA_FRONTIER
+    };
+    let zero_bytes = data.iter().filter(|byte| **byte == 0).count() as u64;
+    let non_zero_bytes = data.len() as u64 - zero_bytes;
+    let mut gas = TRANSACTION + zero_bytes * TRANSACTION_ZERO_DATA + non_zero_bytes * non_zero_cost;
+    if is_create && spec.is_enabled_in(SpecId::Homestead) {
+        gas += TRANSACTION_CREATE;
+    }
+    // EIP-3860: Shanghai之后创建合约的交易同样按字收取init code费用
+    if is_create && spec.is_enabled_in(SpecId::Shanghai) {
+        gas += initcode_cost(data.len() as u64);
+    }
+    for item in &access_list.0 {
+        gas += ACCESS_LIST_ADDRESS + ACCESS_LIST_STORAGE_KEY * item.storage_keys.len() as u64;
+    }
+    gas + PER_EMPTY_ACCOUNT_COST * authorization_count
+}
+
+/// calldatacopy、codecopy等复制类操作按字收取的费用
+pub fn copy_cost(size: U256) -> Result<u64, Box<dyn ExitError>> {
+    word_cost(COPY, size)
+}
+
+/// keccak256按字收取的费用
+pub fn keccak256_cost(size: U256) -> Result<u64, Box<dyn ExitError>> {
+    word_cost(KECCAK256WORD, size)
+}
+
+/// EIP-3860: init code按字收取的费用
+pub fn initcode_cost(len: u64) -> u64 {
+    INITCODE_WORD * num_words(len)
+}
+
+/// exp按指数的字节长度收取的费用，EIP-160将每字节费用从10调整为50
+pub fn exp_cost(exponent: U256, spec: SpecId) -> u64 {
+    let bytes = (exponent.bits() as u64).div_ceil(8);
+    let per_byte = if spec.is_enabled_in(SpecId::SpuriousDragon) { EXP_BYTE } else { EXP_BYTE_FRONTIER };
+    per_byte * bytes
+}
+
+/// log按topic数量以及data长度收取的费用（基础费用375已经在opcode表中收取）
+pub fn log_cost(topics: u64, size: U256) -> Result<u64, Box<dyn ExitError>> {
+    if size > U256::from(u32::MAX) {
+        return Err(Box::new(OpcodeExecutionError::OutOfGas));
+    }
+    Ok(LOGTOPIC * topics + LOGDATA * size.as_u64())
+}
+
+/// 根据存储槽的原始值、当前值和新值计算sstore的费用
+///
+/// Istanbul之前只比较当前值与新值；Istanbul引入EIP-2200的净gas计量；Berlin之后叠加EIP-2929的warm/cold费用
+pub fn sstore_cost(original: H256, current: H256, new: H256, is_cold: bool, spec: SpecId) -> u64 {
+    if !spec.is_enabled_in(SpecId::Istanbul) {
+        return if current.is_zero() && !new.is_zero() { SSTORE_SET } else { SSTORE_RESET };
+    }
+    let sload = sstore_sload_gas(spec);
+    let cost = if new == current {
+        sload
+    } else if original == current {
+        if original.is_zero() {
+            SSTORE_SET
+        } else {
+            sstore_reset_gas(spec)
+        }
+    } else {
+        // 该存储槽已经在本次交易中被修改过
+        sload
+    };
+    if is_cold && spec.is_enabled_in(SpecId::Berlin) { cost + COLD_SLOAD } else { cost }
+}
+
+/// 根据存储槽的原始值、当前值和新值计算sstore产生的退款，可能为负值
+pub fn sstore_refund(original: H256, current: H256, new: H256, spec: SpecId) -> i64 {
+    let clears = sstore_clears_schedule(spec) as i64;
+    if !spec.is_enabled_in(SpecId::Istanbul) {
+        return if !current.is_zero() && new.is_zero() { clears } else { 0 };
+    }
+    if current == new {
+        return 0;
+    }
+    let mut refund: i64 = 0;
+    if original == current {
+        if new.is_zero() {
+            refund += clears;
+        }
+        return refund;
+    }
+    if !original.is_zero() {
+        if current.is_zero() {
+            // 之前清空存储槽获得的退款被撤销
+            refund -= clears;
+        } else if new.is_zero() {
+            refund += clears;
+        }
+    }
+    if original == new {
+        // 存储槽被恢复为原始值
+        let sload = sstore_sload_gas(spec);
+        if original.is_zero() {
+            refund += (SSTORE_SET - sload) as i64;
+        } else {
+            refund += (sstore_reset_gas(spec) - sload) as i64;
+        }
+    }
+    refund
+}
+
+/// EIP-2200中的SLOAD_GAS，Berlin之后为warm读取费用
+fn sstore_sload_gas(spec: SpecId) -> u64 {
+    if spec.is_enabled_in(SpecId::Berlin) { WARM_STORAGE_READ } else { SLOAD_ISTANBUL }
+}
+
+/// 修改非零存储槽的费用，Berlin之后cold费用单独收取
+fn sstore_reset_gas(spec: SpecId) -> u64 {
+    if spec.is_enabled_in(SpecId::Berlin) { SSTORE_RESET - COLD_SLOAD } else { SSTORE_RESET }
+}
+
+/// 清空存储槽的退款，EIP-3529将其从15000调整为4800
+fn sstore_clears_schedule(spec: SpecId) -> u64 {
+    if spec.is_enabled_in(SpecId::London) { SSTORE_CLEARS_SCHEDULE } else { SSTORE_CLEARS_SCHEDULE_LEGACY }
+}
+
+fn word_cost(per_word: u64, size: U256) -> Result<u64, Box<dyn ExitError>> {
+    if size > U256::from(u32::MAX) {
+        return Err(Box::new(OpcodeExecutionError::OutOfGas));
+    }
+    Ok(per_word * num_words(size.as_u64()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::types::transaction::eip2930::AccessListItem;
+
+    #[test]
+    fn test_record_cost() {
+        let mut gas = Gas::new(100);
+        gas.record_cost(60).unwrap();
+        assert_eq!(gas.remaining(), 40);
+        assert_eq!(gas.spent(), 60);
+        assert!(gas.record_cost(41).is_err());
+        assert_eq!(gas.remaining(), 40);
+        gas.erase_cost(10);
+        assert_eq!(gas.remaining(), 50);
+        gas.spend_all();
+        assert_eq!(gas.spent(), 100);
+    }
+
+    #[test]
+    fn test_final_refund() {
+        let mut gas = Gas::new(100_000);
+        gas.record_cost(50_000).unwrap();
+        gas.record_refund(19_900);
+        gas.set_final_refund(MAX_REFUND_QUOTIENT);
+        assert_eq!(gas.refunded(), 10_000);
+        assert_eq!(gas.spent_sub_refunded(), 40_000);
+
+        let mut gas = Gas::new(100_000);
+        gas.record_cost(50_000).unwrap();
+        gas.record_refund(-4_800);
+        gas.set_final_refund(MAX_REFUND_QUOTIENT);
+        assert_eq!(gas.refunded(), 0);
+    }
+
+    #[test]
+    fn test_intrinsic_gas() {
+        let data = [0u8, 1, 0, 2];
+        let access_list = AccessList::default();
+        assert_eq!(intrinsic_gas(&[], false, &access_list, 0, SpecId::Cancun), 21000);
+        assert_eq!(intrinsic_gas(&data, false, &access_list, 0, SpecId::Cancun), 21000 + 2 * 4 + 2 * 16);
+        // EIP-2028之前非零字节为68
+        assert_eq!(intrinsic_gas(&data, false, &access_list, 0, SpecId::Petersburg), 21000 + 2 * 4 + 2 * 68);
+        // 创建合约：Homestead之后额外收取32000，Shanghai之后按字收取init code费用
+        assert_eq!(intrinsic_gas(&data, true, &access_list, 0, SpecId::Frontier), 21000 + 2 * 4 + 2 * 68);
+        assert_eq!(intrinsic_gas(&data, true, &access_list, 0, SpecId::London), 53000 + 2 * 4 + 2 * 16);
+        assert_eq!(intrinsic_gas(&data, true, &access_list, 0, SpecId::Shanghai), 53000 + 2 * 4 + 2 * 16 + 2);
+        let access_list = AccessList(vec![AccessListItem {
+            address: Default::default(),
+            storage_keys: vec![H256::zero(), H256::repeat_byte(1)],
+        }]);
+        assert_eq!(intrinsic_gas(&[], false, &access_list, 0, SpecId::Berlin), 21000 + 2400 + 2 * 1900);
+        // EIP-7702: 每个授权收取25000
+        assert_eq!(intrinsic_gas(&[], false, &AccessList::default(), 2, SpecId::Prague), 21000 + 2 * 25000);
+    }
+
+    #[test]
+    fn test_memory_expansion_cost() {
+        // 首次扩展到一个字：3 * 1 + 1 / 512 = 3
+        assert_eq!(memory_expansion_cost(U256::zero(), U256::zero(), U256::from(32)).unwrap(), 3);
+        // 已经扩展过的区间不再收费
+        assert_eq!(memory_expansion_cost(U256::from(64), U256::zero(), U256::from(32)).unwrap(), 0);
+        // 长度为0的访问不会扩展memory
+        assert_eq!(memory_expansion_cost(U256::zero(), U256::MAX, U256::zero()).unwrap(), 0);
+        // 扩展到1024个字：3 * 1024 + 1024 * 1024 / 512 = 5120
+        assert_eq!(memory_expansion_cost(U256::zero(), U256::zero(), U256::from(32 * 1024)).unwrap(), 5120);
+        assert!(memory_expansion_cost(U256::zero(), U256::MAX, U256::one()).is_err());
+    }
+
+    #[test]
+    fn test_dynamic_costs() {
+        assert_eq!(copy_cost(U256::from(33)).unwrap(), 6);
+        assert_eq!(keccak256_cost(U256::from(64)).unwrap(), 12);
+        assert_eq!(exp_cost(U256::zero(), SpecId::Cancun), 0);
+        assert_eq!(exp_cost(U256::from(0xff), SpecId::Cancun), 50);
+        assert_eq!(exp_cost(U256::from(0x100), SpecId::Cancun), 100);
+        assert_eq!(exp_cost(U256::MAX, SpecId::Cancun), 1600);
+        assert_eq!(exp_cost(U256::from(0x100), SpecId::Homestead), 20);
+        assert_eq!(log_cost(2, U256::from(10)).unwrap(), 830);
+    }
+
+    #[test]
+    fn test_sstore_rules_by_spec() {
+        let zero = H256::zero();
+        let one = H256::from_low_u64_be(1);
+        let two = H256::from_low_u64_be(2);
+        // Istanbul之前只看当前值与新值
+        assert_eq!(sstore_cost(one, one, one, false, SpecId::Petersburg), 5000);
+        assert_eq!(sstore_cost(zero, zero, one, false, SpecId::Petersburg), 20000);
+        assert_eq!(sstore_refund(one, one, zero, SpecId::Petersburg), 15000);
+        // EIP-2200
+        assert_eq!(sstore_cost(one, one, one, false, SpecId::Istanbul), 800);
+        assert_eq!(sstore_cost(one, one, two, false, SpecId::Istanbul), 5000);
+        assert_eq!(sstore_refund(one, two, one, SpecId::Istanbul), 4200);
+        // EIP-2929
+        assert_eq!(sstore_cost(one, one, two, true, SpecId::Berlin), 5000);
+        assert_eq!(sstore_cost(one, one, two, false, SpecId::Berlin), 2900);
+        assert_eq!(sstore_refund(one, one, zero, SpecId::Berlin), 15000);
+        // EIP-3529
+        assert_eq!(sstore_refund(one, one, zero, SpecId::London), 4800);
+    }
+}
@@ -0,0 +1,65 @@
+use primitive_types::U256;
+
+const JUMPDEST: u8 = 0x5b;
+const PUSH1: u8 = 0x60;
+const PUSH32: u8 = 0x7f;
+
+/// 字节码中合法跳转目标的位图，每一位对应一个字节码位置
+///
+/// 只有作为操作码出现的JUMPDEST才是合法的跳转目标，PUSH1~PUSH32携带的立即数中的0x5b不能作为跳转目标
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct JumpDestMap {
+    bitmap: Vec<u64>,
+}
+
+impl JumpDestMap {
+    /// 遍历一遍字节码，跳过PUSH指令的立即数，记录所有JUMPDEST的位置
+    pub fn analyze(code: &[u8]) -> Self {
+        let mut bitmap = vec![0u64; code.len().div_ceil(64)];
+        let mut pc = 0;
+        while pc < code.len() {
+            let op = code[pc];
+            if op == JUMPDEST {
+                bitmap[pc / 64] |= 1 << (pc % 64);
+            } else if (PUSH1..=PUSH32).contains(&op) {
+                pc += (op - PUSH1 + 1) as usize;
+            }
+            pc += 1;
+        }
+        Self { bitmap }
+    }
+
+    /// 判断跳转目标是否为合法的JUMPDEST，超出字节码范围的目标均不合法
+    pub fn is_valid(&self, dest: U256) -> bool {
+        if dest >= U256::from(self.bitmap.len() * 64) {
+            return false;
+        }
+        let dest = dest.as_usize();
+        self.bitmap[dest / 64] & (1 << (dest % 64)) != 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_analyze() {
+        // PUSH1 0x5b JUMPDEST PUSH2 0x5b5b STOP JUMPDEST
+        let code = [0x60, 0x5b, 0x5b, 0x61, 0x5b, 0x5b, 0x00, 0x5b];
+        let jumpdests = JumpDestMap::analyze(&code);
+        assert!(!jumpdests.is_valid(U256::from(1)));
+        assert!(jumpdests.is_valid(U256::from(2)));
+        assert!(!jumpdests.is_valid(U256::from(4)));
+        assert!(!jumpdests.is_valid(U256::from(5)));
+        assert!(!jumpdests.is_valid(U256::from(6)));
+        assert!(jumpdests.is_valid(U256::from(7)));
+        // 超出字节码范围
+        assert!(!jumpdests.is_valid(U256::from(8)));
+        assert!(!jumpdests.is_valid(U256::MAX));
+        // 末尾被截断的PUSH指令
+        let jumpdests = JumpDestMap::analyze(&[0x5b, 0x7f, 0x5b]);
+        assert!(jumpdests.is_valid(U256::zero()));
+        assert!(!jumpdests.is_valid(U256::from(2)));
+    }
+}
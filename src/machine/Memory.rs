@@ -1,5 +1,4 @@
 use primitive_types::U256;
-use crate::error::*;
 
 #[derive(Debug,Clone)]
 pub struct Memory{
@@ -64,6 +63,10 @@ impl Memory{
 
     // 向memory中添加内容
     pub fn write(&mut self, offset: U256, data: &[u8]) -> Result<(), Box<dyn ExitError>> {
+        // 写入空数据时不访问memory，offset可以为任意值
+        if data.is_empty() {
+            return Ok(());
+        }
         self.resize_offset(offset, U256::from(data.len()))?;
         self.data[offset.as_usize()..offset.as_usize() + data.len()].copy_from_slice(data);
         Ok(())
@@ -72,6 +75,10 @@ impl Memory{
     // 从memory中读取指定长度的数据
     pub fn read(&mut self, offset: U256, len: U256) -> Result<Vec<u8>, Box<dyn ExitError>>{
         let mut value:Vec<u8> = Vec::new();
+        // 读取长度为0时不访问memory，offset可以为任意值
+        if len.is_zero() {
+            return Ok(value);
+        }
         if offset + len > self.effective_len{
             self.resize_offset(offset, len)?;
         }
@@ -1,2 +1,4 @@
 pub mod Memory;
-pub mod Stack;
\ No newline at end of file
+pub mod Stack;
+pub mod Gas;
+pub mod JumpDest;
\ No newline at end of file
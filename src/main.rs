@@ -16,9 +16,9 @@ fn main(){
     let world_state = WorldState::new(hash_map);
 
     // 构建EVM状态
-    let mut handler = EVM::new(world_state.clone());
+    let mut handler = EVM::new(world_state);
     let run_time = "0x6080604052348015600e575f80fd5b50607480601a5f395ff3fe6080604052348015600e575f80fd5b50600436106026575f3560e01c806311f37ceb14602a575b5f80fd5b600c60405190815260200160405180910390f3fea26469706673582212208f8107617c0706b60751cb6ed139c3edd4b43be3b02fcbc22b28192e202c027e64736f6c634300081a0033";
-    let to = deploy(&mut handler, run_time.parse().unwrap(), caller, U256::zero());
+    let to = deploy(&mut handler, run_time.parse().unwrap(), caller, U256::zero()).expect("deploy failed!");
 
     let return_value = handler.return_data.clone();
     println!("return_data is : {:?}", return_value);
@@ -35,12 +35,12 @@ fn main(){
         call_type: CallType::Call,
         call_depth: 0,
         pc: 0,
-        world_state: world_state.clone(),
+        is_static: false,
     };
     // 更新EVM信息
-    let value = external_call(&mut handler, call).expect("external call failed!");
+    let result = external_call(&mut handler, call).expect("external call failed!");
 
-    println!("return_data is : {:?}", value);
+    println!("return_data is : {:?}", result.output());
 }
 
 // todo!:使用我们的evm执行真实的链上交易
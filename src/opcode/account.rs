@@ -2,21 +2,24 @@ use ethers::prelude::Bytes;
 use primitive_types::U256;
 use crate::error::exit::*;
 use crate::evm::EVM;
-use crate::globalState::AccountState;
-use crate::utils::{h256_to_u256, u256_to_h160};
+use crate::machine::Gas::copy_cost;
+use crate::utils::{h256_to_u256, slice_with_padding, u256_to_h160};
 
 ///_extcodecopy _extcodehash _extcodesize _selfbalance
 /// 将某个地址的code复制到memory中
 pub fn extcodecopy(evm: &mut EVM) -> Result<(), Box<dyn ExitError>>{
     let address = evm.stack.pop()?;
     let destOffset = evm.stack.pop()?;
-    let offset = evm.stack.pop()?.as_usize();
-    let size = evm.stack.pop()?.as_usize();
+    let offset = evm.stack.pop()?;
+    let size = evm.stack.pop()?;
+    evm.record_account_access(u256_to_h160(address))?;
+    evm.gas.record_cost(copy_cost(size)?)?;
+    evm.record_memory_cost(destOffset, size)?;
     // 获取某个地址的code，eoa账户没有code
     match evm.world_state.get_code(u256_to_h160(address)) {
         Ok(bytecode) => {
-            let bytecode_to_copy = &bytecode[offset..offset+size].to_vec();
-            evm.memory.write(destOffset, &bytecode_to_copy);
+            let bytecode_to_copy = slice_with_padding(&bytecode, offset, size.as_usize());
+            evm.memory.write(destOffset, &bytecode_to_copy)?;
             evm.pc += 1;
             Ok(())
         }
@@ -26,6 +29,7 @@ pub fn extcodecopy(evm: &mut EVM) -> Result<(), Box<dyn ExitError>>{
 
 pub fn extcodehash(evm: &mut EVM) -> Result<(), Box<dyn ExitError>>{
     let address = evm.stack.pop()?;
+    evm.record_account_access(u256_to_h160(address))?;
     match evm.world_state.get_code_hash(u256_to_h160(address)) {
         Ok(code_hash) => {
             match evm.stack.push(h256_to_u256(code_hash)) {
@@ -43,6 +47,7 @@ pub fn extcodehash(evm: &mut EVM) -> Result<(), Box<dyn ExitError>>{
 /// 获取某个地址的code长度
 pub fn extcodesize(evm: &mut EVM) -> Result<(), Box<dyn ExitError>>{
     let address = evm.stack.pop()?;
+    evm.record_account_access(u256_to_h160(address))?;
     match evm.world_state.get_code(u256_to_h160(address)) {
         Ok(bytecode) => {
             let length = U256::from(bytecode.len());
@@ -1,15 +1,18 @@
-use std::ops::{Add, Div, Mul, Sub};
-use primitive_types::{U256};
+use ethnum::I256;
+use primitive_types::{U256, U512};
 
 use crate::error::exit::*;
 use crate::evm::EVM;
+use crate::machine::Gas::exp_cost;
+use crate::utils::{i256_to_u256, u256_to_i256};
 
 /// add sub mul div mod addmod mulmod smod exp signextend sdiv
+/// 所有运算结果均对2^256取模（溢出回绕），除数或模数为0时结果为0
 pub fn add(evm: &mut EVM) -> Result<(), Box<dyn ExitError>> {
     let a = evm.stack.pop()?;
     let b = evm.stack.pop()?;
 
-    let res = a.add(b);
+    let (res, _) = a.overflowing_add(b);
     match evm.stack.push(res) {
         Ok(_) => {
             evm.pc += 1;
@@ -23,7 +26,7 @@ pub fn sub(evm: &mut EVM) -> Result<(), Box<dyn ExitError>> {
     let a = evm.stack.pop()?;
     let b = evm.stack.pop()?;
 
-    let res = a.sub(b);
+    let (res, _) = a.overflowing_sub(b);
     match evm.stack.push(res) {
         Ok(_) => {
             evm.pc += 1;
@@ -37,7 +40,7 @@ pub fn mul(evm: &mut EVM) -> Result<(), Box<dyn ExitError>> {
     let a = evm.stack.pop()?;
     let b = evm.stack.pop()?;
 
-    let res = a.mul(b);
+    let (res, _) = a.overflowing_mul(b);
     match evm.stack.push(res) {
         Ok(_) => {
             evm.pc += 1;
@@ -51,8 +54,7 @@ pub fn div(evm: &mut EVM) -> Result<(), Box<dyn ExitError>> {
     let a = evm.stack.pop()?;
     let b = evm.stack.pop()?;
 
-    if b == U256::zero() { return Err(Box::new(OpcodeExecutionError::DivisionByZero)) };
-    let res = a.div(b);
+    let res = if b.is_zero() { U256::zero() } else { a / b };
     match evm.stack.push(res) {
         Ok(_) => {
             evm.pc += 1;
@@ -66,7 +68,7 @@ pub fn _mod(evm: &mut EVM) -> Result<(), Box<dyn ExitError>> {
     let a = evm.stack.pop()?;
     let b = evm.stack.pop()?;
 
-    let res = a % b;
+    let res = if b.is_zero() { U256::zero() } else { a % b };
     match evm.stack.push(res) {
         Ok(_) => {
             evm.pc += 1;
@@ -76,12 +78,17 @@ pub fn _mod(evm: &mut EVM) -> Result<(), Box<dyn ExitError>> {
     }
 }
 
+/// (a + b) % N，中间结果使用512位整数，不会丢失进位
 pub fn addmod(evm: &mut EVM) -> Result<(), Box<dyn ExitError>> {
     let a = evm.stack.pop()?;
     let b = evm.stack.pop()?;
     let N = evm.stack.pop()?;
 
-    let res = a.add(b) % N;
+    let res = if N.is_zero() {
+        U256::zero()
+    } else {
+        u512_to_u256((U512::from(a) + U512::from(b)) % U512::from(N))
+    };
     match evm.stack.push(res) {
         Ok(_) => {
             evm.pc += 1;
@@ -91,12 +98,17 @@ pub fn addmod(evm: &mut EVM) -> Result<(), Box<dyn ExitError>> {
     }
 }
 
+/// (a * b) % N，中间结果使用512位整数，不会丢失高位
 pub fn mulmod(evm: &mut EVM) -> Result<(), Box<dyn ExitError>> {
     let a = evm.stack.pop()?;
     let b = evm.stack.pop()?;
     let N = evm.stack.pop()?;
 
-    let res = a.mul(b) % N;
+    let res = if N.is_zero() {
+        U256::zero()
+    } else {
+        u512_to_u256(a.full_mul(b) % U512::from(N))
+    };
     match evm.stack.push(res) {
         Ok(_) => {
             evm.pc += 1;
@@ -106,12 +118,18 @@ pub fn mulmod(evm: &mut EVM) -> Result<(), Box<dyn ExitError>> {
     }
 }
 
+/// 对N取模后的结果一定小于2^256
+fn u512_to_u256(value: U512) -> U256 {
+    U256::try_from(value).expect("value reduced modulo a 256-bit number")
+}
+
+/// 有符号取模，结果的符号与被除数相同，模数为0时结果为0
 pub fn smod(evm: &mut EVM) -> Result<(), Box<dyn ExitError>> {
-    let a:isize = evm.stack.pop().unwrap().as_usize().try_into().unwrap();
-    let b:isize = evm.stack.pop().unwrap().as_usize().try_into().unwrap();
+    let a = u256_to_i256(evm.stack.pop()?);
+    let b = u256_to_i256(evm.stack.pop()?);
 
-    let res = a % b;
-    match evm.stack.push(U256::from(res)) {
+    let res = if b == I256::ZERO { I256::ZERO } else { a.wrapping_rem(b) };
+    match evm.stack.push(i256_to_u256(res)) {
         Ok(_) => {
             evm.pc += 1;
             Ok(())
@@ -123,9 +141,10 @@ pub fn smod(evm: &mut EVM) -> Result<(), Box<dyn ExitError>> {
 pub fn exp(evm: &mut EVM) -> Result<(), Box<dyn ExitError>> {
     let a = evm.stack.pop()?;
     let exponent = evm.stack.pop()?;
+    evm.gas.record_cost(exp_cost(exponent, evm.spec_id))?;
 
-    let res = a.pow(exponent);
-    match evm.stack.push(U256::from(res)) {
+    let (res, _) = a.overflowing_pow(exponent);
+    match evm.stack.push(res) {
         Ok(_) => {
             evm.pc += 1;
             Ok(())
@@ -134,18 +153,34 @@ pub fn exp(evm: &mut EVM) -> Result<(), Box<dyn ExitError>> {
     }
 }
 
+/// 将b + 1字节长度的有符号整数扩展为32字节，b >= 31时x保持不变
 pub fn signextend(evm: &mut EVM) -> Result<(), Box<dyn ExitError>> {
-    let b_size = evm.stack.pop()?;
+    let b = evm.stack.pop()?;
     let x = evm.stack.pop()?;
-    Ok(())
+
+    let res = if b < U256::from(31) {
+        let sign_bit = b.as_usize() * 8 + 7;
+        let mask = (U256::one() << sign_bit) - U256::one();
+        if x.bit(sign_bit) { x | !mask } else { x & mask }
+    } else {
+        x
+    };
+    match evm.stack.push(res) {
+        Ok(_) => {
+            evm.pc += 1;
+            Ok(())
+        }
+        Err(e) => Err(e)
+    }
 }
 
+/// 有符号除法，向0取整，除数为0时结果为0，MIN / -1 溢出后结果为MIN
 pub fn sdiv(evm: &mut EVM) -> Result<(), Box<dyn ExitError>> {
-    let a:isize = evm.stack.pop()?.as_usize().try_into().unwrap();
-    let b:isize = evm.stack.pop()?.as_usize().try_into().unwrap();
+    let a = u256_to_i256(evm.stack.pop()?);
+    let b = u256_to_i256(evm.stack.pop()?);
 
-    let res = a.div(b);
-    match evm.stack.push(U256::from(res)) {
+    let res = if b == I256::ZERO { I256::ZERO } else { a.wrapping_div(b) };
+    match evm.stack.push(i256_to_u256(res)) {
         Ok(_) => {
             evm.pc += 1;
             Ok(())
@@ -154,26 +189,102 @@ pub fn sdiv(evm: &mut EVM) -> Result<(), Box<dyn ExitError>> {
     }
 }
 
-
-
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::opcode::test_utils::{min_i256, neg, run_op};
+
     #[test]
-    fn test_add() {
-        let a = U256::from(5);
-        let b = U256::from(45);
+    fn test_sdiv() {
+        let n = U256::from;
+        assert_eq!(run_op(sdiv, &[n(10), n(3)]), n(3));
+        assert_eq!(run_op(sdiv, &[neg(10), n(3)]), neg(3));
+        assert_eq!(run_op(sdiv, &[n(10), neg(3)]), neg(3));
+        assert_eq!(run_op(sdiv, &[neg(10), neg(3)]), n(3));
+        assert_eq!(run_op(sdiv, &[neg(10), n(0)]), n(0));
+        assert_eq!(run_op(sdiv, &[n(0), neg(1)]), n(0));
+        // MIN / -1 溢出后仍为MIN
+        assert_eq!(run_op(sdiv, &[min_i256(), neg(1)]), min_i256());
+        assert_eq!(run_op(sdiv, &[min_i256(), n(1)]), min_i256());
+        assert_eq!(run_op(sdiv, &[U256::MAX, U256::MAX]), n(1));
+    }
 
-        let res = a.add(b);
-        println!("{:?}", res);
+    #[test]
+    fn test_smod() {
+        let n = U256::from;
+        assert_eq!(run_op(smod, &[n(10), n(3)]), n(1));
+        assert_eq!(run_op(smod, &[neg(10), n(3)]), neg(1));
+        assert_eq!(run_op(smod, &[n(10), neg(3)]), n(1));
+        assert_eq!(run_op(smod, &[neg(10), neg(3)]), neg(1));
+        assert_eq!(run_op(smod, &[neg(10), n(0)]), n(0));
+        assert_eq!(run_op(smod, &[min_i256(), neg(1)]), n(0));
+        assert_eq!(run_op(smod, &[min_i256(), n(3)]), neg(2));
     }
 
     #[test]
-    fn test_exp() {
-        let a = U256::from(5);
-        let exp = U256::from(3);
+    fn test_signextend() {
+        let n = U256::from;
+        assert_eq!(run_op(signextend, &[n(0), n(0xff)]), U256::MAX);
+        assert_eq!(run_op(signextend, &[n(0), n(0x7f)]), n(0x7f));
+        // 高位的数据被丢弃
+        assert_eq!(run_op(signextend, &[n(0), n(0x1ff)]), U256::MAX);
+        assert_eq!(run_op(signextend, &[n(0), n(0x17f)]), n(0x7f));
+        assert_eq!(run_op(signextend, &[n(1), n(0x80ff)]), !n(0xffff) | n(0x80ff));
+        assert_eq!(run_op(signextend, &[n(30), min_i256() >> 8]), !((U256::one() << 247) - U256::one()));
+        assert_eq!(run_op(signextend, &[n(30), min_i256()]), n(0));
+        // b >= 31时x保持不变
+        assert_eq!(run_op(signextend, &[n(31), n(0xff)]), n(0xff));
+        assert_eq!(run_op(signextend, &[n(32), n(0xff)]), n(0xff));
+        assert_eq!(run_op(signextend, &[U256::MAX, n(0xff)]), n(0xff));
+    }
+    #[test]
+    fn test_wrapping_arithmetic() {
+        let n = U256::from;
+        assert_eq!(run_op(add, &[n(5), n(45)]), n(50));
+        assert_eq!(run_op(add, &[U256::MAX, n(1)]), n(0));
+        assert_eq!(run_op(add, &[U256::MAX, U256::MAX]), U256::MAX - 1);
+        assert_eq!(run_op(sub, &[n(0), n(1)]), U256::MAX);
+        assert_eq!(run_op(sub, &[n(1), U256::MAX]), n(2));
+        assert_eq!(run_op(mul, &[U256::MAX, n(2)]), U256::MAX - 1);
+        assert_eq!(run_op(mul, &[min_i256(), n(2)]), n(0));
+        assert_eq!(run_op(mul, &[U256::MAX, U256::MAX]), n(1));
+    }
+
+    #[test]
+    fn test_division_by_zero() {
+        let n = U256::from;
+        assert_eq!(run_op(div, &[n(10), n(3)]), n(3));
+        assert_eq!(run_op(div, &[n(10), n(0)]), n(0));
+        assert_eq!(run_op(_mod, &[n(10), n(3)]), n(1));
+        assert_eq!(run_op(_mod, &[n(10), n(0)]), n(0));
+        assert_eq!(run_op(addmod, &[n(10), n(10), n(0)]), n(0));
+        assert_eq!(run_op(mulmod, &[n(10), n(10), n(0)]), n(0));
+    }
 
-        let result = a.pow(exp);
-        println!("{:?}", result);
+    #[test]
+    fn test_addmod_mulmod() {
+        let n = U256::from;
+        assert_eq!(run_op(addmod, &[n(10), n(10), n(8)]), n(4));
+        // 进位不会丢失：(2^256 - 1) * 2 % 2 = 0，(2^256 - 1 + 2) % 3 = (2^256 + 1) % 3 = 2
+        assert_eq!(run_op(addmod, &[U256::MAX, U256::MAX, n(2)]), n(0));
+        assert_eq!(run_op(addmod, &[U256::MAX, n(2), n(3)]), n(2));
+        assert_eq!(run_op(addmod, &[U256::MAX, U256::MAX, U256::MAX]), n(0));
+        assert_eq!(run_op(mulmod, &[n(10), n(10), n(8)]), n(4));
+        // (2^256 - 1)^2 % (2^256 - 1) = 0，(2^255 * 2) % 3 = 2^256 % 3 = 1
+        assert_eq!(run_op(mulmod, &[U256::MAX, U256::MAX, U256::MAX]), n(0));
+        assert_eq!(run_op(mulmod, &[min_i256(), n(2), n(3)]), n(1));
+        assert_eq!(run_op(mulmod, &[U256::MAX, U256::MAX, n(12)]), n(9));
+    }
+
+    #[test]
+    fn test_exp() {
+        let n = U256::from;
+        assert_eq!(run_op(exp, &[n(5), n(3)]), n(125));
+        assert_eq!(run_op(exp, &[n(0), n(0)]), n(1));
+        assert_eq!(run_op(exp, &[n(2), n(255)]), min_i256());
+        // 溢出回绕
+        assert_eq!(run_op(exp, &[n(2), n(256)]), n(0));
+        assert_eq!(run_op(exp, &[n(3), U256::MAX]), n(3).overflowing_pow(U256::MAX).0);
+        assert_eq!(run_op(exp, &[U256::MAX, n(3)]), U256::MAX);
     }
 }
\ No newline at end of file
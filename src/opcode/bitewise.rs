@@ -1,7 +1,9 @@
 // use std::ops::{BitAnd, BitOr};
+use ethnum::I256;
 use primitive_types::U256;
 use crate::error::exit::*;
 use crate::evm::EVM;
+use crate::utils::{i256_to_u256, u256_to_i256};
 
 /// and or xor not shl shr sar byte
 pub fn and(evm: &mut EVM) -> Result<(),Box<dyn ExitError>> {
@@ -60,12 +62,13 @@ pub fn not(evm: &mut EVM) -> Result<(),Box<dyn ExitError>> {
     }
 }
 
+/// 左移，移位数不小于256时结果为0
 pub fn shl(evm: &mut EVM) -> Result<(),Box<dyn ExitError>> {
     let shift = evm.stack.pop()?;
     let value = evm.stack.pop()?;
 
-    let res = value << shift;
-    match evm.stack.push(U256::from(res)) {
+    let res = if shift < U256::from(256) { value << shift.as_usize() } else { U256::zero() };
+    match evm.stack.push(res) {
         Ok(_) => {
             evm.pc += 1;
             Ok(())
@@ -74,11 +77,13 @@ pub fn shl(evm: &mut EVM) -> Result<(),Box<dyn ExitError>> {
     }
 }
 
+/// 逻辑右移，移位数不小于256时结果为0
 pub fn shr(evm: &mut EVM) -> Result<(),Box<dyn ExitError>> {
     let shift = evm.stack.pop()?;
     let value = evm.stack.pop()?;
-    let res = value >> shift;
-    match evm.stack.push(U256::from(res)) {
+
+    let res = if shift < U256::from(256) { value >> shift.as_usize() } else { U256::zero() };
+    match evm.stack.push(res) {
         Ok(_) => {
             evm.pc += 1;
             Ok(())
@@ -87,13 +92,19 @@ pub fn shr(evm: &mut EVM) -> Result<(),Box<dyn ExitError>> {
     }
 }
 
-/// 有符号整数右移
+/// 有符号整数右移（算术右移），移位数不小于256时负数结果为-1，非负数结果为0
 pub fn sar(evm: &mut EVM) -> Result<(),Box<dyn ExitError>> {
     let shift = evm.stack.pop()?;
-    let value = evm.stack.pop()?;
-
-    let res = value >> shift;
-    match evm.stack.push(U256::from(res)) {
+    let value = u256_to_i256(evm.stack.pop()?);
+
+    let res = if shift < U256::from(256) {
+        value >> shift.as_u32()
+    } else if value.is_negative() {
+        I256::MINUS_ONE
+    } else {
+        I256::ZERO
+    };
+    match evm.stack.push(i256_to_u256(res)) {
         Ok(_) => {
             evm.pc += 1;
             Ok(())
@@ -120,6 +131,34 @@ pub fn byte(evm: &mut EVM) -> Result<(),Box<dyn ExitError>> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::opcode::test_utils::{min_i256, neg, run_op};
+
+    #[test]
+    fn test_sar() {
+        let n = U256::from;
+        assert_eq!(run_op(sar, &[n(4), n(0x80)]), n(0x08));
+        assert_eq!(run_op(sar, &[n(1), neg(1)]), neg(1));
+        assert_eq!(run_op(sar, &[n(1), neg(4)]), neg(2));
+        assert_eq!(run_op(sar, &[n(0), min_i256()]), min_i256());
+        assert_eq!(run_op(sar, &[n(254), min_i256()]), neg(2));
+        assert_eq!(run_op(sar, &[n(255), min_i256()]), neg(1));
+        // 移位数不小于256
+        assert_eq!(run_op(sar, &[n(256), min_i256()]), neg(1));
+        assert_eq!(run_op(sar, &[U256::MAX, neg(1)]), neg(1));
+        assert_eq!(run_op(sar, &[n(256), min_i256() - 1]), n(0));
+        assert_eq!(run_op(sar, &[U256::MAX, n(1)]), n(0));
+    }
+
+    #[test]
+    fn test_logical_shift() {
+        let n = U256::from;
+        assert_eq!(run_op(shl, &[n(255), n(1)]), min_i256());
+        assert_eq!(run_op(shl, &[n(256), n(1)]), n(0));
+        assert_eq!(run_op(shl, &[U256::MAX, n(1)]), n(0));
+        assert_eq!(run_op(shr, &[n(255), U256::MAX]), n(1));
+        assert_eq!(run_op(shr, &[n(256), U256::MAX]), n(0));
+        assert_eq!(run_op(shr, &[U256::MAX, U256::MAX]), n(0));
+    }
     #[test]
     fn test_byte() {
         let i:usize = 1;
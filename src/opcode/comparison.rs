@@ -1,7 +1,7 @@
-use ethnum::AsI256;
 use primitive_types::U256;
 use crate::error::exit::*;
 use crate::evm::EVM;
+use crate::utils::u256_to_i256;
 
 ///lt gt slt sgt eq iszero
 
@@ -37,9 +37,10 @@ pub fn gt(evm: &mut EVM) -> Result<(),Box<dyn ExitError>> {
     }
 }
 
+/// 有符号小于比较
 pub fn slt(evm: &mut EVM) -> Result<(),Box<dyn ExitError>> {
-    let a:isize = evm.stack.pop()?.as_usize().try_into().unwrap();
-    let b:isize = evm.stack.pop()?.as_usize().try_into().unwrap();
+    let a = u256_to_i256(evm.stack.pop()?);
+    let b = u256_to_i256(evm.stack.pop()?);
 
     let res = if a < b {
         U256::one()
@@ -53,14 +54,15 @@ pub fn slt(evm: &mut EVM) -> Result<(),Box<dyn ExitError>> {
     }
 }
 
+/// 有符号大于比较
 pub fn sgt(evm: &mut EVM) -> Result<(),Box<dyn ExitError>> {
-    let a:isize = evm.stack.pop()?.as_usize().try_into().unwrap();
-    let b:isize = evm.stack.pop()?.as_usize().try_into().unwrap();
+    let a = u256_to_i256(evm.stack.pop()?);
+    let b = u256_to_i256(evm.stack.pop()?);
 
     let res = if a > b {
         U256::one()
     } else { U256::zero() };
-    match evm.stack.push(U256::from(res)) {
+    match evm.stack.push(res) {
         Ok(_) => {
             evm.pc += 1;
             Ok(())
@@ -98,4 +100,27 @@ pub fn iszero(evm: &mut EVM) -> Result<(),Box<dyn ExitError>> {
         }
         Err(e) => Err(e)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::opcode::test_utils::{min_i256, neg, run_op};
+
+    #[test]
+    fn test_signed_comparison() {
+        let n = U256::from;
+        let max_i256 = min_i256() - U256::one();
+        assert_eq!(run_op(slt, &[neg(1), n(0)]), n(1));
+        assert_eq!(run_op(slt, &[n(0), neg(1)]), n(0));
+        assert_eq!(run_op(slt, &[min_i256(), max_i256]), n(1));
+        assert_eq!(run_op(slt, &[neg(2), neg(1)]), n(1));
+        assert_eq!(run_op(slt, &[neg(1), neg(1)]), n(0));
+        assert_eq!(run_op(sgt, &[max_i256, min_i256()]), n(1));
+        assert_eq!(run_op(sgt, &[neg(1), n(0)]), n(0));
+        assert_eq!(run_op(sgt, &[n(1), neg(1)]), n(1));
+        assert_eq!(run_op(sgt, &[min_i256(), min_i256()]), n(0));
+        // 无符号比较时-1为最大值
+        assert_eq!(run_op(lt, &[neg(1), n(0)]), n(0));
+    }
+}
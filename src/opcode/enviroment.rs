@@ -3,7 +3,9 @@ use primitive_types::{H256, H160, U256};
 use ethers::utils::keccak256 as ethers_keccak256;
 use crate::error::exit::*;
 use crate::evm::EVM;
-use crate::utils::{h160_to_u256, h256_to_u256, u256_to_h256, pad_right, pad_left, u256_to_h160};
+use crate::globalState::MIN_BLOB_GASPRICE;
+use crate::machine::Gas::{copy_cost, keccak256_cost, log_cost};
+use crate::utils::{h160_to_u256, h256_to_u256, slice_with_padding, u256_to_h160};
 
 
 pub fn pc(evm: &mut EVM) -> Result<(), Box<dyn ExitError>> {
@@ -86,6 +88,38 @@ pub fn basefee(evm : &mut EVM) -> Result<(), Box<dyn ExitError>> {
     }
 }
 
+/// EIP-4844: 获取当前交易第index个blob的versioned hash，index越界时为0
+pub fn blobhash(evm : &mut EVM) -> Result<(), Box<dyn ExitError>> {
+    let index = evm.stack.pop()?;
+    let blobhash = if index < U256::from(evm.blob_hashes.len()) {
+        evm.blob_hashes[index.as_usize()]
+    } else {
+        H256::zero()
+    };
+    match evm.stack.push(h256_to_u256(blobhash)) {
+        Ok(_) => {
+            evm.pc += 1;
+            Ok(())
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// EIP-7516: 获取当前区块的blob base fee
+pub fn blobbasefee(evm : &mut EVM) -> Result<(), Box<dyn ExitError>> {
+    let blob_basefee = match &evm.block {
+        Some(b) => b.blob_basefee(evm.spec_id),
+        None => U256::from(MIN_BLOB_GASPRICE)
+    };
+    match evm.stack.push(blob_basefee) {
+        Ok(_) => {
+            evm.pc += 1;
+            Ok(())
+        }
+        Err(e) => Err(e),
+    }
+}
+
 pub fn blockhash(evm : &mut EVM) -> Result<(), Box<dyn ExitError>> {
     let blockhash = match &evm.block {
         Some(b) => b.blockhash.clone(),
@@ -143,8 +177,13 @@ pub fn coinbase(evm : &mut EVM) -> Result<(), Box<dyn ExitError>> {
 }
 
 pub fn gasprice(evm : &mut EVM) -> Result<(), Box<dyn ExitError>> {
-    let gasprice = evm.block.as_ref().unwrap().basefee;
-    match evm.stack.push(U256::from(gasprice)) {
+    // 交易实际支付的gas价格，没有交易信息时使用区块的base fee
+    let basefee = evm.block.as_ref().map_or(U256::zero(), |block| U256::from(block.basefee));
+    let gasprice = match &evm.transaction {
+        Some(transaction) => transaction.effective_gas_price(basefee),
+        None => basefee,
+    };
+    match evm.stack.push(gasprice) {
         Ok(_) => {
             evm.pc += 1;
             Ok(())
@@ -156,11 +195,13 @@ pub fn gasprice(evm : &mut EVM) -> Result<(), Box<dyn ExitError>> {
 ///将当前evm执行的code从offset开始，长度为size的字节码复制到memory从destOffset开始的空间
 pub fn codecopy(evm : &mut EVM) -> Result<(), Box<dyn ExitError>> {
     let destOffset = evm.stack.pop()?;
-    let offset = evm.stack.pop().unwrap().as_usize();
-    let size = evm.stack.pop().unwrap().as_usize();
-
-    let bytecode_slice = &evm.bytecode.as_ref().unwrap()[offset..offset + size].to_vec();
-    evm.memory.write(destOffset, &bytecode_slice);
+    let offset = evm.stack.pop()?;
+    let size = evm.stack.pop()?;
+    // 先按照U256收取gas，无法支付的长度在转换为usize之前就已经OutOfGas
+    evm.gas.record_cost(copy_cost(size)?)?;
+    evm.record_memory_cost(destOffset, size)?;
+    let bytecode_slice = slice_with_padding(evm.bytecode.as_deref().unwrap_or_default(), offset, size.as_usize());
+    evm.memory.write(destOffset, &bytecode_slice)?;
 
     evm.pc += 1;
     Ok(())
@@ -168,12 +209,9 @@ pub fn codecopy(evm : &mut EVM) -> Result<(), Box<dyn ExitError>> {
 
 ///获取当前正在执行合约的bytecode长度
 pub fn codesize(evm : &mut EVM) -> Result<(), Box<dyn ExitError>> {
-
-    let code = match evm.world_state.get_code(evm.call_stack.last().unwrap().address.unwrap()) {
-        Ok(data) => data,
-        Err(e) => return Err(e)
-    };
-    match evm.stack.push(U256::from(code.len())) {
+    // DELEGATECALL中执行的是目标合约的code，而不是当前上下文地址的code
+    let code_len = evm.bytecode.as_ref().map_or(0, |code| code.len());
+    match evm.stack.push(U256::from(code_len)) {
         Ok(_) => {
             evm.pc += 1;
             Ok(())
@@ -228,15 +266,10 @@ pub fn callvalue(evm : &mut EVM) -> Result<(), Box<dyn ExitError>> {
 
 /// 加载指定位置的32字节inputdata到栈中
 pub fn calldataload(evm : &mut EVM) -> Result<(), Box<dyn ExitError>> {
-    let byte_offset = evm.stack.pop().unwrap().as_usize();
-    let call_data = evm.call_stack.last().unwrap().call_data.clone();
-    let mut call_data_slice:[u8; 32] = if call_data.len() > byte_offset + 32 {
-        let mut slice = [0u8; 32];
-        slice.copy_from_slice(&call_data[byte_offset..byte_offset + 32]);
-        slice
-    } else {
-        pad_right(&call_data[byte_offset..])
-    };
+    let byte_offset = evm.stack.pop()?;
+    let call_data = &evm.call_stack.last().unwrap().call_data;
+    // 超出calldata范围的部分补0
+    let call_data_slice = slice_with_padding(call_data, byte_offset, 32);
     match evm.stack.push(U256::from_big_endian(&call_data_slice)) {
         Ok(_) => {
             evm.pc += 1;
@@ -260,12 +293,14 @@ pub fn calldatasize(evm : &mut EVM) -> Result<(), Box<dyn ExitError>> {
 
 /// 复制calldata中指定位置指定长度的数据到 memory 中
 pub fn calldatacopy(evm : &mut EVM) -> Result<(), Box<dyn ExitError>> {
-    let destOffset = evm.stack.pop().unwrap().as_usize();
-    let offset = evm.stack.pop().unwrap().as_usize();
-    let size = evm.stack.pop().unwrap().as_usize();
-    let copy_data_slice = &evm.call_stack.last().unwrap().call_data.clone()[offset..offset+size].to_vec();
+    let destOffset = evm.stack.pop()?;
+    let offset = evm.stack.pop()?;
+    let size = evm.stack.pop()?;
+    evm.gas.record_cost(copy_cost(size)?)?;
+    evm.record_memory_cost(destOffset, size)?;
+    let copy_data_slice = &slice_with_padding(&evm.call_stack.last().unwrap().call_data, offset, size.as_usize());
 
-    evm.memory.write(U256::from(destOffset), copy_data_slice);
+    evm.memory.write(destOffset, copy_data_slice)?;
     evm.pc += 1;
     Ok(())
 }
@@ -275,7 +310,9 @@ pub fn calldatacopy(evm : &mut EVM) -> Result<(), Box<dyn ExitError>> {
 
 pub fn balance(evm : &mut EVM) -> Result<(), Box<dyn ExitError>> {
     let address = evm.stack.pop()?;
-    let value = evm.world_state.get_balance(u256_to_h160(address)).unwrap();
+    evm.record_account_access(u256_to_h160(address))?;
+    // 不存在的账户余额为0
+    let value = evm.world_state.get_balance(u256_to_h160(address)).unwrap_or_default();
     match evm.stack.push(value) {
         Ok(_) => {
             evm.pc += 1;
@@ -289,6 +326,8 @@ pub fn balance(evm : &mut EVM) -> Result<(), Box<dyn ExitError>> {
 pub fn keccak256(evm : &mut EVM) -> Result<(), Box<dyn ExitError>> {
     let offset = evm.stack.pop()?;
     let size = evm.stack.pop()?;
+    evm.gas.record_cost(keccak256_cost(size)?)?;
+    evm.record_memory_cost(offset, size)?;
     match evm.memory.read(offset, size){
      Ok(data) => {
          let keccak_data = ethers_keccak256(data);
@@ -305,101 +344,68 @@ pub fn keccak256(evm : &mut EVM) -> Result<(), Box<dyn ExitError>> {
 }
 
 
-#[derive(Clone, Debug)]
+/// LOG0~LOG4产生的日志
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Log{
-    address: H160,
-    topics: Vec<U256>,
-    data: Vec<u8>,
+    /// 产生日志的合约地址，即当前执行环境的地址
+    pub address: H160,
+    pub topics: Vec<U256>,
+    pub data: Vec<u8>,
 }
 
-/// 暂时不实现吧
-/// log0将memory中的某段数据作为data，函数参数中有index标记的参数会被写入topics中，其余的写入data中
-pub fn log0(evm : &mut EVM) -> Result<(), Box<dyn ExitError>> {
+/// 栈中依次为 offset size topic0 ... topicN-1，将memory[offset..offset + size]作为data记录日志
+/// 日志会随调用帧revert或异常中止而撤销
+fn log(evm: &mut EVM, topic_count: usize) -> Result<(), Box<dyn ExitError>> {
+    evm.ensure_not_static()?;
     let offset = evm.stack.pop()?;
     let size = evm.stack.pop()?;
+    let mut topics = Vec::with_capacity(topic_count);
+    for _ in 0..topic_count {
+        topics.push(evm.stack.pop()?);
+    }
+    evm.gas.record_cost(log_cost(topic_count as u64, size)?)?;
+    evm.record_memory_cost(offset, size)?;
     let data:Vec<u8> = if size == U256::zero() {
         Vec::new()
     } else {
         evm.memory.read(offset, size)?
     };
-    let address = evm.call_stack.last().unwrap().caller.clone();
+    let address = evm.call_stack.last().unwrap().address.unwrap();
+    evm.push_log(Log { address, topics, data });
     evm.pc += 1;
     Ok(())
+}
 
+pub fn log0(evm : &mut EVM) -> Result<(), Box<dyn ExitError>> {
+    log(evm, 0)
 }
+
 pub fn log1(evm : &mut EVM) -> Result<(), Box<dyn ExitError>> {
-    let offset = evm.stack.pop()?;
-    let size = evm.stack.pop()?;
-    let data:Vec<u8> = if size == U256::zero() {
-        Vec::new()
-    } else {
-        evm.memory.read(offset, size)?
-    };
-    let address = evm.call_stack.last().unwrap().caller.clone();
-    let mut topics = Vec::new();
-    let value = evm.stack.pop()?;
-    topics.push(value);
-    evm.pc += 1;
-    Ok(())
+    log(evm, 1)
 }
 
 pub fn log2(evm : &mut EVM) -> Result<(), Box<dyn ExitError>> {
-    let offset = evm.stack.pop()?;
-    let size = evm.stack.pop()?;
-    let data:Vec<u8> = if size == U256::zero() {
-        Vec::new()
-    } else {
-        evm.memory.read(offset, size)?
-    };
-    let address = evm.call_stack.last().unwrap().caller.clone();
-    let mut topics = Vec::new();
-    let value1 = evm.stack.pop()?;
-    let value2 = evm.stack.pop()?;
-    topics.push(value1);
-    evm.pc += 1;
-    Ok(())
+    log(evm, 2)
 }
+
 pub fn log3(evm : &mut EVM) -> Result<(), Box<dyn ExitError>> {
-    let offset = evm.stack.pop()?;
-    let size = evm.stack.pop()?;
-    let data:Vec<u8> = if size == U256::zero() {
-        Vec::new()
-    } else {
-        evm.memory.read(offset, size)?
-    };
-    let address = evm.call_stack.last().unwrap().caller.clone();
-    let mut topics = Vec::new();
-    let value = evm.stack.pop()?;
-    let value2 = evm.stack.pop()?;
-    let value3 = evm.stack.pop()?;
-    topics.push(value);
-    evm.pc += 1;
-    Ok(())
+    log(evm, 3)
 }
 
 pub fn log4(evm : &mut EVM) -> Result<(), Box<dyn ExitError>> {
-    let offset = evm.stack.pop()?;
-    let size = evm.stack.pop()?;
-    let data:Vec<u8> = if size == U256::zero() {
-        Vec::new()
-    } else {
-        evm.memory.read(offset, size)?
-    };
-    let address = evm.call_stack.last().unwrap().caller.clone();
-    let mut topics = Vec::new();
-    let value = evm.stack.pop()?;
-    let value2 = evm.stack.pop()?;
-    let value3 = evm.stack.pop()?;
-    let value4 = evm.stack.pop()?;
-    topics.push(value);
-    evm.pc += 1;
-    Ok(())
+    log(evm, 4)
 }
 
-pub fn gas(_evm: &mut EVM) -> Result<(), Box<dyn ExitError>> {
-    let _ = _evm.stack.push(U256::MAX);
-    _evm.pc += 1;
-    Ok(())
+/// 获取当前调用帧剩余的gas（已扣除gas操作码本身的费用）
+pub fn gas(evm: &mut EVM) -> Result<(), Box<dyn ExitError>> {
+    let remaining = evm.gas.remaining();
+    match evm.stack.push(U256::from(remaining)) {
+        Ok(_) => {
+            evm.pc += 1;
+            Ok(())
+        }
+        Err(e) => Err(e),
+    }
 }
 
 
@@ -1,74 +1,85 @@
 
+use std::sync::Arc;
 use ethers::utils::{hex, keccak256};
 use ethers::types::Bytes;
 use revm_primitives::Address;
-use primitive_types::{H160, H256, U256};
+use primitive_types::{H160, U256};
 use crate::error::exit::*;
 use crate::evm::EVM;
 use crate::globalState::{Call, CallType};
+use crate::journal::JournalCheckpoint;
 use crate::machine::Memory::Memory;
 use crate::machine::Stack::Stack;
-use crate::utils::{u256_to_h160, vec_to_string, vec_to_u256};
-use crate::globalState::AccountState;
+use crate::transaction::delegation_address;
+use crate::machine::Gas::{copy_cost, initcode_cost, keccak256_cost, Gas, CALLVALUE, CALL_STIPEND, COLD_ACCOUNT_ACCESS, NEWACCOUNT, SELFDESTRUCT_REFUND};
+use crate::spec::SpecId;
+use crate::precompile::Precompile;
+use crate::utils::{address_to_h160, h160_to_u256, u256_to_h160, u256_to_h256, vec_to_string};
 use crate::opcode::arithmatic::add;
 
 /// create create2 return revert jump jumpi stop jumpdest returndatacopy returndatasize invalid selfdestruct
 /// call type: call staticcall delegatecall
 
 pub fn create(evm: &mut EVM) -> Result<(), Box<dyn ExitError>> {
+    evm.ensure_not_static()?;
     let value = evm.stack.pop()?;
     let offset = evm.stack.pop()?;
     let size = evm.stack.pop()?;
+    evm.record_memory_cost(offset, size)?;
     let init_code = match evm.memory.read(offset, size) {
         Ok(data) => Bytes::from(data),
         Err(e) => return Err(e)
     };
-    let nonce = match evm.world_state.get_nonce(evm.call_stack.last().unwrap().caller) {
-        Ok(nonce) => nonce,
-        Err(e) => return Err(Box::new(OpcodeExecutionError::MaxNonce))
-    };
-    let address = evm.call_stack.last().unwrap().caller.as_ref();
-    let create_address = Address::from_slice(address).create(nonce as u64);
-    let account_state = AccountState::new_contract(0, Default::default(), H256::default(), Default::default(), init_code);
-    evm.world_state.new_account(u256_to_h160(vec_to_u256(create_address.to_vec())), account_state);
-    // 创建完新账户之后，带着value去call这个地址
-    let call_result = evm.call(u256_to_h160(vec_to_u256(create_address.to_vec())), value, usize::MAX, CallType::Create);
-    if call_result.is_err() {
-        evm.stack.push(U256::zero());
-    } else {
-        evm.stack.push(U256::from(vec_to_u256(create_address.to_vec())));
+    record_initcode_cost(evm, &init_code)?;
+    // 新合约地址由当前执行合约的地址以及nonce决定
+    let creator = evm.call_stack.last().unwrap().address.unwrap();
+    let nonce = evm.world_state.get_nonce(creator)?;
+    let create_address = address_to_h160(Address::from_slice(creator.as_bytes()).create(nonce as u64));
+    let created = evm.create(create_address, value, init_code, CallType::Create)?;
+    match evm.stack.push(created.map_or(U256::zero(), h160_to_u256)) {
+        Ok(_) => {
+            evm.pc += 1;
+            Ok(())
+        }
+        Err(e) => Err(e)
     }
-
-    evm.pc += 1;
-    Ok(())
 }
 
 pub fn create2(evm: &mut EVM) -> Result<(), Box<dyn ExitError>> {
+    evm.ensure_not_static()?;
     let value = evm.stack.pop()?;
     let offset = evm.stack.pop()?;
     let size = evm.stack.pop()?;
-    let salt:[u8;32] = evm.stack.pop()?.try_into().unwrap();
+    let salt = u256_to_h256(evm.stack.pop()?);
+    // create2需要额外支付对init_code进行哈希的费用
+    evm.gas.record_cost(keccak256_cost(size)?)?;
+    evm.record_memory_cost(offset, size)?;
 
     let init_code = match evm.memory.read(offset, size) {
         Ok(data) => Bytes::from(data),
         Err(e) => return Err(e)
     };
-    let code_hash = keccak256(init_code.clone());
-
-    let address = evm.call_stack.last().unwrap().caller.as_ref();
-    let create_address = Address::from_slice(address).create2(salt, code_hash);
-
-    let account_state = AccountState::new_contract(0, value, H256::default(), Default::default(),  init_code);
-    evm.world_state.new_account(u256_to_h160(vec_to_u256(create_address.to_vec())), account_state);
-    // 创建完新账户之后，带着value去call这个地址
-    let call_result = evm.call(u256_to_h160(vec_to_u256(create_address.to_vec())), value, usize::MAX, CallType::Create2);
-    if call_result.is_err() {
-        evm.stack.push(U256::zero());
-    } else {
-        evm.stack.push(U256::from(vec_to_u256(create_address.to_vec())));
+    record_initcode_cost(evm, &init_code)?;
+    // 新合约地址由当前执行合约的地址、salt以及init code的哈希决定
+    let code_hash = keccak256(&init_code);
+    let creator = evm.call_stack.last().unwrap().address.unwrap();
+    let create_address = address_to_h160(Address::from_slice(creator.as_bytes()).create2(salt.0, code_hash));
+    let created = evm.create(create_address, value, init_code, CallType::Create2)?;
+    match evm.stack.push(created.map_or(U256::zero(), h160_to_u256)) {
+        Ok(_) => {
+            evm.pc += 1;
+            Ok(())
+        }
+        Err(e) => Err(e)
     }
+}
 
-    evm.pc += 1;
+/// EIP-3860: Shanghai之后init code超出长度上限时创建失败，并按字收取init code费用
+fn record_initcode_cost(evm: &mut EVM, init_code: &[u8]) -> Result<(), Box<dyn ExitError>> {
+    if evm.spec_id.is_enabled_in(SpecId::Shanghai) {
+        evm.check_initcode_size(init_code)?;
+        evm.gas.record_cost(initcode_cost(init_code.len() as u64))?;
+    }
     Ok(())
 }
 
@@ -76,11 +87,11 @@ pub fn create2(evm: &mut EVM) -> Result<(), Box<dyn ExitError>> {
 pub fn _return(evm: &mut EVM) -> Result<(), Box<dyn ExitError>> {
     let offset = evm.stack.pop()?;
     let size = evm.stack.pop()?;
+    evm.record_memory_cost(offset, size)?;
 
     match evm.memory.read(offset, size) {
         Ok(copy_data) => {
             evm.return_data = Some(copy_data.clone());
-            println!("call returndata:{:?}", evm.return_data);
             evm.pc = usize::MAX;
             Ok(())
         }
@@ -92,6 +103,7 @@ pub fn _return(evm: &mut EVM) -> Result<(), Box<dyn ExitError>> {
 pub fn revert(evm: &mut EVM) -> Result<(), Box<dyn ExitError>> {
     let offset = evm.stack.pop()?;
     let size = evm.stack.pop()?;
+    evm.record_memory_cost(offset, size)?;
 
     evm.return_data = match evm.memory.read(offset,size) {
         Ok(data) => {
@@ -108,22 +120,29 @@ pub fn revert(evm: &mut EVM) -> Result<(), Box<dyn ExitError>> {
 /// jump 需要对目标pc的opcode进行判断吧，判断是否是jumpdest？
 pub fn jump(evm: &mut EVM) -> Result<(), Box<dyn ExitError>> {
     let counter = evm.stack.pop()?;
-
-    evm.pc = counter.as_usize();
-    Ok(())
+    jump_to(evm, counter)
 }
 
 pub fn jumpi(evm: &mut EVM) -> Result<(), Box<dyn ExitError>> {
     let counter = evm.stack.pop()?;
     let b = evm.stack.pop()?;
     if b != U256::zero() {
-        evm.pc = counter.as_usize();
+        jump_to(evm, counter)?;
     } else {
         evm.pc += 1;
     }
     Ok(())
 }
 
+/// 跳转目标必须是字节码中作为操作码出现的JUMPDEST，否则当前调用帧异常中止
+fn jump_to(evm: &mut EVM, counter: U256) -> Result<(), Box<dyn ExitError>> {
+    if !evm.jumpdests.is_valid(counter) {
+        return Err(Box::new(OpcodeExecutionError::InvalidJump));
+    }
+    evm.pc = counter.as_usize();
+    Ok(())
+}
+
 pub fn stop(evm: &mut EVM) -> Result<(), Box<dyn ExitError>> {
     evm.pc = usize::MAX;
     Ok(())
@@ -137,17 +156,19 @@ pub fn jumpdest(evm: &mut EVM) -> Result<(), Box<dyn ExitError>> {
 /// 将上一层子调用的returndata copy到memory中
 pub fn returndatacopy(evm: &mut EVM) -> Result<(), Box<dyn ExitError>> {
     let destOffset = evm.stack.pop()?;
-    let offset = evm.stack.pop()?.as_usize();
-    let size = evm.stack.pop()?.as_usize();
-    // 如果没有returndata则传入一段空数组
-    let copy_data:Vec<u8> = match &evm.return_data {
-        None => { Vec::new() }
-        Some(ret_data) => {
-            ret_data[offset..offset + size].to_vec()
-        }
+    let offset = evm.stack.pop()?;
+    let size = evm.stack.pop()?;
+    evm.gas.record_cost(copy_cost(size)?)?;
+    evm.record_memory_cost(destOffset, size)?;
+    // 子调用的returndata，没有发生过子调用或者子调用异常中止时为空
+    let ret_data = evm.sub_return_data.as_deref().unwrap_or_default();
+    // EIP-211: 读取超出returndata范围时当前调用帧异常中止，returndata为空时同样如此
+    let copy_data = match offset.checked_add(size) {
+        Some(end) if end <= U256::from(ret_data.len()) => ret_data[offset.as_usize()..end.as_usize()].to_vec(),
+        _ => return Err(Box::new(OpcodeExecutionError::OutOfOffset)),
     };
 
-    evm.memory.write(destOffset, &*copy_data);
+    evm.memory.write(destOffset, &copy_data)?;
     evm.pc += 1;
     Ok(())
 }
@@ -160,7 +181,6 @@ pub fn returndatasize(evm: &mut EVM) -> Result<(), Box<dyn ExitError>> {
             data.len()
         }
     };
-    println!("ret_data_size is :{:?}", ret_data_size);
 
     match evm.stack.push(U256::from(ret_data_size)) {
         Ok(_) => {
@@ -171,36 +191,56 @@ pub fn returndatasize(evm: &mut EVM) -> Result<(), Box<dyn ExitError>> {
     }
 }
 
+/// 无效操作码，当前调用帧异常中止并消耗全部剩余gas
 pub fn invalid(evm: &mut EVM) -> Result<(), Box<dyn ExitError>> {
-    evm.pc += 1;
-    Ok(())
+    Err(Box::new(OpcodeExecutionError::DesignatedInvalid))
 }
 
 /// 将当前执行code地址上的全部ether发送到指定address
 pub fn selfdestruct(evm: &mut EVM) -> Result<(), Box<dyn ExitError>> {
+    evm.ensure_not_static()?;
     let address = evm.stack.pop()?;
-    let value = match evm.world_state.get_balance(u256_to_h160(address)) {
-        Ok(balance) => {
-            balance
-        }
-        Err(e) => { return Err(e); }
-    };
-    match evm.world_state.set_balance(evm.call_stack.last().unwrap().address.unwrap(), U256::zero()){
-        Ok(_) => {}
-        Err(e) => { return Err(e); }
-    };
-    match evm.world_state.set_balance(u256_to_h160(address), value){
-        Ok(_) => {}
-        Err(e) => { return Err(e); }
+    // 受益人地址为cold时需要额外支付访问费用
+    if evm.access_address(u256_to_h160(address)) && evm.spec_id.is_enabled_in(SpecId::Berlin) {
+        evm.gas.record_cost(COLD_ACCOUNT_ACCESS)?;
+    }
+    // EIP-150: 向不存在的账户转入余额需要支付创建新账户的费用，EIP-161之后只有转入非零余额且受益人为空账户时才收取
+    let self_balance = evm.world_state.get_balance(evm.call_stack.last().unwrap().address.unwrap()).unwrap_or_default();
+    let creates_account = if evm.spec_id.is_enabled_in(SpecId::SpuriousDragon) {
+        !self_balance.is_zero() && evm.world_state.account_is_empty(u256_to_h160(address))
+    } else {
+        evm.spec_id.is_enabled_in(SpecId::TangerineWhistle) && !evm.world_state.account_is_exsit(u256_to_h160(address))
     };
-    evm.world_state.remove_account(evm.call_stack.last().unwrap().address.unwrap());
+    if creates_account {
+        evm.gas.record_cost(NEWACCOUNT)?;
+    }
+    // 合约余额全部转给受益人
+    let contract = evm.call_stack.last().unwrap().address.unwrap();
+    let beneficiary = u256_to_h160(address);
+    if beneficiary != contract {
+        evm.world_state.transfer(contract, beneficiary, self_balance)?;
+    }
+    // EIP-6780: Cancun之后只有在当前交易中创建的合约才会被删除，其他合约只转移余额，受益人为自身时余额不变
+    if !evm.spec_id.is_enabled_in(SpecId::Cancun) || evm.world_state.is_created_in_transaction(contract) {
+        // 合约在交易结束时删除，受益人为自身时余额被销毁
+        evm.world_state.set_balance(contract, U256::zero())?;
+        let destructed = evm.world_state.mark_selfdestruct(contract);
+        // EIP-3529: London之前第一次SELFDESTRUCT可以获得退款
+        if !destructed && !evm.spec_id.is_enabled_in(SpecId::London) {
+            evm.gas.record_refund(SELFDESTRUCT_REFUND);
+        }
+    }
     evm.pc = usize::MAX;
     Ok(())
 }
 
 pub fn call(evm: &mut EVM) -> Result<(), Box<dyn ExitError>> {
     let (gas, address, value, argsOffset, argsSize, retOffset, retSize) =
-        call_pop(evm, CallType::Call);
+        call_pop(evm, CallType::Call)?;
+    // STATICCALL中不允许进行转账
+    if !value.unwrap().is_zero() {
+        evm.ensure_not_static()?;
+    }
     match call_core(
         evm,
         gas,
@@ -217,11 +257,10 @@ pub fn call(evm: &mut EVM) -> Result<(), Box<dyn ExitError>> {
     }
 }
 
-/// todo!:目前来说staticcall还没有对状态更改进行检查
+/// 子调用及其发起的所有调用都处于static模式，修改状态的操作码会导致异常中止
 pub fn staticcall(evm: &mut EVM) -> Result<(), Box<dyn ExitError>> {
     let (gas, address, value, argsOffset, argsSize, retOffset, retSize) =
-        call_pop(evm, CallType::StaticCall);
-    println!("static call to:{}, callvalue:{}", address, value.unwrap_or(U256::zero()));
+        call_pop(evm, CallType::StaticCall)?;
     match call_core(
         evm,
         gas,
@@ -231,50 +270,68 @@ pub fn staticcall(evm: &mut EVM) -> Result<(), Box<dyn ExitError>> {
         argsSize,
         retOffset,
         retSize,
-        CallType::Call,
+        CallType::StaticCall,
     ) {
         Ok(_) => { Ok(()) }
         Err(e) => Err(e)
     }
 }
 
+/// 在当前合约的上下文中执行目标合约的code，msg.sender与msg.value沿用当前调用帧，不进行转账
 pub fn delegatecall(evm: &mut EVM) -> Result<(), Box<dyn ExitError>> {
-    let (gas, address, value, argsOffset, argsSize, retOffset, retSize) =
-        call_pop(evm, CallType::DelegateCall);
+    let (gas, address, _, argsOffset, argsSize, retOffset, retSize) =
+        call_pop(evm, CallType::DelegateCall)?;
     match call_core(
         evm,
         gas,
         address,
-        value.unwrap(),
+        U256::zero(),
         argsOffset,
         argsSize,
         retOffset,
         retSize,
-        CallType::Call,
+        CallType::DelegateCall,
     ) {
         Ok(_) => { Ok(()) }
         Err(e) => Err(e)
     }
 }
 
+/// 在当前合约的上下文中执行目标合约的code，msg.sender为当前合约，value由当前合约转给自己
 pub fn callcode(evm: &mut EVM) -> Result<(), Box<dyn ExitError>> {
-    Err(Box::new(OpcodeExecutionError::NotImplemented(0xF2)))
+    let (gas, address, value, argsOffset, argsSize, retOffset, retSize) =
+        call_pop(evm, CallType::CallCode)?;
+    match call_core(
+        evm,
+        gas,
+        address,
+        value.unwrap(),
+        argsOffset,
+        argsSize,
+        retOffset,
+        retSize,
+        CallType::CallCode,
+    ) {
+        Ok(_) => { Ok(()) }
+        Err(e) => Err(e)
+    }
 }
 
+/// call类操作码的栈参数，栈中参数不足时返回StackUnderflow
 pub fn call_pop(
     _evm: &mut EVM,
     call_type: CallType,
-) -> (U256, U256, Option<U256>, U256, U256, U256, U256) {
+) -> Result<(U256, U256, Option<U256>, U256, U256, U256, U256), Box<dyn ExitError>> {
     match call_type {
-        CallType::Call => {
-            let gas = _evm.stack.pop().unwrap();
-            let address = _evm.stack.pop().unwrap();
-            let value = _evm.stack.pop().unwrap();
-            let argsOffset = _evm.stack.pop().unwrap();
-            let argsSize = _evm.stack.pop().unwrap();
-            let retOffset = _evm.stack.pop().unwrap();
-            let retSize = _evm.stack.pop().unwrap();
-            (
+        CallType::Call | CallType::CallCode => {
+            let gas = _evm.stack.pop()?;
+            let address = _evm.stack.pop()?;
+            let value = _evm.stack.pop()?;
+            let argsOffset = _evm.stack.pop()?;
+            let argsSize = _evm.stack.pop()?;
+            let retOffset = _evm.stack.pop()?;
+            let retSize = _evm.stack.pop()?;
+            Ok((
                 gas,
                 address,
                 Some(value),
@@ -282,21 +339,52 @@ pub fn call_pop(
                 argsSize,
                 retOffset,
                 retSize,
-            )
+            ))
         }
         CallType::StaticCall | CallType::DelegateCall => {
-            let gas = _evm.stack.pop().unwrap();
-            let address = _evm.stack.pop().unwrap();
-            let argsOffset = _evm.stack.pop().unwrap();
-            let argsSize = _evm.stack.pop().unwrap();
-            let retOffset = _evm.stack.pop().unwrap();
-            let retSize = _evm.stack.pop().unwrap();
-            (gas, address, None, argsOffset, argsSize, retOffset, retSize)
+            let gas = _evm.stack.pop()?;
+            let address = _evm.stack.pop()?;
+            let argsOffset = _evm.stack.pop()?;
+            let argsSize = _evm.stack.pop()?;
+            let retOffset = _evm.stack.pop()?;
+            let retSize = _evm.stack.pop()?;
+            Ok((gas, address, None, argsOffset, argsSize, retOffset, retSize))
+        }
+        _ => Err(Box::new(EVMError::Error)),
+    }
+}
+
+/// 执行预编译合约：成功时退还剩余gas并将结果写入memory，失败时消耗全部转发的gas
+fn call_precompile(
+    evm: &mut EVM,
+    precompile: Arc<dyn Precompile>,
+    calldata: &[u8],
+    gas_limit: u64,
+    retOffset: U256,
+    retSize: U256,
+    checkpoint: JournalCheckpoint,
+) -> Result<(), Box<dyn ExitError>> {
+    let success = match precompile.run(calldata, gas_limit) {
+        // 预编译合约声明的gas消耗超出上限时视为gas耗尽
+        Ok(result) if result.gas_used > gas_limit => false,
+        Ok(result) => {
+            evm.gas.erase_cost(gas_limit - result.gas_used);
+            let copy_len = result.output.len().min(retSize.as_usize());
+            evm.memory.write(retOffset, &result.output[..copy_len])?;
+            evm.sub_return_data = Some(result.output);
+            true
         }
-        _ => {
-            panic!("error pop for call");
+        Err(_) => {
+            evm.sub_return_data = None;
+            false
         }
+    };
+    // 执行失败时撤销转账
+    if !success {
+        evm.revert_to(checkpoint);
     }
+    evm.pc += 1;
+    evm.stack.push(if success { U256::one() } else { U256::zero() })
 }
 
 pub fn call_core(
@@ -310,14 +398,58 @@ pub fn call_core(
     retSize:U256,
     call_type: CallType
     ) -> Result<(), Box<dyn ExitError>> {
+    evm.record_memory_cost(argsOffset, argsSize)?;
+    evm.record_memory_cost(retOffset, retSize)?;
     let calldata = match evm.memory.read(argsOffset, argsSize){
         Ok(data) => data,
         Err(e) => return Err(e)
     };
     let calldata = Bytes::from(calldata);
-    // call深度限制
-    if evm.call_depth > 1024 {
-        return Err(Box::new(OpcodeExecutionError::CallTooDeep));
+
+    evm.record_account_access(u256_to_h160(address))?;
+    // EIP-7702: 调用委托账户时还需要访问被委托的地址
+    let delegate = evm.world_state.get_code(u256_to_h160(address)).ok().and_then(|code| delegation_address(&code));
+    if let Some(delegate) = delegate {
+        evm.record_account_access(delegate)?;
+    }
+    // 携带value的调用需要支付转账费用
+    let transfers_value = !value.is_zero() && (call_type == CallType::Call || call_type == CallType::CallCode);
+    if transfers_value {
+        evm.gas.record_cost(CALLVALUE)?;
+    }
+    // 调用会创建新账户时需要支付创建费用：EIP-161之前目标账户不存在即收取，之后只有向空账户转账才收取
+    if call_type == CallType::Call {
+        let target = u256_to_h160(address);
+        let creates_account = if evm.spec_id.is_enabled_in(SpecId::SpuriousDragon) {
+            transfers_value && evm.world_state.account_is_empty(target)
+        } else {
+            !evm.world_state.account_is_exsit(target)
+        };
+        if creates_account {
+            evm.gas.record_cost(NEWACCOUNT)?;
+        }
+    }
+    // EIP-150: 子调用最多只能获得当前剩余gas的63/64，转账调用额外获得2300的津贴
+    let requested = if gas > U256::from(u64::MAX) { u64::MAX } else { gas.as_u64() };
+    let gas_limit = evm.record_forwarded_gas(requested)?;
+    let stipend = if transfers_value { CALL_STIPEND } else { 0 };
+    // 超出call深度限制或者余额不足以支付转账金额时调用直接失败，不执行目标合约，转发的gas以及津贴退还给当前调用帧
+    let from = evm.call_stack.last().unwrap().address.unwrap();
+    if evm.call_depth >= 1024 || (transfers_value && evm.world_state.get_balance(from)? < value) {
+        evm.gas.erase_cost(gas_limit + stipend);
+        evm.sub_return_data = None;
+        evm.pc += 1;
+        return evm.stack.push(U256::zero());
+    }
+    // 子调用revert或异常中止时回滚其对全局状态以及瞬态存储的全部修改，包括转账
+    let checkpoint = evm.checkpoint();
+    // 只有CALL会在账户之间转账，CALLCODE的转账双方均为当前合约；EIP-161之前CALL不存在的账户同样会创建该账户
+    if call_type == CallType::Call && (!value.is_zero() || !evm.spec_id.is_enabled_in(SpecId::SpuriousDragon)) {
+        evm.world_state.transfer(from, u256_to_h160(address), value)?;
+    }
+    // 目标地址为预编译合约时直接执行预编译合约，不再查找code
+    if let Some(precompile) = evm.precompiles().get(&u256_to_h160(address)) {
+        return call_precompile(evm, precompile, &calldata, gas_limit + stipend, retOffset, retSize, checkpoint);
     }
 
     //保存上下文
@@ -326,32 +458,29 @@ pub fn call_core(
     let now_call = evm.call_stack.last().unwrap();
     // 如果是最外层的call操作应该是不需要在创建一个Call的，就比如说userA -> contractB, 这个call应该是直接就在外层调用函数的时候由用户构建
     // 但是还是有一个问题，就是如果是由用户直接参与的外部调用，应该不会遇到call类型操作码吧 (√)
-    println!("now execution address: {}", address);
+    let is_static = now_call.is_static || call_type == CallType::StaticCall;
+    // DELEGATECALL与CALLCODE执行目标合约的code，但存储、余额等上下文仍为当前合约
+    // DELEGATECALL沿用当前调用帧的msg.sender与msg.value，CALLCODE的msg.sender为当前合约
+    let (caller, context_address, value) = match call_type {
+        CallType::DelegateCall => (now_call.caller, now_call.address, now_call.value),
+        CallType::CallCode => (now_call.address.unwrap(), now_call.address, value),
+        _ => (now_call.address.unwrap(), Some(u256_to_h160(address)), value),
+    };
     let _call = Call {
-        from: now_call.to.unwrap(),
+        from: now_call.address.unwrap(),
         to: Some(u256_to_h160(address)),
         value,
         call_data: calldata,
-        caller: if call_type == CallType::DelegateCall{
-            now_call.caller
-        } else {
-            now_call.address.unwrap()
-        },
-        // 这里的is_err操作难道是如果目标地址没有code，则重新回到当前地址执行？
-        address: if evm.world_state.get_code(u256_to_h160(address)).is_err(){
-            now_call.address
-        } else if call_type.eq(&CallType::DelegateCall){
-            now_call.to
-        } else {
-            Some(u256_to_h160(address))
-        },
+        caller,
+        address: context_address,
         call_type,
         call_depth: evm.call_depth,
         pc: evm.pc,
-        world_state: evm.world_state.clone(),
+        is_static,
     };
     evm.evm_stack.push(evm.stack.clone());
     evm.memory_stack.push(evm.memory.clone());
+    evm.gas_stack.push(std::mem::replace(&mut evm.gas, Gas::new(gas_limit + stipend)));
     evm.call_stack.push(_call.clone());
     evm.call_depth += 1;
     if evm.world_state.get_code(u256_to_h160(address)).is_err() {
@@ -361,49 +490,59 @@ pub fn call_core(
         evm.evm_stack.pop();
         evm.call_stack.pop();
         evm.memory_stack.pop();
+        let child_gas = std::mem::replace(&mut evm.gas, evm.gas_stack.pop().unwrap());
+        evm.gas.erase_cost(child_gas.remaining());
+        // 没有执行任何code，returndata为空
+        evm.sub_return_data = None;
         match evm.stack.push(U256::one()) {
             Ok(_) => {}
             Err(e) => return Err(e)
         }
         return Ok(());
     } else {
-        evm.bytecode = Some(evm.world_state.get_code(u256_to_h160(address)).unwrap());
+        let code = evm.world_state.get_code(u256_to_h160(address)).unwrap();
+        evm.bytecode = Some(evm.resolve_delegation(code));
     }
     evm.pc = 0;
     evm.stack = Stack::new(1024);
     evm.memory = Memory::new(1024);
-    if value != U256::zero() {
-        evm.world_state.sub_balance(_call.from, value);
-        evm.world_state
-            .add_balance(u256_to_h160(address), value);
-    }
     // =========================执行字节码==========================
-    let _ = evm.interepter();
+    let interpret_result = evm.interepter();
     // =========================恢复上下文==========================
     evm.bytecode = before_code;
     evm.pc = _call.pc + 1;
     evm.call_depth -= 1;
     evm.stack = evm.evm_stack.pop().unwrap();
     evm.memory = evm.memory_stack.pop().unwrap();
+    // 子调用未使用的gas退还给当前调用帧，异常中止的子调用不会退还gas
+    let child_gas = std::mem::replace(&mut evm.gas, evm.gas_stack.pop().unwrap());
+    evm.gas.erase_cost(child_gas.remaining());
     evm.call_stack.pop();
+    let success = interpret_result.is_ok() && !evm.is_revert;
+    // 只有成功结束的子调用才会保留其产生的退款
+    if success {
+        evm.gas.record_refund(child_gas.refunded());
+    }
+    if !success {
+        evm.revert_to(checkpoint);
+    }
     // 在每次call结束后，sub_returndata就是当前的returndata，这里我可以理解sub_return_data是存放子调用的return数据，return_data存放的是当前调用的return数据，但是interepter()中好像是没有处理return_data的逻辑
     evm.sub_return_data = match evm.return_data.clone(){
         None => {None}
         Some(ret_data) => {
-            evm.memory.write(retOffset, &*ret_data);
+            // 最多复制retSize字节，返回区域的memory扩展费用在调用之前已经收取
+            let copy_len = ret_data.len().min(retSize.as_usize());
+            evm.memory.write(retOffset, &ret_data[..copy_len])?;
             Some(ret_data)
         }
     };
     // 下一个call的returndata还是空
     evm.return_data = None;
-    if _call.call_type.eq(&CallType::StaticCall) {
-        // 恢复上下文
-        evm.world_state = _call.world_state;
-    }
-    if evm.is_revert {
-        evm.stack.push(U256::zero())?;
-    } else {
+    evm.is_revert = false;
+    if success {
         evm.stack.push(U256::one())?;
+    } else {
+        evm.stack.push(U256::zero())?;
     };
     Ok(())
 }
@@ -7,4 +7,33 @@ pub mod arithmatic;
 pub mod bitewise;
 pub mod comparison;
 pub mod flow;
-pub mod structure;
\ No newline at end of file
+pub mod structure;
+
+#[cfg(test)]
+pub(crate) mod test_utils {
+    use primitive_types::U256;
+    use crate::error::exit::ExitError;
+    use crate::evm::EVM;
+    use crate::globalState::WorldState;
+
+    /// 有符号整数的最小值 -2^255
+    pub fn min_i256() -> U256 {
+        U256::one() << 255
+    }
+
+    /// 有符号整数-n的补码表示
+    pub fn neg(n: u64) -> U256 {
+        (!U256::from(n)).overflowing_add(U256::one()).0
+    }
+
+    /// 在新的EVM中执行单个操作码并返回栈顶结果，args[0]为执行前的栈顶
+    pub fn run_op(op: fn(&mut EVM) -> Result<(), Box<dyn ExitError>>, args: &[U256]) -> U256 {
+        let mut evm = EVM::new(WorldState::default());
+        for arg in args.iter().rev() {
+            evm.stack.push(*arg).unwrap();
+        }
+        op(&mut evm).unwrap();
+        assert_eq!(evm.pc, 1);
+        evm.stack.pop().unwrap()
+    }
+}
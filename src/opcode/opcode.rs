@@ -1,4 +1,5 @@
 use std::fmt;
+use crate::spec::SpecId;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Opcode {
@@ -122,7 +123,11 @@ pub enum Opcode {
     SELFBALANCE,
     /// Opcode 0x48 - Get the base fee
     BASEFEE,
-    // 0x49 - 0x4F are invalid
+    /// Opcode 0x49 - Get versioned hashes
+    BLOBHASH,
+    /// Opcode 0x4A - Returns the value of the blob base-fee of the current block
+    BLOBBASEFEE,
+    // 0x4B - 0x4F are invalid
 
     // 0x50 range - 'storage' and execution.
     /// Opcode 0x50 - Remove item from stack
@@ -152,7 +157,11 @@ pub enum Opcode {
     /// Opcode 0x5B - Mark a valid destination for jumps
     JUMPDEST,
 
-    // 0x5C - 0x5E are invalid
+    /// Opcode 0x5C - Load word from transient storage
+    TLOAD,
+    /// Opcode 0x5D - Save word to transient storage
+    TSTORE,
+    /// Opcode 0x5E - Copy memory areas
     MCOPY,
     // 0x5F range - pushes.
     /// Opcode 0x5F - Place the constant value 0 on stack
@@ -340,4 +349,83 @@ impl fmt::Display for Opcode {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{:?}", self)
     }
-}
\ No newline at end of file
+}
+impl Opcode {
+    /// 引入该操作码的硬分叉，在此之前该字节被视为无效操作码
+    pub fn introduced_in(&self) -> SpecId {
+        match self {
+            Opcode::DELEGATECALL => SpecId::Homestead,
+            Opcode::RETURNDATASIZE | Opcode::RETURNDATACOPY | Opcode::STATICCALL | Opcode::REVERT => SpecId::Byzantium,
+            Opcode::SHL | Opcode::SHR | Opcode::SAR | Opcode::EXTCODEHASH | Opcode::CREATE2 => SpecId::Constantinople,
+            Opcode::CHAINID | Opcode::SELFBALANCE => SpecId::Istanbul,
+            Opcode::BASEFEE => SpecId::London,
+            Opcode::PUSH0 => SpecId::Shanghai,
+            Opcode::TLOAD | Opcode::TSTORE | Opcode::MCOPY | Opcode::BLOBHASH | Opcode::BLOBBASEFEE => SpecId::Cancun,
+            _ => SpecId::Frontier,
+        }
+    }
+
+    /// 操作码的基础gas费用，动态部分（memory扩展、复制、log等）在执行对应操作码时另行收取
+    pub fn gas_cost(&self, spec: SpecId) -> u64 {
+        match self {
+            Opcode::STOP | Opcode::RETURN | Opcode::REVERT | Opcode::INVALID | Opcode::SSTORE => 0,
+            Opcode::JUMPDEST => 1,
+            Opcode::ADDRESS | Opcode::ORIGIN | Opcode::CALLER | Opcode::CALLVALUE
+            | Opcode::CALLDATASIZE | Opcode::CODESIZE | Opcode::GASPRICE | Opcode::RETURNDATASIZE
+            | Opcode::COINBASE | Opcode::TIMESTAMP | Opcode::NUMBER | Opcode::DIFFICULTY
+            | Opcode::GASLIMIT | Opcode::CHAINID | Opcode::BASEFEE | Opcode::BLOBBASEFEE | Opcode::POP | Opcode::PC
+            | Opcode::MSIZE | Opcode::GAS | Opcode::PUSH0 => 2,
+            Opcode::ADD | Opcode::SUB | Opcode::LT | Opcode::GT | Opcode::SLT | Opcode::SGT
+            | Opcode::EQ | Opcode::ISZERO | Opcode::AND | Opcode::OR | Opcode::XOR | Opcode::NOT
+            | Opcode::BYTE | Opcode::SHL | Opcode::SHR | Opcode::SAR | Opcode::CALLDATALOAD
+            | Opcode::CALLDATACOPY | Opcode::CODECOPY | Opcode::RETURNDATACOPY | Opcode::MLOAD
+            | Opcode::MSTORE | Opcode::MSTORE8 | Opcode::MCOPY | Opcode::BLOBHASH => 3,
+            Opcode::MUL | Opcode::DIV | Opcode::SDIV | Opcode::MOD | Opcode::SMOD
+            | Opcode::SIGNEXTEND | Opcode::SELFBALANCE => 5,
+            Opcode::ADDMOD | Opcode::MULMOD | Opcode::JUMP => 8,
+            Opcode::EXP | Opcode::JUMPI => 10,
+            Opcode::BLOCKHASH => 20,
+            Opcode::KECCAK256 => 30,
+            // EIP-1153: 瞬态存储的读写费用与warm存储读取相同
+            Opcode::TLOAD | Opcode::TSTORE => 100,
+            Opcode::LOG0 | Opcode::LOG1 | Opcode::LOG2 | Opcode::LOG3 | Opcode::LOG4 => 375,
+            // EIP-2929: Berlin之后表中为warm访问费用，cold访问的额外费用在执行时收取
+            _ if spec.is_enabled_in(SpecId::Berlin) && self.is_state_access() => 100,
+            Opcode::BALANCE => {
+                if spec.is_enabled_in(SpecId::Istanbul) { 700 }
+                else if spec.is_enabled_in(SpecId::TangerineWhistle) { 400 }
+                else { 20 }
+            }
+            Opcode::EXTCODESIZE | Opcode::EXTCODECOPY => {
+                if spec.is_enabled_in(SpecId::TangerineWhistle) { 700 } else { 20 }
+            }
+            Opcode::EXTCODEHASH => {
+                if spec.is_enabled_in(SpecId::Istanbul) { 700 } else { 400 }
+            }
+            Opcode::CALL | Opcode::CALLCODE | Opcode::DELEGATECALL | Opcode::STATICCALL => {
+                if spec.is_enabled_in(SpecId::TangerineWhistle) { 700 } else { 40 }
+            }
+            Opcode::SLOAD => {
+                if spec.is_enabled_in(SpecId::Istanbul) { 800 }
+                else if spec.is_enabled_in(SpecId::TangerineWhistle) { 200 }
+                else { 50 }
+            }
+            Opcode::SELFDESTRUCT => {
+                if spec.is_enabled_in(SpecId::TangerineWhistle) { 5000 } else { 0 }
+            }
+            Opcode::CREATE | Opcode::CREATE2 => 32000,
+            // push1-push32、dup、swap
+            _ => 3,
+        }
+    }
+
+    /// 受EIP-2929 warm/cold规则影响的操作码（SELFDESTRUCT与SSTORE单独计算）
+    fn is_state_access(&self) -> bool {
+        matches!(
+            self,
+            Opcode::BALANCE | Opcode::EXTCODESIZE | Opcode::EXTCODECOPY | Opcode::EXTCODEHASH
+            | Opcode::CALL | Opcode::CALLCODE | Opcode::DELEGATECALL | Opcode::STATICCALL
+            | Opcode::SLOAD
+        )
+    }
+}
@@ -3,16 +3,18 @@ use ethers::utils::__serde_json::to_vec;
 use primitive_types::{H256, U256};
 use crate::error::exit::*;
 use crate::evm::EVM;
-use crate::utils::{h256_to_u256, u256_to_h256, vec_to_u256, u256_to_vec};
+use crate::spec::SpecId;
+use crate::machine::Gas::{copy_cost, sstore_cost, sstore_refund, CALL_STIPEND, COLD_SLOAD, WARM_STORAGE_READ};
+use crate::utils::{h256_to_u256, u256_to_h256, vec_to_u256, u256_to_vec, slice_with_padding};
 
 // push dup swap sstore sload mstore mstore8 mload mcopy pop
 pub fn _push(evm: &mut EVM, value_len: usize) -> Result<(), Box<dyn ExitError>> {
     let put_value = if value_len == 0 {
         U256::zero()
     } else {
+        // 字节码末尾被截断的PUSH指令缺少的字节补0
         let start = evm.pc + 1;
-        let end = start + value_len;
-        U256::from(&evm.bytecode.as_ref().unwrap()[start..end])
+        U256::from(&slice_with_padding(evm.bytecode.as_deref().unwrap_or_default(), U256::from(start), value_len)[..])
     };
 
     match evm.stack.push(put_value) {
@@ -53,11 +55,24 @@ pub fn _swap(evm: &mut EVM, swp_loc: usize) -> Result<(), Box<dyn ExitError>> {
 }
 
 pub fn sstore(evm: &mut EVM) -> Result<(), Box<dyn ExitError>> {
+    evm.ensure_not_static()?;
     let key = evm.stack.pop()?;
     let value = evm.stack.pop()?;
     let address = evm.call_stack.last().unwrap().address.unwrap();
-
-    match evm.world_state.insert_storage_value(address, u256_to_h256(key), u256_to_h256(value)) {
+    // EIP-2200: 剩余gas不超过2300时不允许执行sstore
+    if evm.spec_id.is_enabled_in(SpecId::Istanbul) && evm.gas.remaining() <= CALL_STIPEND {
+        return Err(Box::new(OpcodeExecutionError::OutOfGas));
+    }
+    let key = u256_to_h256(key);
+    let new = u256_to_h256(value);
+    let current = evm.world_state.get_storage_value(address, key).unwrap_or_default();
+    // 交易中第一次写入该存储槽时，当前值即为原始值
+    let original = *evm.original_storage.entry((address, key)).or_insert(current);
+    let is_cold = evm.access_storage_key(address, key);
+    evm.gas.record_cost(sstore_cost(original, current, new, is_cold, evm.spec_id))?;
+    evm.gas.record_refund(sstore_refund(original, current, new, evm.spec_id));
+
+    match evm.world_state.insert_storage_value(address, key, new) {
         Ok(_) => {
             evm.pc += 1;
             Ok(())
@@ -68,7 +83,11 @@ pub fn sstore(evm: &mut EVM) -> Result<(), Box<dyn ExitError>> {
 
 pub fn sload(evm: &mut EVM) -> Result<(), Box<dyn ExitError>> {
     let key = evm.stack.pop()?;
-    let value = evm.world_state.get_storage_value(evm.call_stack.last().unwrap().address.unwrap(), u256_to_h256(key)).unwrap_or_else(|_| { H256::zero() });
+    let address = evm.call_stack.last().unwrap().address.unwrap();
+    if evm.access_storage_key(address, u256_to_h256(key)) && evm.spec_id.is_enabled_in(SpecId::Berlin) {
+        evm.gas.record_cost(COLD_SLOAD - WARM_STORAGE_READ)?;
+    }
+    let value = evm.world_state.get_storage_value(address, u256_to_h256(key)).unwrap_or_else(|_| { H256::zero() });
 
     match evm.stack.push(h256_to_u256(value)){
         Ok(_) => {
@@ -82,6 +101,7 @@ pub fn sload(evm: &mut EVM) -> Result<(), Box<dyn ExitError>> {
 pub fn msotre(evm: &mut EVM) -> Result<(), Box<dyn ExitError>> {
     let offset = evm.stack.pop()?;
     let value = evm.stack.pop()?;
+    evm.record_memory_cost(offset, U256::from(32))?;
     match evm.memory.mstore(offset, &*u256_to_vec(value)) {
         Ok(_) => {
             evm.pc += 1;
@@ -94,6 +114,7 @@ pub fn msotre(evm: &mut EVM) -> Result<(), Box<dyn ExitError>> {
 pub fn msotre8(evm: &mut EVM) -> Result<(), Box<dyn ExitError>> {
     let offset = evm.stack.pop()?;
     let value = evm.stack.pop()?;
+    evm.record_memory_cost(offset, U256::one())?;
     match evm.memory.mstore8(offset, &*u256_to_vec(value)) {
         Ok(_) => {
             evm.pc += 1;
@@ -105,6 +126,7 @@ pub fn msotre8(evm: &mut EVM) -> Result<(), Box<dyn ExitError>> {
 
 pub fn mload(evm: &mut EVM) -> Result<(), Box<dyn ExitError>> {
     let offset = evm.stack.pop()?;
+    evm.record_memory_cost(offset, U256::from(32))?;
     let value = match evm.memory.mload(offset) {
         Ok(value) => value,
         Err(e) => return Err(e)
@@ -118,10 +140,42 @@ pub fn mload(evm: &mut EVM) -> Result<(), Box<dyn ExitError>> {
     }
 }
 
+/// EIP-1153: 读取当前合约的瞬态存储，未写入过的存储槽为0
+pub fn tload(evm: &mut EVM) -> Result<(), Box<dyn ExitError>> {
+    let key = evm.stack.pop()?;
+    let address = evm.call_stack.last().unwrap().address.unwrap();
+    let value = evm.transient_storage
+        .get(&address)
+        .and_then(|storage| storage.get(&u256_to_h256(key)))
+        .copied()
+        .unwrap_or_default();
+
+    match evm.stack.push(h256_to_u256(value)) {
+        Ok(_) => {
+            evm.pc += 1;
+            Ok(())
+        }
+        Err(e) => Err(e)
+    }
+}
+
+/// EIP-1153: 写入当前合约的瞬态存储，瞬态存储在交易结束时清空
+pub fn tstore(evm: &mut EVM) -> Result<(), Box<dyn ExitError>> {
+    evm.ensure_not_static()?;
+    let key = evm.stack.pop()?;
+    let value = evm.stack.pop()?;
+    let address = evm.call_stack.last().unwrap().address.unwrap();
+    evm.set_transient_storage(address, u256_to_h256(key), u256_to_h256(value));
+    evm.pc += 1;
+    Ok(())
+}
+
 pub fn mcopy(evm: &mut EVM) -> Result<(), Box<dyn ExitError>> {
     let destOffset = evm.stack.pop()?;
     let offset = evm.stack.pop()?;
     let size = evm.stack.pop()?;
+    evm.gas.record_cost(copy_cost(size)?)?;
+    evm.record_memory_cost(offset.max(destOffset), size)?;
 
     let data_cpoy = match evm.memory.read(offset, size){
         Ok(data) => {
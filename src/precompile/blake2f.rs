@@ -0,0 +1,134 @@
+use crate::error::exit::*;
+use crate::precompile::{check_gas, PrecompileOutput, PrecompileResult};
+
+/// EIP-152: 输入为 rounds(4) | h(64) | m(128) | t(16) | f(1)
+const INPUT_LEN: usize = 213;
+/// 每一轮的费用
+const GAS_PER_ROUND: u64 = 1;
+
+const IV: [u64; 8] = [
+    0x6a09e667f3bcc908,
+    0xbb67ae8584caa73b,
+    0x3c6ef372fe94f82b,
+    0xa54ff53a5f1d36f1,
+    0x510e527fade682d1,
+    0x9b05688c2b3e6c1f,
+    0x1f83d9abfb41bd6b,
+    0x5be0cd19137e2179,
+];
+
+const SIGMA: [[usize; 16]; 10] = [
+    [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+    [14, 10, 4, 8, 9, 15, 13, 6, 1, 12, 0, 2, 11, 7, 5, 3],
+    [11, 8, 12, 0, 5, 2, 15, 13, 10, 14, 3, 6, 7, 1, 9, 4],
+    [7, 9, 3, 1, 13, 12, 11, 14, 2, 6, 5, 10, 4, 0, 15, 8],
+    [9, 0, 5, 7, 2, 4, 10, 15, 14, 1, 11, 12, 6, 8, 3, 13],
+    [2, 12, 6, 10, 0, 11, 8, 3, 4, 13, 7, 5, 15, 14, 1, 9],
+    [12, 5, 1, 15, 14, 13, 4, 10, 0, 7, 6, 3, 9, 2, 8, 11],
+    [13, 11, 7, 14, 12, 1, 3, 9, 5, 0, 15, 4, 8, 6, 2, 10],
+    [6, 15, 14, 9, 11, 3, 0, 8, 12, 2, 13, 7, 1, 4, 10, 5],
+    [10, 2, 8, 4, 7, 6, 1, 5, 15, 11, 9, 14, 3, 12, 13, 0],
+];
+
+/// 0x09: blake2b的压缩函数F
+pub fn blake2f(input: &[u8], gas_limit: u64) -> PrecompileResult {
+    if input.len() != INPUT_LEN {
+        return Err(PrecompileError::InvalidInputLength);
+    }
+    let rounds = u32::from_be_bytes(input[0..4].try_into().unwrap());
+    let gas_used = rounds as u64 * GAS_PER_ROUND;
+    check_gas(gas_used, gas_limit)?;
+
+    let f = match input[212] {
+        0 => false,
+        1 => true,
+        _ => return Err(PrecompileError::Blake2WrongFinalIndicatorFlag),
+    };
+    let read_u64 = |offset: usize| u64::from_le_bytes(input[offset..offset + 8].try_into().unwrap());
+    let mut h = [0u64; 8];
+    for (i, word) in h.iter_mut().enumerate() {
+        *word = read_u64(4 + i * 8);
+    }
+    let mut m = [0u64; 16];
+    for (i, word) in m.iter_mut().enumerate() {
+        *word = read_u64(68 + i * 8);
+    }
+    let t = [read_u64(196), read_u64(204)];
+
+    compress(rounds as usize, &mut h, m, t, f);
+    let output = h.iter().flat_map(|word| word.to_le_bytes()).collect();
+    Ok(PrecompileOutput::new(gas_used, output))
+}
+
+/// blake2b的混合函数G
+#[allow(clippy::many_single_char_names)]
+fn g(v: &mut [u64; 16], a: usize, b: usize, c: usize, d: usize, x: u64, y: u64) {
+    v[a] = v[a].wrapping_add(v[b]).wrapping_add(x);
+    v[d] = (v[d] ^ v[a]).rotate_right(32);
+    v[c] = v[c].wrapping_add(v[d]);
+    v[b] = (v[b] ^ v[c]).rotate_right(24);
+    v[a] = v[a].wrapping_add(v[b]).wrapping_add(y);
+    v[d] = (v[d] ^ v[a]).rotate_right(16);
+    v[c] = v[c].wrapping_add(v[d]);
+    v[b] = (v[b] ^ v[c]).rotate_right(63);
+}
+
+fn compress(rounds: usize, h: &mut [u64; 8], m: [u64; 16], t: [u64; 2], f: bool) {
+    let mut v = [0u64; 16];
+    v[..8].copy_from_slice(h);
+    v[8..].copy_from_slice(&IV);
+    v[12] ^= t[0];
+    v[13] ^= t[1];
+    if f {
+        v[14] = !v[14];
+    }
+    for i in 0..rounds {
+        let s = &SIGMA[i % 10];
+        g(&mut v, 0, 4, 8, 12, m[s[0]], m[s[1]]);
+        g(&mut v, 1, 5, 9, 13, m[s[2]], m[s[3]]);
+        g(&mut v, 2, 6, 10, 14, m[s[4]], m[s[5]]);
+        g(&mut v, 3, 7, 11, 15, m[s[6]], m[s[7]]);
+        g(&mut v, 0, 5, 10, 15, m[s[8]], m[s[9]]);
+        g(&mut v, 1, 6, 11, 12, m[s[10]], m[s[11]]);
+        g(&mut v, 2, 7, 8, 13, m[s[12]], m[s[13]]);
+        g(&mut v, 3, 4, 9, 14, m[s[14]], m[s[15]]);
+    }
+    for i in 0..8 {
+        h[i] ^= v[i] ^ v[i + 8];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::utils::hex;
+
+    /// EIP-152中的测试用例：对"abc"计算blake2b-512
+    fn abc_input(rounds: u32, f: u8) -> Vec<u8> {
+        let mut input = rounds.to_be_bytes().to_vec();
+        let mut h = IV;
+        // 参数块：输出长度64字节，无key
+        h[0] ^= 0x01010040;
+        input.extend(h.iter().flat_map(|word| word.to_le_bytes()));
+        let mut m = [0u8; 128];
+        m[..3].copy_from_slice(b"abc");
+        input.extend_from_slice(&m);
+        input.extend_from_slice(&3u64.to_le_bytes());
+        input.extend_from_slice(&0u64.to_le_bytes());
+        input.push(f);
+        input
+    }
+
+    #[test]
+    fn test_blake2f() {
+        let result = blake2f(&abc_input(12, 1), 12).unwrap();
+        assert_eq!(result.gas_used, 12);
+        assert_eq!(
+            hex::encode(result.output),
+            "ba80a53f981c4d0d6a2797b69f12f6e94c212f14685ac4b74b12bb6fdbffa2d17d87c5392aab792dc252d5de4533cc9518d38aa8dbf1925ab92386edd4009923"
+        );
+        assert_eq!(blake2f(&abc_input(12, 2), 12), Err(PrecompileError::Blake2WrongFinalIndicatorFlag));
+        assert_eq!(blake2f(&abc_input(12, 1), 11), Err(PrecompileError::OutOfGas));
+        assert_eq!(blake2f(&[0u8; 212], 100), Err(PrecompileError::InvalidInputLength));
+    }
+}
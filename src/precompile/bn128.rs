@@ -0,0 +1,163 @@
+use substrate_bn::{AffineG1, AffineG2, Fq, Fq2, Fr, Group, Gt, G1, G2};
+use crate::error::exit::*;
+use crate::precompile::{check_gas, right_pad, PrecompileOutput, PrecompileResult};
+
+/// EIP-196/EIP-1108: bn128加法、乘法的费用
+const ADD_GAS_BYZANTIUM: u64 = 500;
+const ADD_GAS_ISTANBUL: u64 = 150;
+const MUL_GAS_BYZANTIUM: u64 = 40000;
+const MUL_GAS_ISTANBUL: u64 = 6000;
+/// EIP-197/EIP-1108: 配对检查的费用为 base + per_pair * k
+const PAIRING_BASE_BYZANTIUM: u64 = 100000;
+const PAIRING_PER_PAIR_BYZANTIUM: u64 = 80000;
+const PAIRING_BASE_ISTANBUL: u64 = 45000;
+const PAIRING_PER_PAIR_ISTANBUL: u64 = 34000;
+/// 每组配对输入包含一个G1点(64字节)以及一个G2点(128字节)
+const PAIR_LEN: usize = 192;
+
+/// 0x06: bn128点加法
+pub fn add_byzantium(input: &[u8], gas_limit: u64) -> PrecompileResult {
+    run_add(input, ADD_GAS_BYZANTIUM, gas_limit)
+}
+
+pub fn add_istanbul(input: &[u8], gas_limit: u64) -> PrecompileResult {
+    run_add(input, ADD_GAS_ISTANBUL, gas_limit)
+}
+
+/// 0x07: bn128标量乘法
+pub fn mul_byzantium(input: &[u8], gas_limit: u64) -> PrecompileResult {
+    run_mul(input, MUL_GAS_BYZANTIUM, gas_limit)
+}
+
+pub fn mul_istanbul(input: &[u8], gas_limit: u64) -> PrecompileResult {
+    run_mul(input, MUL_GAS_ISTANBUL, gas_limit)
+}
+
+/// 0x08: bn128配对检查
+pub fn pairing_byzantium(input: &[u8], gas_limit: u64) -> PrecompileResult {
+    run_pairing(input, PAIRING_BASE_BYZANTIUM, PAIRING_PER_PAIR_BYZANTIUM, gas_limit)
+}
+
+pub fn pairing_istanbul(input: &[u8], gas_limit: u64) -> PrecompileResult {
+    run_pairing(input, PAIRING_BASE_ISTANBUL, PAIRING_PER_PAIR_ISTANBUL, gas_limit)
+}
+
+fn read_fq(input: &[u8]) -> Result<Fq, PrecompileError> {
+    Fq::from_slice(&input[..32]).map_err(|_| PrecompileError::Bn128FieldPointNotAMember)
+}
+
+/// 读取64字节编码的G1点，(0, 0)表示无穷远点
+fn read_g1(input: &[u8]) -> Result<G1, PrecompileError> {
+    let x = read_fq(&input[0..32])?;
+    let y = read_fq(&input[32..64])?;
+    if x.is_zero() && y.is_zero() {
+        return Ok(G1::zero());
+    }
+    AffineG1::new(x, y)
+        .map(Into::into)
+        .map_err(|_| PrecompileError::Bn128AffinePointCreationFailed)
+}
+
+/// 读取128字节编码的G2点，Fq2元素按照(虚部, 实部)的顺序编码
+fn read_g2(input: &[u8]) -> Result<G2, PrecompileError> {
+    let x = Fq2::new(read_fq(&input[32..64])?, read_fq(&input[0..32])?);
+    let y = Fq2::new(read_fq(&input[96..128])?, read_fq(&input[64..96])?);
+    if x.is_zero() && y.is_zero() {
+        return Ok(G2::zero());
+    }
+    AffineG2::new(x, y)
+        .map(Into::into)
+        .map_err(|_| PrecompileError::Bn128AffinePointCreationFailed)
+}
+
+/// 将G1点编码为64字节，无穷远点编码为全0
+fn encode_g1(point: G1) -> Vec<u8> {
+    let mut output = vec![0u8; 64];
+    if let Some(point) = AffineG1::from_jacobian(point) {
+        point.x().to_big_endian(&mut output[..32]).unwrap();
+        point.y().to_big_endian(&mut output[32..]).unwrap();
+    }
+    output
+}
+
+fn run_add(input: &[u8], gas_used: u64, gas_limit: u64) -> PrecompileResult {
+    check_gas(gas_used, gas_limit)?;
+    let input = right_pad(input, 128);
+    let p1 = read_g1(&input[0..64])?;
+    let p2 = read_g1(&input[64..128])?;
+    Ok(PrecompileOutput::new(gas_used, encode_g1(p1 + p2)))
+}
+
+fn run_mul(input: &[u8], gas_used: u64, gas_limit: u64) -> PrecompileResult {
+    check_gas(gas_used, gas_limit)?;
+    let input = right_pad(input, 96);
+    let point = read_g1(&input[0..64])?;
+    // 标量允许超出Fr的模数，超出部分取模
+    let scalar = Fr::from_slice(&input[64..96]).map_err(|_| PrecompileError::Bn128FieldPointNotAMember)?;
+    Ok(PrecompileOutput::new(gas_used, encode_g1(point * scalar)))
+}
+
+fn run_pairing(input: &[u8], base: u64, per_pair: u64, gas_limit: u64) -> PrecompileResult {
+    let pairs_len = input.len() / PAIR_LEN;
+    let gas_used = base.saturating_add(per_pair.saturating_mul(pairs_len as u64));
+    check_gas(gas_used, gas_limit)?;
+    if input.len() % PAIR_LEN != 0 {
+        return Err(PrecompileError::InvalidInputLength);
+    }
+    let mut pairs = Vec::with_capacity(pairs_len);
+    for chunk in input.chunks(PAIR_LEN) {
+        pairs.push((read_g1(&chunk[..64])?, read_g2(&chunk[64..])?));
+    }
+    // 空输入视为配对成功
+    let success = substrate_bn::pairing_batch(&pairs) == Gt::one();
+    let mut output = vec![0u8; 32];
+    output[31] = success as u8;
+    Ok(PrecompileOutput::new(gas_used, output))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::utils::hex;
+
+    /// 生成元(1, 2)的2倍点
+    const DOUBLE_GENERATOR: &str = "030644e72e131a029b85045b68181585d97816a916871ca8d3c208c16d87cfd315ed738c0e0a7c92e7845f96b2ae9c0a68a6a449e3538fc7ff3ebf7a5a18a2c4";
+
+    fn generator() -> Vec<u8> {
+        let mut point = vec![0u8; 64];
+        point[31] = 1;
+        point[63] = 2;
+        point
+    }
+
+    #[test]
+    fn test_add_and_mul() {
+        let input = [generator(), generator()].concat();
+        let result = add_istanbul(&input, 150).unwrap();
+        assert_eq!(hex::encode(result.output), DOUBLE_GENERATOR);
+        assert_eq!(add_byzantium(&input, 150), Err(PrecompileError::OutOfGas));
+
+        let mut scalar = vec![0u8; 32];
+        scalar[31] = 2;
+        let result = mul_istanbul(&[generator(), scalar].concat(), 6000).unwrap();
+        assert_eq!(hex::encode(result.output), DOUBLE_GENERATOR);
+
+        // 空输入即两个无穷远点相加
+        assert_eq!(add_istanbul(&[], 150).unwrap().output, vec![0u8; 64]);
+        // 不在曲线上的点
+        let mut invalid = generator();
+        invalid[63] = 3;
+        assert_eq!(add_istanbul(&invalid, 150), Err(PrecompileError::Bn128AffinePointCreationFailed));
+    }
+
+    #[test]
+    fn test_pairing() {
+        let result = pairing_istanbul(&[], 45000).unwrap();
+        assert_eq!(result.output[31], 1);
+        assert_eq!(pairing_istanbul(&[0u8; 100], 1_000_000), Err(PrecompileError::InvalidInputLength));
+        // G1无穷远点与任意G2点的配对结果为1
+        let result = pairing_istanbul(&[0u8; 192], 79000).unwrap();
+        assert_eq!(result.gas_used, 79000);
+        assert_eq!(result.output[31], 1);
+    }
+}
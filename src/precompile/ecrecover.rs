@@ -0,0 +1,67 @@
+use ethers::utils::keccak256;
+use k256::ecdsa::{RecoveryId, Signature, VerifyingKey};
+use primitive_types::{H160, H256, U256};
+use crate::precompile::{check_gas, right_pad, PrecompileOutput, PrecompileResult};
+
+const ECRECOVER_GAS: u64 = 3000;
+
+/// 0x01: 根据消息哈希以及签名(v, r, s)恢复签名者地址，签名无效时返回空数据
+pub fn ecrecover(input: &[u8], gas_limit: u64) -> PrecompileResult {
+    check_gas(ECRECOVER_GAS, gas_limit)?;
+    let input = right_pad(input, 128);
+    // v只能为27或28，且高位必须为0
+    if input[32..63].iter().any(|byte| *byte != 0) || !matches!(input[63], 27 | 28) {
+        return Ok(PrecompileOutput::new(ECRECOVER_GAS, Vec::new()));
+    }
+    let output = recover_address(&input[..32], input[63] - 27, &input[64..128]).unwrap_or_default();
+    Ok(PrecompileOutput::new(ECRECOVER_GAS, output))
+}
+
+/// 恢复签名者地址，结果左侧补0到32字节
+fn recover_address(hash: &[u8], recovery_id: u8, signature: &[u8]) -> Option<Vec<u8>> {
+    let mut signature = Signature::from_slice(signature).ok()?;
+    let mut recovery_id = RecoveryId::from_byte(recovery_id)?;
+    // 预编译合约接受high-s签名，k256只接受low-s签名，因此先对s进行归一化
+    if let Some(normalized) = signature.normalize_s() {
+        signature = normalized;
+        recovery_id = RecoveryId::from_byte(recovery_id.to_byte() ^ 1)?;
+    }
+    let verifying_key = VerifyingKey::recover_from_prehash(hash, &signature, recovery_id).ok()?;
+    let public_key = verifying_key.to_encoded_point(false);
+    let hash = keccak256(&public_key.as_bytes()[1..]);
+    let mut output = vec![0u8; 32];
+    output[12..].copy_from_slice(&hash[12..]);
+    Some(output)
+}
+
+/// 恢复交易以及EIP-7702授权的签名者，EIP-2规定这些签名的s不能大于secp256k1n/2
+pub fn recover_signer(hash: H256, y_parity: u8, r: U256, s: U256) -> Option<H160> {
+    let mut signature = [0u8; 64];
+    r.to_big_endian(&mut signature[..32]);
+    s.to_big_endian(&mut signature[32..]);
+    if Signature::from_slice(&signature).ok()?.normalize_s().is_some() {
+        return None;
+    }
+    let output = recover_address(hash.as_bytes(), y_parity, &signature)?;
+    Some(H160::from_slice(&output[12..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::utils::hex;
+
+    #[test]
+    fn test_ecrecover() {
+        let input = hex::decode("38d18acb67d25c8bb9942764b62f18e17054f66a817bd4295423adf9ed98873e000000000000000000000000000000000000000000000000000000000000001b38d18acb67d25c8bb9942764b62f18e17054f66a817bd4295423adf9ed98873e789d1dd423d25f0772d2748d60f7e4b81bb14d086eba8e8e8efb6dcff8a4ae02").unwrap();
+        let result = ecrecover(&input, 3000).unwrap();
+        assert_eq!(result.gas_used, 3000);
+        assert_eq!(hex::encode(result.output), "000000000000000000000000ceaccac640adf55b2028469bd36ba501f28b699d");
+
+        // v不合法时返回空数据
+        let mut invalid = input.clone();
+        invalid[63] = 29;
+        assert!(ecrecover(&invalid, 3000).unwrap().output.is_empty());
+        assert!(ecrecover(&input, 2999).is_err());
+    }
+}
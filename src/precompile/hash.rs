@@ -0,0 +1,40 @@
+use sha2::Digest;
+use crate::precompile::{check_gas, linear_cost, PrecompileOutput, PrecompileResult};
+
+/// 0x02: sha256哈希
+pub fn sha256(input: &[u8], gas_limit: u64) -> PrecompileResult {
+    let gas_used = linear_cost(input.len(), 60, 12);
+    check_gas(gas_used, gas_limit)?;
+    let output = sha2::Sha256::digest(input).to_vec();
+    Ok(PrecompileOutput::new(gas_used, output))
+}
+
+/// 0x03: ripemd160哈希，结果左侧补0到32字节
+pub fn ripemd160(input: &[u8], gas_limit: u64) -> PrecompileResult {
+    let gas_used = linear_cost(input.len(), 600, 120);
+    check_gas(gas_used, gas_limit)?;
+    let mut output = vec![0u8; 32];
+    output[12..].copy_from_slice(&ripemd::Ripemd160::digest(input));
+    Ok(PrecompileOutput::new(gas_used, output))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::utils::hex;
+    use crate::precompile::PrecompileError;
+
+    #[test]
+    fn test_sha256() {
+        let result = sha256(&[], 100).unwrap();
+        assert_eq!(result.gas_used, 60);
+        assert_eq!(hex::encode(result.output), "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+        assert_eq!(sha256(&[0u8; 33], 83), Err(PrecompileError::OutOfGas));
+    }
+
+    #[test]
+    fn test_ripemd160() {
+        let result = ripemd160(&[], 600).unwrap();
+        assert_eq!(hex::encode(result.output), "0000000000000000000000009c1185a5c5e9fc54612808977ee8f548b2258d31");
+    }
+}
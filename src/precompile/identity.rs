@@ -0,0 +1,8 @@
+use crate::precompile::{check_gas, linear_cost, PrecompileOutput, PrecompileResult};
+
+/// 0x04: 原样返回输入数据
+pub fn identity(input: &[u8], gas_limit: u64) -> PrecompileResult {
+    let gas_used = linear_cost(input.len(), 15, 3);
+    check_gas(gas_used, gas_limit)?;
+    Ok(PrecompileOutput::new(gas_used, input.to_vec()))
+}
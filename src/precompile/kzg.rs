@@ -0,0 +1,88 @@
+use c_kzg::{Bytes32, Bytes48, KzgProof};
+use sha2::Digest;
+use crate::error::exit::*;
+use crate::precompile::{check_gas, PrecompileOutput, PrecompileResult};
+
+/// EIP-4844: point evaluation的费用
+const POINT_EVALUATION_GAS: u64 = 50000;
+/// 输入为 versioned_hash(32) | z(32) | y(32) | commitment(48) | proof(48)
+const INPUT_LEN: usize = 192;
+/// commitment对应的versioned hash的版本号
+const VERSIONED_HASH_VERSION_KZG: u8 = 0x01;
+/// 返回值：每个blob包含的域元素数量(4096)以及BLS12-381的模数
+const RETURN_VALUE: [u8; 64] = {
+    let mut value = [0u8; 64];
+    value[30] = 0x10;
+    let modulus: [u8; 32] = [
+        0x73, 0xed, 0xa7, 0x53, 0x29, 0x9d, 0x7d, 0x48, 0x33, 0x39, 0xd8, 0x08, 0x09, 0xa1, 0xd8, 0x05,
+        0x53, 0xbd, 0xa4, 0x02, 0xff, 0xfe, 0x5b, 0xfe, 0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0x00, 0x01,
+    ];
+    let mut i = 0;
+    while i < 32 {
+        value[32 + i] = modulus[i];
+        i += 1;
+    }
+    value
+};
+
+/// 0x0a: 验证blob对应的多项式在z处的取值为y
+pub fn point_evaluation(input: &[u8], gas_limit: u64) -> PrecompileResult {
+    check_gas(POINT_EVALUATION_GAS, gas_limit)?;
+    if input.len() != INPUT_LEN {
+        return Err(PrecompileError::InvalidInputLength);
+    }
+    let commitment = &input[96..144];
+    if kzg_to_versioned_hash(commitment) != input[0..32] {
+        return Err(PrecompileError::BlobMismatchedVersion);
+    }
+    let to_bytes32 = |bytes: &[u8]| Bytes32::from_bytes(bytes).map_err(|e| PrecompileError::Other(format!("{:?}", e)));
+    let to_bytes48 = |bytes: &[u8]| Bytes48::from_bytes(bytes).map_err(|e| PrecompileError::Other(format!("{:?}", e)));
+    let verified = KzgProof::verify_kzg_proof(
+        &to_bytes48(commitment)?,
+        &to_bytes32(&input[32..64])?,
+        &to_bytes32(&input[64..96])?,
+        &to_bytes48(&input[144..192])?,
+        c_kzg::ethereum_kzg_settings(),
+    )
+    .unwrap_or(false);
+    if !verified {
+        return Err(PrecompileError::BlobVerifyKzgProofFailed);
+    }
+    Ok(PrecompileOutput::new(POINT_EVALUATION_GAS, RETURN_VALUE.to_vec()))
+}
+
+/// versioned hash为commitment的sha256哈希，并将第一个字节替换为版本号
+pub fn kzg_to_versioned_hash(commitment: &[u8]) -> [u8; 32] {
+    let mut hash: [u8; 32] = sha2::Sha256::digest(commitment).into();
+    hash[0] = VERSIONED_HASH_VERSION_KZG;
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_point_evaluation() {
+        // 零多项式：commitment与proof均为无穷远点，任意z处的取值均为0
+        let mut infinity = [0u8; 48];
+        infinity[0] = 0xc0;
+        let mut input = kzg_to_versioned_hash(&infinity).to_vec();
+        input.extend_from_slice(&[0u8; 32]);
+        input.extend_from_slice(&[0u8; 32]);
+        input.extend_from_slice(&infinity);
+        input.extend_from_slice(&infinity);
+
+        let result = point_evaluation(&input, 50000).unwrap();
+        assert_eq!(result.output, RETURN_VALUE.to_vec());
+
+        // y不为0时验证失败
+        let mut wrong_y = input.clone();
+        wrong_y[95] = 1;
+        assert_eq!(point_evaluation(&wrong_y, 50000), Err(PrecompileError::BlobVerifyKzgProofFailed));
+        // versioned hash不匹配
+        let mut wrong_hash = input.clone();
+        wrong_hash[0] = 0x02;
+        assert_eq!(point_evaluation(&wrong_hash, 50000), Err(PrecompileError::BlobMismatchedVersion));
+    }
+}
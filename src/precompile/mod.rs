@@ -0,0 +1,189 @@
+pub mod hash;
+pub mod identity;
+pub mod ecrecover;
+pub mod modexp;
+pub mod bn128;
+pub mod blake2f;
+pub mod kzg;
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+use primitive_types::H160;
+use crate::error::exit::*;
+use crate::spec::SpecId;
+
+/// 预编译合约执行成功时的结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrecompileOutput {
+    /// 预编译合约消耗的gas
+    pub gas_used: u64,
+    pub output: Vec<u8>,
+}
+
+impl PrecompileOutput {
+    pub fn new(gas_used: u64, output: Vec<u8>) -> Self {
+        Self { gas_used, output }
+    }
+}
+
+pub type PrecompileResult = Result<PrecompileOutput, PrecompileError>;
+
+/// 标准预编译合约：输入为calldata以及可使用的gas上限
+pub type PrecompileFn = fn(&[u8], u64) -> PrecompileResult;
+
+/// 预编译合约，自定义预编译合约（如其他链特有的预编译合约、模拟预言机）实现该特征后即可注册到EVM中
+///
+/// 返回的gas_used不能超过gas_limit，返回错误时转发给预编译合约的gas全部被消耗
+pub trait Precompile: Send + Sync {
+    fn run(&self, input: &[u8], gas_limit: u64) -> PrecompileResult;
+}
+
+impl<F> Precompile for F
+where
+    F: Fn(&[u8], u64) -> PrecompileResult + Send + Sync,
+{
+    fn run(&self, input: &[u8], gas_limit: u64) -> PrecompileResult {
+        self(input, gas_limit)
+    }
+}
+
+/// 预编译合约注册表，根据硬分叉确定可用的预编译合约及其gas费用，call类操作码在查找目标地址的code之前先查询该表
+#[derive(Clone, Default)]
+pub struct Precompiles {
+    inner: BTreeMap<H160, Arc<dyn Precompile>>,
+}
+
+impl fmt::Debug for Precompiles {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.inner.keys()).finish()
+    }
+}
+
+impl Precompiles {
+    pub fn new(spec: SpecId) -> Self {
+        let mut precompiles = Self::default();
+        precompiles.insert(1, ecrecover::ecrecover);
+        precompiles.insert(2, hash::sha256);
+        precompiles.insert(3, hash::ripemd160);
+        precompiles.insert(4, identity::identity);
+        if spec.is_enabled_in(SpecId::Byzantium) {
+            if spec.is_enabled_in(SpecId::Berlin) {
+                precompiles.insert(5, modexp::modexp_berlin);
+            } else {
+                precompiles.insert(5, modexp::modexp_byzantium);
+            }
+            if spec.is_enabled_in(SpecId::Istanbul) {
+                precompiles.insert(6, bn128::add_istanbul);
+                precompiles.insert(7, bn128::mul_istanbul);
+                precompiles.insert(8, bn128::pairing_istanbul);
+            } else {
+                precompiles.insert(6, bn128::add_byzantium);
+                precompiles.insert(7, bn128::mul_byzantium);
+                precompiles.insert(8, bn128::pairing_byzantium);
+            }
+        }
+        if spec.is_enabled_in(SpecId::Istanbul) {
+            precompiles.insert(9, blake2f::blake2f);
+        }
+        if spec.is_enabled_in(SpecId::Cancun) {
+            precompiles.insert(10, kzg::point_evaluation);
+        }
+        precompiles
+    }
+
+    fn insert(&mut self, index: u64, precompile: PrecompileFn) {
+        self.inner.insert(H160::from_low_u64_be(index), Arc::new(precompile));
+    }
+
+    /// 在指定地址注册预编译合约，已经存在的预编译合约会被覆盖
+    pub fn register<P: Precompile + 'static>(&mut self, address: H160, precompile: P) {
+        self.inner.insert(address, Arc::new(precompile));
+    }
+
+    /// 移除指定地址上的预编译合约
+    pub fn remove(&mut self, address: &H160) -> bool {
+        self.inner.remove(address).is_some()
+    }
+
+    /// 合并另一个注册表，地址相同时以other中的预编译合约为准
+    pub fn extend(&mut self, other: &Precompiles) {
+        for (address, precompile) in &other.inner {
+            self.inner.insert(*address, precompile.clone());
+        }
+    }
+
+    pub fn get(&self, address: &H160) -> Option<Arc<dyn Precompile>> {
+        self.inner.get(address).cloned()
+    }
+
+    pub fn contains(&self, address: &H160) -> bool {
+        self.inner.contains_key(address)
+    }
+
+    /// 所有预编译合约的地址，EIP-2929中这些地址在交易开始时即为warm
+    pub fn addresses(&self) -> impl Iterator<Item = &H160> {
+        self.inner.keys()
+    }
+}
+
+/// 按字收取费用的预编译合约：base + per_word * words
+pub fn linear_cost(len: usize, base: u64, per_word: u64) -> u64 {
+    (len as u64).div_ceil(32) * per_word + base
+}
+
+/// 检查gas是否足够支付预编译合约的费用
+pub fn check_gas(gas_used: u64, gas_limit: u64) -> Result<(), PrecompileError> {
+    if gas_used > gas_limit {
+        return Err(PrecompileError::OutOfGas);
+    }
+    Ok(())
+}
+
+/// 将输入在右侧补0到指定长度，超出的部分保留
+pub fn right_pad(input: &[u8], len: usize) -> Vec<u8> {
+    let mut padded = input.to_vec();
+    if padded.len() < len {
+        padded.resize(len, 0);
+    }
+    padded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_precompiles_by_spec() {
+        let count = |spec: SpecId| Precompiles::new(spec).addresses().count();
+        assert_eq!(count(SpecId::Homestead), 4);
+        assert_eq!(count(SpecId::Byzantium), 8);
+        assert_eq!(count(SpecId::Istanbul), 9);
+        assert_eq!(count(SpecId::Cancun), 10);
+        assert!(!Precompiles::new(SpecId::Cancun).contains(&H160::from_low_u64_be(11)));
+    }
+
+    #[test]
+    fn test_register_precompile() {
+        let oracle = H160::from_low_u64_be(0x1000);
+        let mut custom = Precompiles::default();
+        custom.register(oracle, |_: &[u8], _: u64| Ok(PrecompileOutput::new(10, vec![1])));
+        // 覆盖标准的identity预编译合约
+        custom.register(H160::from_low_u64_be(4), |_: &[u8], _: u64| Err(PrecompileError::Other("disabled".into())));
+
+        let mut precompiles = Precompiles::new(SpecId::Cancun);
+        precompiles.extend(&custom);
+        assert_eq!(precompiles.addresses().count(), 11);
+        assert_eq!(precompiles.get(&oracle).unwrap().run(&[], 100).unwrap().output, vec![1]);
+        assert!(precompiles.get(&H160::from_low_u64_be(4)).unwrap().run(&[], 100).is_err());
+        assert!(precompiles.remove(&oracle));
+        assert!(!precompiles.contains(&oracle));
+    }
+
+    #[test]
+    fn test_linear_cost() {
+        assert_eq!(linear_cost(0, 60, 12), 60);
+        assert_eq!(linear_cost(33, 60, 12), 84);
+        assert_eq!(right_pad(&[1, 2], 4), vec![1, 2, 0, 0]);
+    }
+}
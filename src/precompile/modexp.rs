@@ -0,0 +1,135 @@
+use num_bigint::BigUint;
+use primitive_types::U256;
+use crate::error::exit::*;
+use crate::precompile::{PrecompileOutput, PrecompileResult};
+
+/// 0x05: EIP-198定价的模幂运算
+pub fn modexp_byzantium(input: &[u8], gas_limit: u64) -> PrecompileResult {
+    run(input, gas_limit, byzantium_gas)
+}
+
+/// 0x05: EIP-2565定价的模幂运算
+pub fn modexp_berlin(input: &[u8], gas_limit: u64) -> PrecompileResult {
+    run(input, gas_limit, berlin_gas)
+}
+
+/// 读取input[offset..offset + len]，超出输入长度的部分视为0
+fn read_padded(input: &[u8], offset: usize, len: usize) -> Vec<u8> {
+    let mut data = vec![0u8; len];
+    if offset < input.len() {
+        let end = input.len().min(offset.saturating_add(len));
+        data[..end - offset].copy_from_slice(&input[offset..end]);
+    }
+    data
+}
+
+/// 长度超出u64时按u64::MAX计算，此时gas费用必然超出上限
+fn read_len(input: &[u8], offset: usize) -> u64 {
+    let len = U256::from_big_endian(&read_padded(input, offset, 32));
+    if len > U256::from(u64::MAX) { u64::MAX } else { len.as_u64() }
+}
+
+fn run(input: &[u8], gas_limit: u64, gas_fn: fn(u64, u64, u64, &BigUint) -> u128) -> PrecompileResult {
+    let base_len = read_len(input, 0);
+    let exp_len = read_len(input, 32);
+    let mod_len = read_len(input, 64);
+
+    // 指数的前32字节用于计算迭代次数
+    let exp_head = if base_len > u32::MAX as u64 {
+        BigUint::default()
+    } else {
+        let head_len = exp_len.min(32) as usize;
+        BigUint::from_bytes_be(&read_padded(input, 96 + base_len as usize, head_len))
+    };
+    let gas_used = gas_fn(base_len, exp_len, mod_len, &exp_head);
+    if gas_used > gas_limit as u128 {
+        return Err(PrecompileError::OutOfGas);
+    }
+    let gas_used = gas_used as u64;
+
+    if base_len == 0 && mod_len == 0 {
+        return Ok(PrecompileOutput::new(gas_used, Vec::new()));
+    }
+    let (base_len, exp_len, mod_len) = (base_len as usize, exp_len as usize, mod_len as usize);
+    let base = BigUint::from_bytes_be(&read_padded(input, 96, base_len));
+    let exponent = BigUint::from_bytes_be(&read_padded(input, 96 + base_len, exp_len));
+    let modulus = BigUint::from_bytes_be(&read_padded(input, 96 + base_len + exp_len, mod_len));
+
+    let result = if modulus == BigUint::default() {
+        Vec::new()
+    } else {
+        base.modpow(&exponent, &modulus).to_bytes_be()
+    };
+    // 结果左侧补0到mod_len字节
+    let mut output = vec![0u8; mod_len];
+    output[mod_len - result.len()..].copy_from_slice(&result);
+    Ok(PrecompileOutput::new(gas_used, output))
+}
+
+/// 根据指数长度以及指数的前32字节计算迭代次数
+fn iteration_count(exp_len: u64, exp_head: &BigUint) -> u128 {
+    let bits = exp_head.bits().saturating_sub(1) as u128;
+    if exp_len <= 32 {
+        bits
+    } else {
+        8 * (exp_len as u128 - 32) + bits
+    }
+}
+
+fn byzantium_gas(base_len: u64, exp_len: u64, mod_len: u64, exp_head: &BigUint) -> u128 {
+    let x = base_len.max(mod_len) as u128;
+    let complexity = if x <= 64 {
+        x * x
+    } else if x <= 1024 {
+        x * x / 4 + 96 * x - 3072
+    } else {
+        (x * x / 16 + 480 * x).saturating_sub(199680)
+    };
+    complexity.saturating_mul(iteration_count(exp_len, exp_head).max(1)) / 20
+}
+
+fn berlin_gas(base_len: u64, exp_len: u64, mod_len: u64, exp_head: &BigUint) -> u128 {
+    let words = (base_len.max(mod_len) as u128).div_ceil(8);
+    let complexity = words.saturating_mul(words);
+    (complexity.saturating_mul(iteration_count(exp_len, exp_head).max(1)) / 3).max(200)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 按照 base_len | exp_len | mod_len | base | exp | mod 的格式构造输入
+    fn encode(base: &[u8], exponent: &[u8], modulus: &[u8]) -> Vec<u8> {
+        let mut input = Vec::new();
+        for len in [base.len(), exponent.len(), modulus.len()] {
+            let mut word = [0u8; 32];
+            U256::from(len).to_big_endian(&mut word);
+            input.extend_from_slice(&word);
+        }
+        input.extend_from_slice(base);
+        input.extend_from_slice(exponent);
+        input.extend_from_slice(modulus);
+        input
+    }
+
+    #[test]
+    fn test_modexp() {
+        // 3 ** 5 % 7 = 5
+        let input = encode(&[3], &[5], &[7]);
+        let result = modexp_berlin(&input, 1000).unwrap();
+        assert_eq!(result.output, vec![5]);
+        assert_eq!(result.gas_used, 200);
+        let result = modexp_byzantium(&input, 1000).unwrap();
+        assert_eq!(result.output, vec![5]);
+        assert_eq!(result.gas_used, 0);
+
+        // 模数为0时结果为0
+        let result = modexp_berlin(&encode(&[3], &[5], &[0, 0]), 1000).unwrap();
+        assert_eq!(result.output, vec![0, 0]);
+
+        // 指数为2^256 - 1时迭代次数为255
+        let input = encode(&[3; 32], &[0xff; 32], &[7; 32]);
+        assert_eq!(modexp_berlin(&input, 100_000).unwrap().gas_used, 16 * 255 / 3);
+        assert_eq!(modexp_berlin(&input, 1000), Err(PrecompileError::OutOfGas));
+    }
+}
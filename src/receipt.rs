@@ -0,0 +1,220 @@
+use ethers::abi::ethereum_types::BloomInput;
+use ethers::types::{Bloom, TransactionReceipt};
+use ethers::utils::rlp::RlpStream;
+use primitive_types::H160;
+use crate::opcode::enviroment::Log;
+use crate::result::ExecutionResult;
+use crate::utils::{h256_to_u256, u256_to_h256};
+
+/// 交易收据，编码格式与eth_getTransactionReceipt返回的收据一致
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Receipt {
+    /// EIP-2718: 交易类型，legacy交易为0
+    pub tx_type: u8,
+    pub status: bool,
+    /// 当前交易消耗的gas
+    pub gas_used: u64,
+    /// 区块中截至当前交易(包含当前交易)累计消耗的gas
+    pub cumulative_gas_used: u64,
+    pub logs: Vec<Log>,
+    pub logs_bloom: Bloom,
+    /// 创建合约交易所创建的合约地址
+    pub contract_address: Option<H160>,
+}
+
+impl Receipt {
+    /// 根据交易执行结果生成收据，previous_cumulative_gas_used为区块中之前所有交易累计消耗的gas
+    pub fn new(tx_type: u8, result: &ExecutionResult, previous_cumulative_gas_used: u64, contract_address: Option<H160>) -> Self {
+        let logs = result.logs().to_vec();
+        Self {
+            tx_type,
+            status: result.is_success(),
+            gas_used: result.gas_used(),
+            cumulative_gas_used: previous_cumulative_gas_used + result.gas_used(),
+            logs_bloom: logs_bloom(&logs),
+            logs,
+            contract_address,
+        }
+    }
+
+    /// EIP-2718编码：legacy交易为 rlp([status, cumulative_gas_used, logs_bloom, logs])，其他类型交易在前面加上类型字节
+    pub fn encode(&self) -> Vec<u8> {
+        let mut stream = RlpStream::new_list(4);
+        stream.append(&self.status);
+        stream.append(&self.cumulative_gas_used);
+        stream.append(&self.logs_bloom);
+        stream.begin_list(self.logs.len());
+        for log in &self.logs {
+            stream.begin_list(3);
+            stream.append(&log.address);
+            stream.begin_list(log.topics.len());
+            for topic in &log.topics {
+                stream.append(&u256_to_h256(*topic));
+            }
+            stream.append(&log.data);
+        }
+        let mut encoded = Vec::new();
+        if self.tx_type != 0 {
+            encoded.push(self.tx_type);
+        }
+        encoded.extend_from_slice(&stream.out());
+        encoded
+    }
+}
+
+/// 节点返回的收据，用于与本地生成的收据进行比较
+impl From<&TransactionReceipt> for Receipt {
+    fn from(receipt: &TransactionReceipt) -> Self {
+        Self {
+            tx_type: receipt.transaction_type.map_or(0, |tx_type| tx_type.as_u64() as u8),
+            status: receipt.status.is_some_and(|status| !status.is_zero()),
+            gas_used: receipt.gas_used.unwrap_or_default().as_u64(),
+            cumulative_gas_used: receipt.cumulative_gas_used.as_u64(),
+            logs: receipt.logs.iter().map(|log| Log {
+                address: log.address,
+                topics: log.topics.iter().map(|topic| h256_to_u256(*topic)).collect(),
+                data: log.data.to_vec(),
+            }).collect(),
+            logs_bloom: receipt.logs_bloom,
+            contract_address: receipt.contract_address,
+        }
+    }
+}
+
+/// 2048位的日志布隆过滤器，包含每条日志的地址以及全部topic
+pub fn logs_bloom(logs: &[Log]) -> Bloom {
+    let mut bloom = Bloom::zero();
+    for log in logs {
+        bloom.accrue(BloomInput::Raw(log.address.as_bytes()));
+        for topic in &log.topics {
+            bloom.accrue(BloomInput::Raw(u256_to_h256(*topic).as_bytes()));
+        }
+    }
+    bloom
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::types::U64;
+    use primitive_types::U256;
+    use ethers::utils::{hex, keccak256, rlp};
+
+    /// ERC-20 Transfer(address,address,uint256)事件
+    fn transfer_log() -> Log {
+        let topic0 = U256::from(keccak256("Transfer(address,address,uint256)"));
+        Log {
+            address: H160::repeat_byte(0x11),
+            topics: vec![topic0, U256::from(0x22), U256::from(0x33)],
+            data: vec![0u8; 32],
+        }
+    }
+
+    #[test]
+    fn test_logs_bloom() {
+        let log = transfer_log();
+        let bloom = logs_bloom(&[log.clone()]);
+        assert!(bloom.contains_input(BloomInput::Raw(log.address.as_bytes())));
+        assert!(bloom.contains_input(BloomInput::Raw(u256_to_h256(log.topics[0]).as_bytes())));
+        assert!(!bloom.contains_input(BloomInput::Raw(H160::repeat_byte(0x44).as_bytes())));
+        // 每个元素在布隆过滤器中最多设置3位
+        let bits: u32 = bloom.as_bytes().iter().map(|byte| byte.count_ones()).sum();
+        assert!(bits <= 12);
+        assert_eq!(logs_bloom(&[]), Bloom::zero());
+    }
+
+    #[test]
+    fn test_receipt_encoding() {
+        let result = ExecutionResult::Success { output: Vec::new(), gas_used: 21000, logs: Vec::new() };
+        let receipt = Receipt::new(0, &result, 0, None);
+        let expected = format!("f9010801825208b90100{}c0", "00".repeat(256));
+        assert_eq!(hex::encode(receipt.encode()), expected);
+
+        // 与节点返回的收据编码一致
+        let result = ExecutionResult::Success { output: Vec::new(), gas_used: 30000, logs: vec![transfer_log()] };
+        let receipt = Receipt::new(2, &result, 21000, None);
+        assert_eq!(receipt.cumulative_gas_used, 51000);
+        let node_receipt = TransactionReceipt {
+            status: Some(U64::one()),
+            gas_used: Some(U256::from(30000)),
+            cumulative_gas_used: U256::from(51000),
+            logs_bloom: receipt.logs_bloom,
+            logs: vec![ethers::types::Log {
+                address: H160::repeat_byte(0x11),
+                topics: transfer_log().topics.into_iter().map(u256_to_h256).collect(),
+                data: vec![0u8; 32].into(),
+                ..Default::default()
+            }],
+            transaction_type: Some(U64::from(2)),
+            ..Default::default()
+        };
+        assert_eq!(Receipt::from(&node_receipt), receipt);
+        assert_eq!(receipt.encode(), [vec![2u8], rlp::encode(&node_receipt).to_vec()].concat());
+
+        // revert的交易状态为0且不包含日志
+        let result = ExecutionResult::Revert { output: Vec::new(), gas_used: 25000 };
+        let receipt = Receipt::new(0, &result, 51000, None);
+        assert!(!receipt.status);
+        assert_eq!(receipt.logs_bloom, Bloom::zero());
+        assert_eq!(receipt.encode()[3], 0x80);
+    }
+
+    /// 节点通过eth_getTransactionReceipt返回的一笔创建合约的EIP-1559交易的收据(区块0xe3c1d8)
+    const CREATE_RECEIPT: &str = r#"{
+        "transactionHash": "0x611b173b0e0dfda94da7bfb6cb77c9f1c03e2f2149ba060e6bddfaa219942369",
+        "blockHash": "0xa11871d61e0e703ae33b358a6a9653c43e4216f277d4a1c7377b76b4d5b4cbf1",
+        "blockNumber": "0xe3c1d8",
+        "contractAddress": "0x08f6db30039218894067023a3593baf27d3f4a2b",
+        "cumulativeGasUsed": "0x1246047",
+        "effectiveGasPrice": "0xa02ffee00",
+        "from": "0x0968995a48162a23af60d3ca25cddfa143cd8891",
+        "gasUsed": "0x1b9229",
+        "logs": [
+            {
+                "address": "0x08f6db30039218894067023a3593baf27d3f4a2b",
+                "topics": ["0x40c340f65e17194d14ddddb073d3c9f888e3cb52b5aae0c6c7706b4fbc905fac"],
+                "data": "0x0000000000000000000000000968995a48162a23af60d3ca25cddfa143cd88910000000000000000000000000000000000000000000000000000000000002616",
+                "blockNumber": "0xe3c1d8",
+                "transactionHash": "0x611b173b0e0dfda94da7bfb6cb77c9f1c03e2f2149ba060e6bddfaa219942369",
+                "transactionIndex": "0xdf",
+                "blockHash": "0xa11871d61e0e703ae33b358a6a9653c43e4216f277d4a1c7377b76b4d5b4cbf1",
+                "logIndex": "0x196",
+                "removed": false
+            },
+            {
+                "address": "0x08f6db30039218894067023a3593baf27d3f4a2b",
+                "topics": ["0x40c340f65e17194d14ddddb073d3c9f888e3cb52b5aae0c6c7706b4fbc905fac"],
+                "data": "0x00000000000000000000000059750ac0631f63bfdce0f0867618e468e11ee34700000000000000000000000000000000000000000000000000000000000000fa",
+                "blockNumber": "0xe3c1d8",
+                "transactionHash": "0x611b173b0e0dfda94da7bfb6cb77c9f1c03e2f2149ba060e6bddfaa219942369",
+                "transactionIndex": "0xdf",
+                "blockHash": "0xa11871d61e0e703ae33b358a6a9653c43e4216f277d4a1c7377b76b4d5b4cbf1",
+                "logIndex": "0x197",
+                "removed": false
+            }
+        ],
+        "logsBloom": "0x00000000000000800000000040000000000000000000000000000000000000000000008000000000000000000000000000000000000000000010000000000000000000000000000000000000000000000000000000000000800000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000080000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000",
+        "status": "0x1",
+        "to": null,
+        "transactionIndex": "0xdf",
+        "type": "0x2"
+    }"#;
+
+    #[test]
+    fn test_receipt_matches_node_receipt() {
+        let node_receipt: TransactionReceipt = ethers::utils::__serde_json::from_str(CREATE_RECEIPT).unwrap();
+        let logs = node_receipt.logs.iter().map(|log| Log {
+            address: log.address,
+            topics: log.topics.iter().map(|topic| h256_to_u256(*topic)).collect(),
+            data: log.data.to_vec(),
+        }).collect();
+        let gas_used = node_receipt.gas_used.unwrap().as_u64();
+        let result = ExecutionResult::Success { output: Vec::new(), gas_used, logs };
+        // 区块中之前的交易累计消耗的gas
+        let previous_cumulative_gas_used = node_receipt.cumulative_gas_used.as_u64() - gas_used;
+        let receipt = Receipt::new(2, &result, previous_cumulative_gas_used, node_receipt.contract_address);
+        // 本地计算的布隆过滤器以及收据编码与节点返回的一致
+        assert_eq!(receipt, Receipt::from(&node_receipt));
+        assert_eq!(receipt.encode(), [vec![2u8], rlp::encode(&node_receipt).to_vec()].concat());
+    }
+}
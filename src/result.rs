@@ -0,0 +1,69 @@
+use crate::error::exit::*;
+use crate::opcode::enviroment::Log;
+
+/// 交易执行结果
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExecutionResult {
+    /// 执行成功，gas_used为扣除退款之后实际消耗的gas
+    Success { output: Vec<u8>, gas_used: u64, logs: Vec<Log> },
+    /// 执行REVERT，状态修改全部撤销，未使用的gas退还
+    Revert { output: Vec<u8>, gas_used: u64 },
+    /// 异常中止，状态修改全部撤销并消耗全部gas
+    Halt { reason: OpcodeExecutionError, gas_used: u64 },
+}
+
+impl ExecutionResult {
+    /// 根据执行过程中出现的错误构造Halt，非字节码执行错误统一归为Other
+    pub fn halt(error: Box<dyn ExitError>, gas_used: u64) -> Self {
+        let reason = error
+            .as_opcode_error()
+            .unwrap_or_else(|| OpcodeExecutionError::Other(error.to_string()));
+        ExecutionResult::Halt { reason, gas_used }
+    }
+
+    pub fn is_success(&self) -> bool {
+        matches!(self, ExecutionResult::Success { .. })
+    }
+
+    pub fn is_halt(&self) -> bool {
+        matches!(self, ExecutionResult::Halt { .. })
+    }
+
+    /// 成功或revert时返回的数据，异常中止时为None
+    pub fn output(&self) -> Option<&[u8]> {
+        match self {
+            ExecutionResult::Success { output, .. } | ExecutionResult::Revert { output, .. } => Some(output),
+            ExecutionResult::Halt { .. } => None,
+        }
+    }
+
+    pub fn gas_used(&self) -> u64 {
+        match self {
+            ExecutionResult::Success { gas_used, .. }
+            | ExecutionResult::Revert { gas_used, .. }
+            | ExecutionResult::Halt { gas_used, .. } => *gas_used,
+        }
+    }
+
+    /// 执行成功时产生的日志，revert或异常中止的交易不产生日志
+    pub fn logs(&self) -> &[Log] {
+        match self {
+            ExecutionResult::Success { logs, .. } => logs,
+            _ => &[],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_halt_reason() {
+        let result = ExecutionResult::halt(Box::new(StackError::StackUnderflow), 100);
+        assert_eq!(result, ExecutionResult::Halt { reason: OpcodeExecutionError::StackUnderflow, gas_used: 100 });
+        let result = ExecutionResult::halt(Box::new(EVMError::DeployContractFailed), 100);
+        assert_eq!(result, ExecutionResult::Halt { reason: OpcodeExecutionError::Other("Deploy contract failed".into()), gas_used: 100 });
+        assert!(result.output().is_none());
+    }
+}
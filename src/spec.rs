@@ -0,0 +1,129 @@
+/// 以太坊硬分叉，决定EVM可用的操作码、gas费用以及执行规则
+///
+/// Constantinople在主网上与Petersburg同时激活，因此这里不单独实现EIP-1283
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SpecId {
+    Frontier,
+    Homestead,
+    TangerineWhistle,
+    SpuriousDragon,
+    Byzantium,
+    Constantinople,
+    Petersburg,
+    Istanbul,
+    Berlin,
+    London,
+    Merge,
+    Shanghai,
+    #[default]
+    Cancun,
+    Prague,
+}
+
+/// 主网按区块高度激活的硬分叉
+const MAINNET_BLOCK_FORKS: [(u64, SpecId); 10] = [
+    (15_537_394, SpecId::Merge),
+    (12_965_000, SpecId::London),
+    (12_244_000, SpecId::Berlin),
+    (9_069_000, SpecId::Istanbul),
+    (7_280_000, SpecId::Petersburg),
+    (4_370_000, SpecId::Byzantium),
+    (2_675_000, SpecId::SpuriousDragon),
+    (2_463_000, SpecId::TangerineWhistle),
+    (1_150_000, SpecId::Homestead),
+    (0, SpecId::Frontier),
+];
+
+/// 主网按区块时间戳激活的硬分叉
+const MAINNET_TIMESTAMP_FORKS: [(u64, SpecId); 3] = [
+    (1_746_612_311, SpecId::Prague),
+    (1_710_338_135, SpecId::Cancun),
+    (1_681_338_455, SpecId::Shanghai),
+];
+
+impl SpecId {
+    /// 当前硬分叉是否已经包含了指定硬分叉引入的规则
+    pub fn is_enabled_in(self, other: SpecId) -> bool {
+        self >= other
+    }
+
+    /// 根据主网区块高度以及时间戳获取该区块所使用的硬分叉
+    pub fn from_mainnet_block(number: u64, timestamp: u64) -> SpecId {
+        if let Some((_, spec)) = MAINNET_TIMESTAMP_FORKS.iter().find(|(activation, _)| timestamp >= *activation) {
+            // 基于时间戳的硬分叉都在Merge之后
+            if number >= 15_537_394 {
+                return *spec;
+            }
+        }
+        MAINNET_BLOCK_FORKS
+            .iter()
+            .find(|(activation, _)| number >= *activation)
+            .map(|(_, spec)| *spec)
+            .unwrap_or(SpecId::Frontier)
+    }
+
+    /// EIP-170: 合约runtime code的长度上限
+    pub fn max_code_size(self) -> Option<usize> {
+        if self.is_enabled_in(SpecId::SpuriousDragon) {
+            Some(0x6000)
+        } else {
+            None
+        }
+    }
+
+    /// EIP-3860: init code的长度上限
+    pub fn max_initcode_size(self) -> Option<usize> {
+        if self.is_enabled_in(SpecId::Shanghai) {
+            Some(2 * 0x6000)
+        } else {
+            None
+        }
+    }
+
+    /// EIP-4844: 每个区块最多携带的blob数量，EIP-7691在Prague中将其从6调整为9
+    pub fn max_blobs_per_block(self) -> usize {
+        if self.is_enabled_in(SpecId::Prague) {
+            9
+        } else {
+            6
+        }
+    }
+
+    /// 交易结束时退款上限为已消耗gas的1/quotient，EIP-3529将其从2调整为5
+    pub fn max_refund_quotient(self) -> u64 {
+        if self.is_enabled_in(SpecId::London) {
+            5
+        } else {
+            2
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_mainnet_block() {
+        assert_eq!(SpecId::from_mainnet_block(0, 0), SpecId::Frontier);
+        assert_eq!(SpecId::from_mainnet_block(4_369_999, 0), SpecId::SpuriousDragon);
+        assert_eq!(SpecId::from_mainnet_block(4_370_000, 0), SpecId::Byzantium);
+        assert_eq!(SpecId::from_mainnet_block(12_965_000, 1_628_166_822), SpecId::London);
+        assert_eq!(SpecId::from_mainnet_block(15_537_394, 1_663_224_162), SpecId::Merge);
+        assert_eq!(SpecId::from_mainnet_block(17_034_870, 1_681_338_455), SpecId::Shanghai);
+        assert_eq!(SpecId::from_mainnet_block(19_426_587, 1_710_338_135), SpecId::Cancun);
+        assert_eq!(SpecId::from_mainnet_block(22_431_084, 1_746_612_311), SpecId::Prague);
+    }
+
+    #[test]
+    fn test_spec_rules() {
+        assert!(SpecId::Cancun.is_enabled_in(SpecId::Berlin));
+        assert!(!SpecId::Istanbul.is_enabled_in(SpecId::Berlin));
+        assert_eq!(SpecId::Homestead.max_code_size(), None);
+        assert_eq!(SpecId::Byzantium.max_code_size(), Some(24576));
+        assert_eq!(SpecId::London.max_initcode_size(), None);
+        assert_eq!(SpecId::Shanghai.max_initcode_size(), Some(49152));
+        assert_eq!(SpecId::Berlin.max_refund_quotient(), 2);
+        assert_eq!(SpecId::London.max_refund_quotient(), 5);
+    }
+}
@@ -1,8 +1,7 @@
 use primitive_types::{H160, H256, U256};
 use std::sync::Arc;
-use ethers::providers::Middleware;
+use ethers::providers::{Middleware, ProviderError};
 use std::collections::{BTreeMap, HashMap};
-use std::process;
 use std::str::FromStr;
 use ethers::types::{Address, Bytes};
 use crate::tracer::getTransaction::StateTracerType;
@@ -54,7 +53,7 @@ pub async fn get_accounts_state_tx(
     provider: Arc<Provider<Http>>,
     tx_hash: H256,
     is_diff: ISDiff,
-) -> BTreeMap<H160, AccountStateEx> {
+) -> Result<BTreeMap<H160, AccountStateEx>, ProviderError> {
     let tracer_config = GethDebugTracerConfig::BuiltInTracer(
         GethDebugBuiltInTracerConfig::PreStateTracer(PreStateConfig {
             diff_mode: Some(is_diff.is_diff),
@@ -67,41 +66,78 @@ pub async fn get_accounts_state_tx(
 
     let tracer_info = provider
         .debug_trace_transaction(tx_hash, options)
-        .await
-        .unwrap_or_else(|err| {
-            eprintln!("transaction reverted with err: {}", err);
-            process::exit(1);
-        });
+        .await?;
     // println!("PreStatetracer difference：{:?}",tracer_info);
-    let mut tx_account_state_ex: BTreeMap<Address, AccountStateEx> = BTreeMap::new();
-
-    match tracer_info {
+    let tx_account_state_ex = match tracer_info {
         ethers::types::GethTrace::Known(ref geth_tracer_frame) => match geth_tracer_frame {
-            ethers::types::GethTraceFrame::PreStateTracer(pre_state_frame ) => match pre_state_frame {
-                PreStateFrame::Default(default_mode ) => {
-                    let true_off_pre_state = &default_mode.0;
-                    tx_account_state_ex = insert_tx_account_state_ex(tx_account_state_ex, true_off_pre_state, is_diff);
-                }
-                PreStateFrame::Diff(diff_on) => {
-                    if is_diff.is_pre.unwrap() == true {
-                        let turn_on_diff_pre_state = &diff_on.pre;
-                        println!("This is diff_on.pre:{:?}",turn_on_diff_pre_state);
-                        tx_account_state_ex = insert_tx_account_state_ex(tx_account_state_ex, turn_on_diff_pre_state, is_diff);
-                    } else {
-                        let turn_on_diff_pre_state = &diff_on.post;
-                        println!("This is diff_on.post:{:?}",turn_on_diff_pre_state);
-                        tx_account_state_ex = insert_tx_account_state_ex(tx_account_state_ex, turn_on_diff_pre_state, is_diff);
-                    }
-                }
-            },
+            ethers::types::GethTraceFrame::PreStateTracer(pre_state_frame ) => accounts_state_from_frame(pre_state_frame, is_diff),
             _ => todo!(),
         },
         _ => todo!(),
     };
-    tx_account_state_ex
+    Ok(tx_account_state_ex)
 }
 
 
+/// 将prestateTracer的返回结果转换为账户状态，diff模式下根据is_pre返回交易前或者交易后的状态
+pub fn accounts_state_from_frame(pre_state_frame: &PreStateFrame, is_diff: ISDiff) -> BTreeMap<Address, AccountStateEx> {
+    let tx_account_state_ex: BTreeMap<Address, AccountStateEx> = BTreeMap::new();
+    match pre_state_frame {
+        PreStateFrame::Default(default_mode ) => {
+            let true_off_pre_state = &default_mode.0;
+            insert_tx_account_state_ex(tx_account_state_ex, true_off_pre_state, is_diff)
+        }
+        PreStateFrame::Diff(diff_on) => {
+            if is_diff.is_pre.unwrap() == true {
+                let turn_on_diff_pre_state = &diff_on.pre;
+                insert_tx_account_state_ex(tx_account_state_ex, turn_on_diff_pre_state, is_diff)
+            } else {
+                // diff模式的post只包含发生变化的字段，需要与pre合并得到完整的post state
+                let turn_on_diff_post_state = merge_diff_post_state(&diff_on.pre, &diff_on.post);
+                insert_tx_account_state_ex(tx_account_state_ex, &turn_on_diff_post_state, is_diff)
+            }
+        }
+    }
+}
+
+/// prestateTracer的diff模式中，pre为发生变化的账户在交易前的状态(存储只包含被修改的存储槽)，
+/// post只包含交易后发生变化的字段，被删除的账户以及被清零的存储槽不会出现在post中
+/// 合并两者得到这些账户在交易后的完整状态，被删除的账户为空账户
+pub fn merge_diff_post_state(
+    pre: &BTreeMap<Address, AccountState>,
+    post: &BTreeMap<Address, AccountState>,
+) -> BTreeMap<Address, AccountState> {
+    pre.keys()
+        .chain(post.keys())
+        .map(|address| {
+            let pre_account = pre.get(address).cloned().unwrap_or_default();
+            // 被修改的存储槽没有出现在post中说明被清零
+            let mut storage: BTreeMap<H256, H256> = pre_account.storage.unwrap_or_default()
+                .into_keys()
+                .map(|slot| (slot, H256::zero()))
+                .collect();
+            let account = match post.get(address) {
+                Some(post_account) => {
+                    storage.extend(post_account.storage.clone().unwrap_or_default());
+                    AccountState {
+                        balance: post_account.balance.or(pre_account.balance),
+                        code: post_account.code.clone().or(pre_account.code),
+                        nonce: post_account.nonce.or(pre_account.nonce),
+                        storage: Some(storage),
+                    }
+                }
+                None => AccountState {
+                    balance: Some(U256::zero()),
+                    code: None,
+                    nonce: Some(U256::zero()),
+                    storage: Some(storage),
+                },
+            };
+            (*address, account)
+        })
+        .collect()
+}
+
 // 将完成的新的账户状态进行插入
 pub fn insert_tx_account_state_ex(
     mut tx_account_state_ex: BTreeMap<Address, AccountStateEx>,
@@ -111,10 +147,9 @@ pub fn insert_tx_account_state_ex(
     new_tx_account_state_ex
         .iter()
         .for_each(|(_addr, _account_state)| {
-            let balance = _account_state.balance.unwrap();
+            let balance = _account_state.balance.unwrap_or_default();
 
             let code: Option<String> = _account_state.clone().code;
-            println!("_account_state.code is {:?}", _account_state.clone().code);
             // 使用 `map` 将 `Option<String>` 转换为 `Option<Bytes>`
             let code_bytes: Option<Bytes> = if code.is_some(){
                 Some(Bytes::from_str(code.unwrap().as_str()).unwrap())
@@ -165,14 +200,128 @@ pub fn insert_tx_account_state_ex(
 
 
 
+#[test]
+fn test_merge_diff_post_state() {
+    let user = Address::repeat_byte(0x01);
+    let contract = Address::repeat_byte(0x02);
+    let destroyed = Address::repeat_byte(0x03);
+    let slot = |n: u64| H256::from_low_u64_be(n);
+    let account = |balance: u64, nonce: Option<u64>, storage: Option<BTreeMap<H256, H256>>| AccountState {
+        balance: Some(U256::from(balance)),
+        code: None,
+        nonce: nonce.map(U256::from),
+        storage,
+    };
+    let pre = BTreeMap::from([
+        (user, account(100, Some(1), None)),
+        (contract, AccountState { code: Some("0x00".to_string()), ..account(0, Some(1), Some(BTreeMap::from([(slot(1), slot(1)), (slot(2), slot(2))]))) }),
+        (destroyed, account(5, Some(1), None)),
+    ]);
+    // 合约只修改了存储，slot 2被清零
+    let post = BTreeMap::from([
+        (user, account(90, Some(2), None)),
+        (contract, AccountState { balance: None, nonce: None, ..account(0, None, Some(BTreeMap::from([(slot(1), slot(7))]))) }),
+    ]);
+    let merged = merge_diff_post_state(&pre, &post);
+    assert_eq!(merged[&user], account(90, Some(2), Some(BTreeMap::new())));
+    assert_eq!(merged[&contract], AccountState {
+        code: Some("0x00".to_string()),
+        ..account(0, Some(1), Some(BTreeMap::from([(slot(1), slot(7)), (slot(2), H256::zero())])))
+    });
+    assert_eq!(merged[&destroyed], account(0, Some(0), Some(BTreeMap::new())));
+}
+
+/// 构造的prestateTracer diff模式返回结果，字段格式与geth一致：
+/// 未变化的字段不出现在post中，被清零的slot与被删除的账户只出现在pre中
+const DIFF_MODE_PRESTATE: &str = r#"{
+    "pre": {
+        "0x1111111111111111111111111111111111111111": {
+            "balance": "0xde0b6b3a7640000",
+            "nonce": 1
+        },
+        "0x2222222222222222222222222222222222222222": {
+            "balance": "0x0",
+            "code": "0x6000",
+            "nonce": 1,
+            "storage": {
+                "0x0000000000000000000000000000000000000000000000000000000000000000": "0x0000000000000000000000000000000000000000000000000000000000000001",
+                "0x0000000000000000000000000000000000000000000000000000000000000001": "0x0000000000000000000000000000000000000000000000000000000000000002"
+            }
+        },
+        "0x3333333333333333333333333333333333333333": {
+            "balance": "0x1"
+        },
+        "0x4444444444444444444444444444444444444444": {
+            "balance": "0x5",
+            "code": "0x00",
+            "nonce": 1
+        }
+    },
+    "post": {
+        "0x1111111111111111111111111111111111111111": {
+            "balance": "0xde0b6b3a7630000",
+            "nonce": 2
+        },
+        "0x2222222222222222222222222222222222222222": {
+            "storage": {
+                "0x0000000000000000000000000000000000000000000000000000000000000000": "0x0000000000000000000000000000000000000000000000000000000000000005"
+            }
+        },
+        "0x3333333333333333333333333333333333333333": {
+            "balance": "0x100"
+        }
+    }
+}"#;
+
+#[test]
+fn test_accounts_state_from_diff_frame() {
+    let diff: ethers::types::DiffMode = ethers::utils::__serde_json::from_str(DIFF_MODE_PRESTATE).unwrap();
+    let frame = PreStateFrame::Diff(diff);
+    let user = Address::repeat_byte(0x11);
+    let contract = Address::repeat_byte(0x22);
+    let coinbase = Address::repeat_byte(0x33);
+    let destroyed = Address::repeat_byte(0x44);
+    let slot = |n: u64| H256::from_low_u64_be(n);
+
+    let pre_state = accounts_state_from_frame(&frame, ISDiff::new(true, Some(true)));
+    assert_eq!(pre_state.len(), 4);
+    assert_eq!(pre_state[&user].balance, U256::from(0xde0b6b3a7640000u64));
+    assert_eq!(pre_state[&user].nonce, 1);
+    assert_eq!(pre_state[&contract].storage.as_ref().unwrap()[&slot(1)], slot(2));
+
+    let post_state = accounts_state_from_frame(&frame, ISDiff::new(true, Some(false)));
+    assert_eq!(post_state.len(), 4);
+    // post中出现的字段覆盖pre
+    assert_eq!(post_state[&user].balance, U256::from(0xde0b6b3a7630000u64));
+    assert_eq!(post_state[&user].nonce, 2);
+    // 只修改了存储的合约，余额、nonce与代码沿用pre，未出现在post中的slot被清零
+    assert_eq!(post_state[&contract].balance, U256::zero());
+    assert_eq!(post_state[&contract].nonce, 1);
+    assert_eq!(post_state[&contract].code, Some(Bytes::from(vec![0x60, 0x00])));
+    assert_eq!(post_state[&contract].code_hash, Some(keccak256([0x60, 0x00])));
+    assert_eq!(
+        post_state[&contract].storage,
+        Some(BTreeMap::from([(slot(0), slot(5)), (slot(1), H256::zero())]))
+    );
+    // pre中缺省的nonce按0处理
+    assert_eq!(post_state[&coinbase].balance, U256::from(0x100));
+    assert_eq!(post_state[&coinbase].nonce, 0);
+    // 只出现在pre中的账户在交易后被删除
+    assert_eq!(post_state[&destroyed].balance, U256::zero());
+    assert_eq!(post_state[&destroyed].nonce, 0);
+    assert_eq!(post_state[&destroyed].code, None);
+}
+
 #[tokio::test]
- pub async fn test_get_accounts_state_tx() {
-    let provider_http_url = String::from("https://lb.nodies.app/v1/181a5ebf4c954f8496ae7cbc1ac8d03b");
+#[ignore = "需要访问以太坊节点"]
+pub async fn test_get_accounts_state_tx() {
+    dotenv::dotenv().ok();
+    let provider_http_url = std::env::var("ethereum").expect("ethereum rpc url not set");
     let provider = Provider::try_connect(provider_http_url.as_str()).await.expect("could not connect");
 
     let attack_hash = "0x3ed75df83d907412af874b7998d911fdf990704da87c2b1a8cf95ca5d21504cf";
 
     let account_state =
         get_accounts_state_tx(Arc::from(provider), H256::from_str(attack_hash).unwrap(), ISDiff::default()).await;
-    println!("{:?}", account_state);
+    assert!(account_state.is_ok());
 }
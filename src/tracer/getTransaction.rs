@@ -2,6 +2,9 @@ use primitive_types::{H160, U256, H256};
 use ethers::prelude::{Http, Provider, TxHash};
 use ethers::providers::{Middleware, ProviderError};
 use ethers::types::{Bytes};
+use ethers::types::transaction::eip2930::AccessList;
+use crate::receipt::Receipt;
+use crate::transaction::Transaction;
 
 
 #[derive(Debug, Clone)]
@@ -36,6 +39,7 @@ pub struct TransactionEnv {
     /// Input data
     pub calldata: Bytes,
 
+    /// 区块的base fee，London之前的区块为None
     pub basefee: Option<U256>,
 
     pub difficulty: U256,
@@ -43,6 +47,18 @@ pub struct TransactionEnv {
     pub prevrandao: Option<U256>,
 
     pub chain_id: Option<U256>,
+
+    /// EIP-2930 access list
+    pub access_list: Option<AccessList>,
+
+    /// EIP-4844 blob交易携带的versioned hashes
+    pub blob_versioned_hashes: Vec<H256>,
+
+    /// EIP-4844 区块的excess blob gas，Cancun之前的区块为None
+    pub excess_blob_gas: Option<U256>,
+
+    /// EIP-2718 类型交易，包含各类型交易特有的gas价格、blob以及授权列表等字段
+    pub transaction: Transaction,
 }
 
 #[derive(Debug, Clone)]
@@ -96,15 +112,6 @@ pub async fn get_transaction_content(
     let mut gas = [0u8; 32];
     transaction.clone().unwrap().gas.to_big_endian(&mut gas);
 
-    // get transaction basefee
-    let mut basefee = [0u8; 32];
-    transaction
-        .clone()
-        .unwrap()
-        .max_fee_per_gas
-        .unwrap()
-        .to_big_endian(&mut basefee);
-
     // get transaction difficulty
     let mut difficulty = [0u8; 32];
     block_info
@@ -134,9 +141,28 @@ pub async fn get_transaction_content(
         gas_price: transaction.clone().unwrap().gas_price,
         gas: transaction.clone().unwrap().gas,
         calldata: Bytes::from(calldata),
-        basefee: transaction.clone().unwrap().max_fee_per_gas,
+        basefee: block_info.clone().unwrap().base_fee_per_gas,
         difficulty: block_info.clone().unwrap().difficulty,
         prevrandao: None,
         chain_id: transaction.clone().unwrap().chain_id,
+        access_list: transaction.clone().unwrap().access_list,
+        blob_versioned_hashes: transaction
+            .clone()
+            .unwrap()
+            .other
+            .get_deserialized::<Vec<H256>>("blobVersionedHashes")
+            .and_then(|hashes| hashes.ok())
+            .unwrap_or_default(),
+        excess_blob_gas: block_info.clone().unwrap().excess_blob_gas,
+        transaction: Transaction::from(transaction.as_ref().unwrap()),
     })
 }
+
+/// 获取交易在链上的收据，用于与本地执行生成的收据进行比较
+pub async fn get_transaction_receipt(
+    provider: Provider<Http>,
+    tx_hash: TxHash,
+) -> Result<Option<Receipt>, ProviderError> {
+    let receipt = provider.get_transaction_receipt(tx_hash).await?;
+    Ok(receipt.as_ref().map(Receipt::from))
+}
@@ -0,0 +1,1064 @@
+use ethers::types::{Bytes, Transaction as RpcTransaction, U64};
+use ethers::types::transaction::eip2930::AccessList;
+use ethers::utils::keccak256;
+use ethers::utils::rlp::{DecoderError, Rlp, RlpStream};
+use primitive_types::{H160, H256, U256};
+use serde::Deserialize;
+use crate::error::exit::InvalidTransaction;
+use crate::machine::Gas::GAS_PER_BLOB;
+use crate::precompile::ecrecover::recover_signer;
+use crate::spec::SpecId;
+use crate::utils::address_to_h160;
+use revm_primitives::Address;
+
+/// EIP-2718: 交易类型
+pub const LEGACY_TX_TYPE: u8 = 0;
+pub const ACCESS_LIST_TX_TYPE: u8 = 1;
+pub const DYNAMIC_FEE_TX_TYPE: u8 = 2;
+pub const BLOB_TX_TYPE: u8 = 3;
+pub const SET_CODE_TX_TYPE: u8 = 4;
+
+/// EIP-4844: versioned hash的版本号
+pub const VERSIONED_HASH_VERSION_KZG: u8 = 0x01;
+/// EIP-7702: 委托标识的前缀，委托账户的code为 0xef0100 || address
+pub const DELEGATION_PREFIX: [u8; 3] = [0xef, 0x01, 0x00];
+
+static EMPTY_ACCESS_LIST: AccessList = AccessList(Vec::new());
+
+/// legacy交易，EIP-155之前的交易不包含chain id
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LegacyTx {
+    pub chain_id: Option<u64>,
+    pub nonce: u64,
+    pub gas_price: U256,
+    pub gas_limit: u64,
+    /// 交易接收者，为None时为创建合约交易
+    pub to: Option<H160>,
+    pub value: U256,
+    pub data: Bytes,
+}
+
+/// EIP-2930: 携带访问列表的交易
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AccessListTx {
+    pub chain_id: u64,
+    pub nonce: u64,
+    pub gas_price: U256,
+    pub gas_limit: u64,
+    pub to: Option<H160>,
+    pub value: U256,
+    pub data: Bytes,
+    pub access_list: AccessList,
+}
+
+/// EIP-1559: 动态手续费交易
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DynamicFeeTx {
+    pub chain_id: u64,
+    pub nonce: u64,
+    pub max_priority_fee_per_gas: U256,
+    pub max_fee_per_gas: U256,
+    pub gas_limit: u64,
+    pub to: Option<H160>,
+    pub value: U256,
+    pub data: Bytes,
+    pub access_list: AccessList,
+}
+
+/// EIP-4844: 携带blob的交易，不能用于创建合约
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BlobTx {
+    pub chain_id: u64,
+    pub nonce: u64,
+    pub max_priority_fee_per_gas: U256,
+    pub max_fee_per_gas: U256,
+    pub gas_limit: u64,
+    pub to: H160,
+    pub value: U256,
+    pub data: Bytes,
+    pub access_list: AccessList,
+    pub max_fee_per_blob_gas: U256,
+    pub blob_versioned_hashes: Vec<H256>,
+}
+
+/// EIP-7702: 为EOA设置委托code的交易，不能用于创建合约
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SetCodeTx {
+    pub chain_id: u64,
+    pub nonce: u64,
+    pub max_priority_fee_per_gas: U256,
+    pub max_fee_per_gas: U256,
+    pub gas_limit: u64,
+    pub to: H160,
+    pub value: U256,
+    pub data: Bytes,
+    pub access_list: AccessList,
+    pub authorization_list: Vec<Authorization>,
+}
+
+/// EIP-7702: 授权账户将自己的code委托给address，address为0时清除委托
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Authorization {
+    /// 为0时在任意链上有效
+    pub chain_id: U256,
+    pub address: H160,
+    pub nonce: U64,
+    pub y_parity: U64,
+    pub r: U256,
+    pub s: U256,
+}
+
+impl Authorization {
+    /// 授权签名的消息哈希：keccak256(0x05 || rlp([chain_id, address, nonce]))
+    pub fn signature_hash(&self) -> H256 {
+        let mut stream = RlpStream::new_list(3);
+        stream.append(&self.chain_id);
+        stream.append(&self.address);
+        stream.append(&self.nonce);
+        let mut message = vec![0x05];
+        message.extend_from_slice(&stream.out());
+        H256::from(keccak256(message))
+    }
+
+    /// 根据签名恢复授权账户，签名无效时返回None
+    pub fn authority(&self) -> Option<H160> {
+        if self.y_parity > U64::one() {
+            return None;
+        }
+        recover_signer(self.signature_hash(), self.y_parity.as_u64() as u8, self.r, self.s)
+    }
+}
+
+/// EIP-7702: 委托账户的code
+pub fn delegation_code(address: H160) -> Bytes {
+    [DELEGATION_PREFIX.as_slice(), address.as_bytes()].concat().into()
+}
+
+/// EIP-7702: 如果code为委托标识，返回被委托的地址
+pub fn delegation_address(code: &[u8]) -> Option<H160> {
+    if code.len() == 23 && code.starts_with(&DELEGATION_PREFIX) {
+        return Some(H160::from_slice(&code[3..]));
+    }
+    None
+}
+
+/// EIP-2718类型交易，每种类型的交易有各自的gas价格计算方式与校验规则
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Transaction {
+    Legacy(LegacyTx),
+    AccessList(AccessListTx),
+    DynamicFee(DynamicFeeTx),
+    Blob(BlobTx),
+    SetCode(SetCodeTx),
+}
+
+impl Default for Transaction {
+    fn default() -> Self {
+        Transaction::Legacy(LegacyTx::default())
+    }
+}
+
+impl Transaction {
+    pub fn tx_type(&self) -> u8 {
+        match self {
+            Transaction::Legacy(_) => LEGACY_TX_TYPE,
+            Transaction::AccessList(_) => ACCESS_LIST_TX_TYPE,
+            Transaction::DynamicFee(_) => DYNAMIC_FEE_TX_TYPE,
+            Transaction::Blob(_) => BLOB_TX_TYPE,
+            Transaction::SetCode(_) => SET_CODE_TX_TYPE,
+        }
+    }
+
+    pub fn chain_id(&self) -> Option<u64> {
+        match self {
+            Transaction::Legacy(tx) => tx.chain_id,
+            Transaction::AccessList(tx) => Some(tx.chain_id),
+            Transaction::DynamicFee(tx) => Some(tx.chain_id),
+            Transaction::Blob(tx) => Some(tx.chain_id),
+            Transaction::SetCode(tx) => Some(tx.chain_id),
+        }
+    }
+
+    pub fn nonce(&self) -> u64 {
+        match self {
+            Transaction::Legacy(tx) => tx.nonce,
+            Transaction::AccessList(tx) => tx.nonce,
+            Transaction::DynamicFee(tx) => tx.nonce,
+            Transaction::Blob(tx) => tx.nonce,
+            Transaction::SetCode(tx) => tx.nonce,
+        }
+    }
+
+    pub fn gas_limit(&self) -> u64 {
+        match self {
+            Transaction::Legacy(tx) => tx.gas_limit,
+            Transaction::AccessList(tx) => tx.gas_limit,
+            Transaction::DynamicFee(tx) => tx.gas_limit,
+            Transaction::Blob(tx) => tx.gas_limit,
+            Transaction::SetCode(tx) => tx.gas_limit,
+        }
+    }
+
+    /// 交易接收者，为None时为创建合约交易
+    pub fn to(&self) -> Option<H160> {
+        match self {
+            Transaction::Legacy(tx) => tx.to,
+            Transaction::AccessList(tx) => tx.to,
+            Transaction::DynamicFee(tx) => tx.to,
+            Transaction::Blob(tx) => Some(tx.to),
+            Transaction::SetCode(tx) => Some(tx.to),
+        }
+    }
+
+    pub fn value(&self) -> U256 {
+        match self {
+            Transaction::Legacy(tx) => tx.value,
+            Transaction::AccessList(tx) => tx.value,
+            Transaction::DynamicFee(tx) => tx.value,
+            Transaction::Blob(tx) => tx.value,
+            Transaction::SetCode(tx) => tx.value,
+        }
+    }
+
+    pub fn data(&self) -> &Bytes {
+        match self {
+            Transaction::Legacy(tx) => &tx.data,
+            Transaction::AccessList(tx) => &tx.data,
+            Transaction::DynamicFee(tx) => &tx.data,
+            Transaction::Blob(tx) => &tx.data,
+            Transaction::SetCode(tx) => &tx.data,
+        }
+    }
+
+    /// EIP-2930: 访问列表，legacy交易为空
+    pub fn access_list(&self) -> &AccessList {
+        match self {
+            Transaction::Legacy(_) => &EMPTY_ACCESS_LIST,
+            Transaction::AccessList(tx) => &tx.access_list,
+            Transaction::DynamicFee(tx) => &tx.access_list,
+            Transaction::Blob(tx) => &tx.access_list,
+            Transaction::SetCode(tx) => &tx.access_list,
+        }
+    }
+
+    /// 发送者愿意支付的最高gas价格：legacy交易与EIP-2930交易为gas_price，其他交易为max_fee_per_gas
+    pub fn max_fee_per_gas(&self) -> U256 {
+        match self {
+            Transaction::Legacy(tx) => tx.gas_price,
+            Transaction::AccessList(tx) => tx.gas_price,
+            Transaction::DynamicFee(tx) => tx.max_fee_per_gas,
+            Transaction::Blob(tx) => tx.max_fee_per_gas,
+            Transaction::SetCode(tx) => tx.max_fee_per_gas,
+        }
+    }
+
+    /// EIP-1559: 小费上限，EIP-1559之前的交易类型为None
+    pub fn max_priority_fee_per_gas(&self) -> Option<U256> {
+        match self {
+            Transaction::Legacy(_) | Transaction::AccessList(_) => None,
+            Transaction::DynamicFee(tx) => Some(tx.max_priority_fee_per_gas),
+            Transaction::Blob(tx) => Some(tx.max_priority_fee_per_gas),
+            Transaction::SetCode(tx) => Some(tx.max_priority_fee_per_gas),
+        }
+    }
+
+    /// 实际支付的gas价格，EIP-1559之后的交易类型为 min(max_fee_per_gas, basefee + max_priority_fee_per_gas)
+    pub fn effective_gas_price(&self, basefee: U256) -> U256 {
+        match self.max_priority_fee_per_gas() {
+            Some(priority_fee) => self.max_fee_per_gas().min(basefee.saturating_add(priority_fee)),
+            None => self.max_fee_per_gas(),
+        }
+    }
+
+    /// EIP-4844: blob交易携带的versioned hashes
+    pub fn blob_versioned_hashes(&self) -> &[H256] {
+        match self {
+            Transaction::Blob(tx) => &tx.blob_versioned_hashes,
+            _ => &[],
+        }
+    }
+
+    /// EIP-4844: blob gas价格上限，非blob交易为0
+    pub fn max_fee_per_blob_gas(&self) -> U256 {
+        match self {
+            Transaction::Blob(tx) => tx.max_fee_per_blob_gas,
+            _ => U256::zero(),
+        }
+    }
+
+    /// EIP-4844: 交易消耗的blob gas
+    pub fn blob_gas(&self) -> u64 {
+        GAS_PER_BLOB * self.blob_versioned_hashes().len() as u64
+    }
+
+    /// EIP-7702: 授权列表
+    pub fn authorization_list(&self) -> &[Authorization] {
+        match self {
+            Transaction::SetCode(tx) => &tx.authorization_list,
+            _ => &[],
+        }
+    }
+
+    /// 与交易类型相关的校验：当前硬分叉是否支持该交易类型、chain id、gas价格以及blob和授权列表
+    pub fn validate(&self, spec: SpecId, chain_id: Option<u64>, basefee: U256, blob_basefee: U256) -> Result<(), InvalidTransaction> {
+        let activation = match self {
+            Transaction::Legacy(_) => SpecId::Frontier,
+            Transaction::AccessList(_) => SpecId::Berlin,
+            Transaction::DynamicFee(_) => SpecId::London,
+            Transaction::Blob(_) => SpecId::Cancun,
+            Transaction::SetCode(_) => SpecId::Prague,
+        };
+        if !spec.is_enabled_in(activation) {
+            return Err(InvalidTransaction::TxTypeNotSupported);
+        }
+        if let (Some(tx_chain_id), Some(chain_id)) = (self.chain_id(), chain_id) {
+            if tx_chain_id != chain_id {
+                return Err(InvalidTransaction::InvalidChainId);
+            }
+        }
+        // EIP-1559: 小费上限不能超过gas价格上限，London之后gas价格上限不能低于base fee
+        if self.max_priority_fee_per_gas().is_some_and(|priority_fee| priority_fee > self.max_fee_per_gas()) {
+            return Err(InvalidTransaction::PriorityFeeGreaterThanMaxFee);
+        }
+        if spec.is_enabled_in(SpecId::London) && self.max_fee_per_gas() < basefee {
+            return Err(InvalidTransaction::GasPriceLessThanBasefee);
+        }
+        match self {
+            Transaction::Blob(tx) => {
+                if tx.blob_versioned_hashes.is_empty() {
+                    return Err(InvalidTransaction::EmptyBlobs);
+                }
+                if tx.blob_versioned_hashes.iter().any(|hash| hash[0] != VERSIONED_HASH_VERSION_KZG) {
+                    return Err(InvalidTransaction::BlobVersionNotSupported);
+                }
+                let max = spec.max_blobs_per_block();
+                if tx.blob_versioned_hashes.len() > max {
+                    return Err(InvalidTransaction::TooManyBlobs { max, have: tx.blob_versioned_hashes.len() });
+                }
+                if tx.max_fee_per_blob_gas < blob_basefee {
+                    return Err(InvalidTransaction::BlobGasPriceGreaterThanMax);
+                }
+            }
+            Transaction::SetCode(tx) if tx.authorization_list.is_empty() => {
+                return Err(InvalidTransaction::EmptyAuthorizationList);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// 节点返回的交易，EIP-4844与EIP-7702新增的字段从未解析的字段中读取
+impl From<&RpcTransaction> for Transaction {
+    fn from(tx: &RpcTransaction) -> Self {
+        let chain_id = tx.chain_id.map(|chain_id| chain_id.as_u64());
+        let nonce = tx.nonce.as_u64();
+        let gas_limit = tx.gas.as_u64();
+        let value = tx.value;
+        let data = tx.input.clone();
+        let access_list = tx.access_list.clone().unwrap_or_default();
+        let max_priority_fee_per_gas = tx.max_priority_fee_per_gas.unwrap_or_default();
+        let max_fee_per_gas = tx.max_fee_per_gas.unwrap_or_default();
+        match tx.transaction_type.map_or(LEGACY_TX_TYPE, |tx_type| tx_type.as_u64() as u8) {
+            ACCESS_LIST_TX_TYPE => Transaction::AccessList(AccessListTx {
+                chain_id: chain_id.unwrap_or_default(),
+                nonce,
+                gas_price: tx.gas_price.unwrap_or_default(),
+                gas_limit,
+                to: tx.to,
+                value,
+                data,
+                access_list,
+            }),
+            DYNAMIC_FEE_TX_TYPE => Transaction::DynamicFee(DynamicFeeTx {
+                chain_id: chain_id.unwrap_or_default(),
+                nonce,
+                max_priority_fee_per_gas,
+                max_fee_per_gas,
+                gas_limit,
+                to: tx.to,
+                value,
+                data,
+                access_list,
+            }),
+            BLOB_TX_TYPE => Transaction::Blob(BlobTx {
+                chain_id: chain_id.unwrap_or_default(),
+                nonce,
+                max_priority_fee_per_gas,
+                max_fee_per_gas,
+                gas_limit,
+                to: tx.to.unwrap_or_default(),
+                value,
+                data,
+                access_list,
+                max_fee_per_blob_gas: rpc_field(tx, "maxFeePerBlobGas").unwrap_or_default(),
+                blob_versioned_hashes: rpc_field(tx, "blobVersionedHashes").unwrap_or_default(),
+            }),
+            SET_CODE_TX_TYPE => Transaction::SetCode(SetCodeTx {
+                chain_id: chain_id.unwrap_or_default(),
+                nonce,
+                max_priority_fee_per_gas,
+                max_fee_per_gas,
+                gas_limit,
+                to: tx.to.unwrap_or_default(),
+                value,
+                data,
+                access_list,
+                authorization_list: rpc_field(tx, "authorizationList").unwrap_or_default(),
+            }),
+            _ => Transaction::Legacy(LegacyTx {
+                chain_id,
+                nonce,
+                gas_price: tx.gas_price.unwrap_or_default(),
+                gas_limit,
+                to: tx.to,
+                value,
+                data,
+            }),
+        }
+    }
+}
+
+/// 读取ethers没有解析的交易字段
+fn rpc_field<T: serde::de::DeserializeOwned>(tx: &RpcTransaction, key: &str) -> Option<T> {
+    tx.other.get_deserialized::<T>(key).and_then(|value| value.ok())
+}
+
+/// 交易执行环境：交易本身以及交易的发送者
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TxEnv {
+    pub caller: H160,
+    pub transaction: Transaction,
+}
+
+impl From<&RpcTransaction> for TxEnv {
+    fn from(tx: &RpcTransaction) -> Self {
+        Self { caller: tx.from, transaction: Transaction::from(tx) }
+    }
+}
+
+impl TxEnv {
+    /// 创建合约交易所创建的合约地址，由发送者地址与交易的nonce决定
+    pub fn create_address(&self) -> Option<H160> {
+        match self.transaction.to() {
+            Some(_) => None,
+            None => Some(address_to_h160(Address::from_slice(self.caller.as_ref()).create(self.transaction.nonce()))),
+        }
+    }
+
+    /// 解码EIP-2718编码的签名交易(例如交易池中的原始交易)，并根据签名恢复交易的发送者
+    pub fn decode_signed(raw: &[u8]) -> Result<Self, InvalidTransaction> {
+        let signed = decode_signed_transaction(raw).map_err(|e| InvalidTransaction::InvalidRlp(e.to_string()))?;
+        if signed.y_parity > 1 {
+            return Err(InvalidTransaction::InvalidSignature);
+        }
+        let caller = recover_signer(signed.signature_hash, signed.y_parity, signed.r, signed.s)
+            .ok_or(InvalidTransaction::InvalidSignature)?;
+        Ok(Self { caller, transaction: signed.transaction })
+    }
+}
+
+/// 解码后的签名交易，signature_hash为签名的消息哈希
+struct SignedTransaction {
+    transaction: Transaction,
+    signature_hash: H256,
+    y_parity: u8,
+    r: U256,
+    s: U256,
+}
+
+/// legacy交易为 rlp([nonce, gas_price, gas_limit, to, value, data, v, r, s])，
+/// 其他类型交易为 类型字节 || rlp([交易字段..., y_parity, r, s])
+fn decode_signed_transaction(raw: &[u8]) -> Result<SignedTransaction, DecoderError> {
+    let first = *raw.first().ok_or(DecoderError::RlpIsTooShort)?;
+    if first >= 0xc0 {
+        return decode_signed_legacy(&Rlp::new(raw));
+    }
+    // 节点有时将类型交易再编码为一个rlp字符串
+    if first > 0x7f {
+        return decode_signed_transaction(Rlp::new(raw).data()?);
+    }
+    let mut payload = Rlp::new(&raw[1..]);
+    // EIP-4844: 交易池中的blob交易为 rlp([交易字段, blobs, commitments, proofs])
+    if first == BLOB_TX_TYPE && payload.at(0)?.is_list() {
+        payload = payload.at(0)?;
+    }
+    let field_count = match first {
+        ACCESS_LIST_TX_TYPE => 8,
+        DYNAMIC_FEE_TX_TYPE => 9,
+        BLOB_TX_TYPE => 11,
+        SET_CODE_TX_TYPE => 10,
+        _ => return Err(DecoderError::Custom("unsupported transaction type")),
+    };
+    if payload.item_count()? != field_count + 3 {
+        return Err(DecoderError::RlpIncorrectListLen);
+    }
+    let transaction = match first {
+        ACCESS_LIST_TX_TYPE => Transaction::AccessList(AccessListTx {
+            chain_id: payload.val_at(0)?,
+            nonce: payload.val_at(1)?,
+            gas_price: payload.val_at(2)?,
+            gas_limit: payload.val_at(3)?,
+            to: decode_to(&payload.at(4)?)?,
+            value: payload.val_at(5)?,
+            data: payload.val_at::<Vec<u8>>(6)?.into(),
+            access_list: payload.val_at(7)?,
+        }),
+        DYNAMIC_FEE_TX_TYPE => Transaction::DynamicFee(DynamicFeeTx {
+            chain_id: payload.val_at(0)?,
+            nonce: payload.val_at(1)?,
+            max_priority_fee_per_gas: payload.val_at(2)?,
+            max_fee_per_gas: payload.val_at(3)?,
+            gas_limit: payload.val_at(4)?,
+            to: decode_to(&payload.at(5)?)?,
+            value: payload.val_at(6)?,
+            data: payload.val_at::<Vec<u8>>(7)?.into(),
+            access_list: payload.val_at(8)?,
+        }),
+        BLOB_TX_TYPE => Transaction::Blob(BlobTx {
+            chain_id: payload.val_at(0)?,
+            nonce: payload.val_at(1)?,
+            max_priority_fee_per_gas: payload.val_at(2)?,
+            max_fee_per_gas: payload.val_at(3)?,
+            gas_limit: payload.val_at(4)?,
+            to: payload.val_at(5)?,
+            value: payload.val_at(6)?,
+            data: payload.val_at::<Vec<u8>>(7)?.into(),
+            access_list: payload.val_at(8)?,
+            max_fee_per_blob_gas: payload.val_at(9)?,
+            blob_versioned_hashes: payload.list_at(10)?,
+        }),
+        _ => Transaction::SetCode(SetCodeTx {
+            chain_id: payload.val_at(0)?,
+            nonce: payload.val_at(1)?,
+            max_priority_fee_per_gas: payload.val_at(2)?,
+            max_fee_per_gas: payload.val_at(3)?,
+            gas_limit: payload.val_at(4)?,
+            to: payload.val_at(5)?,
+            value: payload.val_at(6)?,
+            data: payload.val_at::<Vec<u8>>(7)?.into(),
+            access_list: payload.val_at(8)?,
+            authorization_list: payload.at(9)?.iter().map(|item| decode_authorization(&item)).collect::<Result<_, _>>()?,
+        }),
+    };
+    // 签名的消息为 类型字节 || rlp([交易字段])
+    let mut message = vec![first];
+    message.extend_from_slice(&reencode_fields(&payload, field_count)?);
+    Ok(SignedTransaction {
+        transaction,
+        signature_hash: H256::from(keccak256(message)),
+        y_parity: payload.val_at(field_count)?,
+        r: payload.val_at(field_count + 1)?,
+        s: payload.val_at(field_count + 2)?,
+    })
+}
+
+/// EIP-155之后v = chain_id * 2 + 35 + y_parity，签名的消息中包含chain id；之前v = 27 + y_parity
+fn decode_signed_legacy(rlp: &Rlp) -> Result<SignedTransaction, DecoderError> {
+    if rlp.item_count()? != 9 {
+        return Err(DecoderError::RlpIncorrectListLen);
+    }
+    let v: u64 = rlp.val_at(6)?;
+    let (chain_id, y_parity) = if v >= 35 {
+        (Some((v - 35) / 2), ((v - 35) % 2) as u8)
+    } else {
+        (None, v.checked_sub(27).ok_or(DecoderError::Custom("invalid v"))? as u8)
+    };
+    let mut stream = RlpStream::new_list(if chain_id.is_some() { 9 } else { 6 });
+    for i in 0..6 {
+        stream.append_raw(rlp.at(i)?.as_raw(), 1);
+    }
+    if let Some(chain_id) = chain_id {
+        stream.append(&chain_id);
+        stream.append(&0u8);
+        stream.append(&0u8);
+    }
+    let transaction = Transaction::Legacy(LegacyTx {
+        chain_id,
+        nonce: rlp.val_at(0)?,
+        gas_price: rlp.val_at(1)?,
+        gas_limit: rlp.val_at(2)?,
+        to: decode_to(&rlp.at(3)?)?,
+        value: rlp.val_at(4)?,
+        data: rlp.val_at::<Vec<u8>>(5)?.into(),
+    });
+    Ok(SignedTransaction {
+        transaction,
+        signature_hash: H256::from(keccak256(stream.out())),
+        y_parity,
+        r: rlp.val_at(7)?,
+        s: rlp.val_at(8)?,
+    })
+}
+
+/// 重新编码列表中的前count项
+fn reencode_fields(rlp: &Rlp, count: usize) -> Result<Vec<u8>, DecoderError> {
+    let mut stream = RlpStream::new_list(count);
+    for i in 0..count {
+        stream.append_raw(rlp.at(i)?.as_raw(), 1);
+    }
+    Ok(stream.out().to_vec())
+}
+
+/// 交易接收者为空字符串时为创建合约交易
+fn decode_to(rlp: &Rlp) -> Result<Option<H160>, DecoderError> {
+    if rlp.is_empty() {
+        return Ok(None);
+    }
+    rlp.as_val().map(Some)
+}
+
+/// EIP-7702: 授权为 rlp([chain_id, address, nonce, y_parity, r, s])
+fn decode_authorization(rlp: &Rlp) -> Result<Authorization, DecoderError> {
+    if rlp.item_count()? != 6 {
+        return Err(DecoderError::RlpIncorrectListLen);
+    }
+    Ok(Authorization {
+        chain_id: rlp.val_at(0)?,
+        address: rlp.val_at(1)?,
+        nonce: rlp.val_at(2)?,
+        y_parity: rlp.val_at(3)?,
+        r: rlp.val_at(4)?,
+        s: rlp.val_at(5)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use k256::ecdsa::SigningKey;
+    use crate::evm::EVM;
+    use crate::globalState::{AccountState, Block, WorldState};
+    use crate::transact;
+    use crate::result::ExecutionResult;
+
+    const CALLER_BALANCE: u64 = 1_000_000_000;
+    const CALLER: H160 = H160([0x01; 20]);
+    const RECIPIENT: H160 = H160([0x02; 20]);
+
+    fn test_block() -> Block {
+        Block {
+            blockhash: H256::zero(),
+            coinbase: H160::repeat_byte(0xcb),
+            timestamp: 0,
+            number: 1,
+            prevrandao: H256::zero(),
+            gas_limit: U256::from(30_000_000),
+            chainid: 1,
+            basefee: 10,
+            excess_blob_gas: Some(0),
+        }
+    }
+
+    /// 发送者余额为CALLER_BALANCE，区块的base fee为10
+    fn setup() -> EVM {
+        let mut world_state = WorldState::default();
+        world_state.new_account(CALLER, AccountState::new_eoa(0, U256::from(CALLER_BALANCE)));
+        let mut evm = EVM::new(world_state);
+        evm.block = Some(test_block());
+        evm
+    }
+
+    fn legacy_tx() -> LegacyTx {
+        LegacyTx {
+            to: Some(RECIPIENT),
+            value: U256::from(1000),
+            gas_limit: 100_000,
+            gas_price: U256::from(15),
+            ..Default::default()
+        }
+    }
+
+    fn dynamic_fee_tx() -> DynamicFeeTx {
+        DynamicFeeTx {
+            chain_id: 1,
+            to: Some(RECIPIENT),
+            value: U256::from(1000),
+            gas_limit: 100_000,
+            max_fee_per_gas: U256::from(20),
+            max_priority_fee_per_gas: U256::from(2),
+            ..Default::default()
+        }
+    }
+
+    fn blob_tx() -> BlobTx {
+        let mut versioned_hash = H256::from_low_u64_be(1);
+        versioned_hash.0[0] = VERSIONED_HASH_VERSION_KZG;
+        BlobTx {
+            chain_id: 1,
+            to: RECIPIENT,
+            gas_limit: 100_000,
+            max_fee_per_gas: U256::from(20),
+            max_priority_fee_per_gas: U256::from(2),
+            max_fee_per_blob_gas: U256::from(1),
+            blob_versioned_hashes: vec![versioned_hash],
+            ..Default::default()
+        }
+    }
+
+    fn env(transaction: Transaction) -> TxEnv {
+        TxEnv { caller: CALLER, transaction }
+    }
+
+    fn invalid(evm: &mut EVM, tx: TxEnv) -> String {
+        let before = evm.world_state.state.clone();
+        let error = transact(evm, tx).unwrap_err();
+        // 校验失败的交易不修改任何状态
+        assert_eq!(evm.world_state.state, before);
+        error.to_string()
+    }
+
+    #[test]
+    fn test_effective_gas_price() {
+        let basefee = U256::from(10);
+        assert_eq!(Transaction::Legacy(legacy_tx()).effective_gas_price(basefee), U256::from(15));
+        let tx = Transaction::AccessList(AccessListTx { gas_price: U256::from(15), ..Default::default() });
+        assert_eq!(tx.effective_gas_price(basefee), U256::from(15));
+        // EIP-1559: min(max_fee, basefee + priority_fee)
+        let tx = Transaction::DynamicFee(dynamic_fee_tx());
+        assert_eq!(tx.effective_gas_price(basefee), U256::from(12));
+        assert_eq!(tx.effective_gas_price(U256::from(19)), U256::from(20));
+        assert_eq!(Transaction::Blob(blob_tx()).effective_gas_price(basefee), U256::from(12));
+    }
+
+    #[test]
+    fn test_validate_tx_type() {
+        let (basefee, blob_basefee) = (U256::from(10), U256::one());
+        let validate = |tx: &Transaction, spec: SpecId| tx.validate(spec, Some(1), basefee, blob_basefee);
+        // 交易类型在引入该类型的硬分叉之前无效
+        let tx = Transaction::AccessList(AccessListTx { chain_id: 1, gas_price: U256::from(15), ..Default::default() });
+        assert_eq!(validate(&tx, SpecId::Istanbul), Err(InvalidTransaction::TxTypeNotSupported));
+        assert_eq!(validate(&tx, SpecId::Berlin), Ok(()));
+        let tx = Transaction::DynamicFee(dynamic_fee_tx());
+        assert_eq!(validate(&tx, SpecId::Berlin), Err(InvalidTransaction::TxTypeNotSupported));
+        assert_eq!(validate(&tx, SpecId::London), Ok(()));
+        assert_eq!(tx.validate(SpecId::London, Some(5), basefee, blob_basefee), Err(InvalidTransaction::InvalidChainId));
+        // EIP-155之前的legacy交易不校验chain id
+        assert_eq!(Transaction::Legacy(legacy_tx()).validate(SpecId::London, Some(5), basefee, blob_basefee), Ok(()));
+
+        let mut tx = blob_tx();
+        assert_eq!(validate(&Transaction::Blob(tx.clone()), SpecId::Shanghai), Err(InvalidTransaction::TxTypeNotSupported));
+        assert_eq!(validate(&Transaction::Blob(tx.clone()), SpecId::Cancun), Ok(()));
+        tx.blob_versioned_hashes = vec![tx.blob_versioned_hashes[0]; 7];
+        assert_eq!(validate(&Transaction::Blob(tx.clone()), SpecId::Cancun), Err(InvalidTransaction::TooManyBlobs { max: 6, have: 7 }));
+        assert_eq!(validate(&Transaction::Blob(tx.clone()), SpecId::Prague), Ok(()));
+        tx.max_fee_per_blob_gas = U256::zero();
+        assert_eq!(validate(&Transaction::Blob(tx.clone()), SpecId::Prague), Err(InvalidTransaction::BlobGasPriceGreaterThanMax));
+        tx.blob_versioned_hashes = vec![H256::zero()];
+        assert_eq!(validate(&Transaction::Blob(tx.clone()), SpecId::Prague), Err(InvalidTransaction::BlobVersionNotSupported));
+        tx.blob_versioned_hashes = Vec::new();
+        assert_eq!(validate(&Transaction::Blob(tx), SpecId::Prague), Err(InvalidTransaction::EmptyBlobs));
+
+        let tx = Transaction::SetCode(SetCodeTx { chain_id: 1, max_fee_per_gas: U256::from(20), ..Default::default() });
+        assert_eq!(validate(&tx, SpecId::Cancun), Err(InvalidTransaction::TxTypeNotSupported));
+        assert_eq!(validate(&tx, SpecId::Prague), Err(InvalidTransaction::EmptyAuthorizationList));
+    }
+
+    #[test]
+    fn test_from_rpc() {
+        let rpc = RpcTransaction {
+            from: CALLER,
+            to: Some(RECIPIENT),
+            nonce: U256::from(3),
+            gas: U256::from(50_000),
+            gas_price: Some(U256::from(12)),
+            max_fee_per_gas: Some(U256::from(20)),
+            max_priority_fee_per_gas: Some(U256::from(2)),
+            transaction_type: Some(U64::from(2)),
+            chain_id: Some(U256::one()),
+            ..Default::default()
+        };
+        let tx = TxEnv::from(&rpc);
+        assert_eq!(tx.caller, CALLER);
+        assert_eq!(tx.transaction, Transaction::DynamicFee(DynamicFeeTx {
+            chain_id: 1,
+            nonce: 3,
+            max_priority_fee_per_gas: U256::from(2),
+            max_fee_per_gas: U256::from(20),
+            gas_limit: 50_000,
+            to: Some(RECIPIENT),
+            ..Default::default()
+        }));
+        // 没有类型的交易为legacy交易
+        let rpc = RpcTransaction { transaction_type: None, chain_id: None, ..rpc };
+        assert_eq!(Transaction::from(&rpc).tx_type(), LEGACY_TX_TYPE);
+        assert_eq!(Transaction::from(&rpc).max_fee_per_gas(), U256::from(12));
+    }
+
+    /// 使用私钥对类型交易签名，返回 类型字节 || rlp([交易字段..., y_parity, r, s])
+    fn sign_typed(key: &SigningKey, tx_type: u8, fields: RlpStream) -> Vec<u8> {
+        let fields = fields.out();
+        let message = [vec![tx_type], fields.to_vec()].concat();
+        let (signature, recovery_id) = key.sign_prehash_recoverable(&keccak256(message)).unwrap();
+        let fields = Rlp::new(&fields);
+        let mut stream = RlpStream::new_list(fields.item_count().unwrap() + 3);
+        for item in fields.iter() {
+            stream.append_raw(item.as_raw(), 1);
+        }
+        stream.append(&recovery_id.to_byte());
+        stream.append(&U256::from_big_endian(&signature.to_bytes()[..32]));
+        stream.append(&U256::from_big_endian(&signature.to_bytes()[32..]));
+        [vec![tx_type], stream.out().to_vec()].concat()
+    }
+
+    #[test]
+    fn test_decode_signed() {
+        use ethers::signers::{LocalWallet, Signer};
+        use ethers::types::{Eip1559TransactionRequest, TransactionRequest};
+        use ethers::types::transaction::eip2718::TypedTransaction;
+        use ethers::utils::rlp::Decodable;
+
+        let wallet = LocalWallet::from_bytes(&[0x42; 32]).unwrap().with_chain_id(1u64);
+        let legacy = TypedTransaction::Legacy(TransactionRequest::new()
+            .to(RECIPIENT).value(1000).gas(21000).gas_price(15).nonce(7).data(vec![1, 2, 3]).chain_id(1));
+        let dynamic_fee = TypedTransaction::Eip1559(Eip1559TransactionRequest::new()
+            .to(RECIPIENT).value(1000).gas(21000).max_fee_per_gas(20).max_priority_fee_per_gas(2).nonce(7).chain_id(1));
+        let create = TypedTransaction::Eip1559(Eip1559TransactionRequest::new()
+            .gas(100_000).max_fee_per_gas(20).nonce(8).data(vec![0x00]).chain_id(1));
+        for tx in [legacy, dynamic_fee, create] {
+            let raw = tx.rlp_signed(&wallet.sign_transaction_sync(&tx).unwrap());
+            let decoded = TxEnv::decode_signed(&raw).unwrap();
+            assert_eq!(decoded.caller, wallet.address());
+            // 与根据节点返回的交易构建的交易环境一致
+            let mut rpc = RpcTransaction::decode(&Rlp::new(&raw)).unwrap();
+            rpc.recover_from_mut().unwrap();
+            assert_eq!(decoded, TxEnv::from(&rpc));
+        }
+
+        let key = SigningKey::from_slice(&[0x42; 32]).unwrap();
+        // EIP-4844: 交易池中的blob交易携带blob、commitment以及proof
+        let tx = blob_tx();
+        let mut fields = RlpStream::new_list(11);
+        fields.append(&tx.chain_id).append(&tx.nonce).append(&tx.max_priority_fee_per_gas).append(&tx.max_fee_per_gas)
+            .append(&tx.gas_limit).append(&tx.to).append(&tx.value).append(&tx.data.to_vec());
+        fields.begin_list(0);
+        fields.append(&tx.max_fee_per_blob_gas).append_list(&tx.blob_versioned_hashes);
+        let raw = sign_typed(&key, BLOB_TX_TYPE, fields);
+        let decoded = TxEnv::decode_signed(&raw).unwrap();
+        assert_eq!(decoded, TxEnv { caller: wallet.address(), transaction: Transaction::Blob(tx.clone()) });
+        let mut network = RlpStream::new_list(4);
+        network.append_raw(&raw[1..], 1);
+        network.append_list::<Vec<u8>, Vec<u8>>(&[vec![0u8; 32]]).append_list::<Vec<u8>, Vec<u8>>(&[]).append_list::<Vec<u8>, Vec<u8>>(&[]);
+        let network = [vec![BLOB_TX_TYPE], network.out().to_vec()].concat();
+        assert_eq!(TxEnv::decode_signed(&network).unwrap(), decoded);
+
+        // EIP-7702
+        let authorization = sign_authorization(&key, Authorization { chain_id: U256::one(), address: RECIPIENT, ..Default::default() });
+        let mut fields = RlpStream::new_list(10);
+        fields.append(&1u64).append(&0u64).append(&2u64).append(&20u64).append(&100_000u64).append(&RECIPIENT).append(&0u64).append(&Vec::<u8>::new());
+        fields.begin_list(0);
+        fields.begin_list(1).begin_list(6)
+            .append(&authorization.chain_id).append(&authorization.address).append(&authorization.nonce)
+            .append(&authorization.y_parity).append(&authorization.r).append(&authorization.s);
+        let mut raw = sign_typed(&key, SET_CODE_TX_TYPE, fields);
+        let decoded = TxEnv::decode_signed(&raw).unwrap();
+        assert_eq!(decoded.caller, wallet.address());
+        assert_eq!(decoded.transaction.authorization_list(), &[authorization]);
+        assert_eq!(decoded.transaction.max_fee_per_gas(), U256::from(20));
+
+        // 无效的编码与签名
+        assert!(matches!(TxEnv::decode_signed(&[0x05, 0xc0]), Err(InvalidTransaction::InvalidRlp(_))));
+        assert!(matches!(TxEnv::decode_signed(&[]), Err(InvalidTransaction::InvalidRlp(_))));
+        let length = raw.len();
+        raw[length - 1] ^= 0xff;
+        assert_ne!(TxEnv::decode_signed(&raw).map(|tx| tx.caller), Ok(wallet.address()));
+    }
+
+    #[test]
+    fn test_transfer_fees() {
+        let mut evm = setup();
+        let result = transact(&mut evm, env(Transaction::Legacy(legacy_tx()))).unwrap();
+        assert!(result.is_success());
+        assert_eq!(result.gas_used(), 21000);
+        // 发送者支付gas_used * gas_price，coinbase只获得扣除base fee之后的小费
+        assert_eq!(evm.world_state.get_balance(CALLER).unwrap(), U256::from(CALLER_BALANCE - 21000 * 15 - 1000));
+        assert_eq!(evm.world_state.get_balance(RECIPIENT).unwrap(), U256::from(1000));
+        assert_eq!(evm.world_state.get_balance(test_block().coinbase).unwrap(), U256::from(21000 * 5));
+        assert_eq!(evm.world_state.get_nonce(CALLER).unwrap(), 1);
+
+        // EIP-1559: 实际gas价格为 min(max_fee, basefee + priority_fee)
+        let mut evm = setup();
+        let mut tx = dynamic_fee_tx();
+        transact(&mut evm, env(Transaction::DynamicFee(tx.clone()))).unwrap();
+        assert_eq!(evm.world_state.get_balance(CALLER).unwrap(), U256::from(CALLER_BALANCE - 21000 * 12 - 1000));
+        assert_eq!(evm.world_state.get_balance(test_block().coinbase).unwrap(), U256::from(21000 * 2));
+        // 同一个EVM可以连续执行多笔交易
+        tx.nonce = 1;
+        assert!(transact(&mut evm, env(Transaction::DynamicFee(tx))).unwrap().is_success());
+
+        // EIP-4844: blob gas按照blob base fee收取，与gas费用分开计算
+        let mut evm = setup();
+        transact(&mut evm, env(Transaction::Blob(blob_tx()))).unwrap();
+        assert_eq!(evm.world_state.get_balance(CALLER).unwrap(), U256::from(CALLER_BALANCE - 21000 * 12 - 131072));
+        assert_eq!(evm.blob_hashes, blob_tx().blob_versioned_hashes);
+    }
+
+    #[test]
+    fn test_gasprice_opcode() {
+        let mut evm = setup();
+        // GASPRICE PUSH1 0 MSTORE PUSH1 32 PUSH1 0 RETURN
+        evm.world_state.new_account(RECIPIENT, AccountState::new_contract(1, U256::zero(), H256::default(), Default::default(), "0x3a60005260206000f3".parse().unwrap()));
+        let result = transact(&mut evm, env(Transaction::DynamicFee(dynamic_fee_tx()))).unwrap();
+        assert_eq!(U256::from_big_endian(result.output().unwrap()), U256::from(12));
+    }
+
+    #[test]
+    fn test_invalid_transaction() {
+        let mut evm = setup();
+        let mut tx = legacy_tx();
+        tx.nonce = 1;
+        assert_eq!(invalid(&mut evm, env(Transaction::Legacy(tx.clone()))), InvalidTransaction::NonceTooHigh { tx: 1, state: 0 }.to_string());
+        evm.world_state.increment_nonce(CALLER).unwrap();
+        evm.world_state.increment_nonce(CALLER).unwrap();
+        assert_eq!(invalid(&mut evm, env(Transaction::Legacy(tx))), InvalidTransaction::NonceTooLow { tx: 1, state: 2 }.to_string());
+
+        let mut evm = setup();
+        let tx = LegacyTx { gas_limit: 20000, ..legacy_tx() };
+        assert_eq!(invalid(&mut evm, env(Transaction::Legacy(tx))), InvalidTransaction::CallGasCostMoreThanGasLimit { intrinsic: 21000, gas_limit: 20000 }.to_string());
+
+        let tx = LegacyTx { value: U256::from(CALLER_BALANCE), ..legacy_tx() };
+        assert_eq!(invalid(&mut evm, env(Transaction::Legacy(tx))), InvalidTransaction::LackOfFundForMaxFee {
+            fee: U256::from(CALLER_BALANCE + 100_000 * 15),
+            balance: U256::from(CALLER_BALANCE),
+        }.to_string());
+
+        let tx = LegacyTx { gas_price: U256::from(9), ..legacy_tx() };
+        assert_eq!(invalid(&mut evm, env(Transaction::Legacy(tx))), InvalidTransaction::GasPriceLessThanBasefee.to_string());
+        let tx = DynamicFeeTx { max_priority_fee_per_gas: U256::from(21), ..dynamic_fee_tx() };
+        assert_eq!(invalid(&mut evm, env(Transaction::DynamicFee(tx))), InvalidTransaction::PriorityFeeGreaterThanMaxFee.to_string());
+        let tx = LegacyTx { gas_limit: 30_000_001, ..legacy_tx() };
+        assert_eq!(invalid(&mut evm, env(Transaction::Legacy(tx))), InvalidTransaction::CallerGasLimitMoreThanBlock.to_string());
+
+        // EIP-3607
+        let mut evm = setup();
+        evm.world_state.insert_code(CALLER, Bytes::from(vec![0x00]));
+        assert_eq!(invalid(&mut evm, env(Transaction::Legacy(legacy_tx()))), InvalidTransaction::RejectCallerWithCode.to_string());
+    }
+
+    #[test]
+    fn test_reverted_transaction() {
+        let mut evm = setup();
+        // PUSH1 0 PUSH1 0 REVERT
+        evm.world_state.new_account(RECIPIENT, AccountState::new_contract(1, U256::zero(), H256::default(), Default::default(), "0x60006000fd".parse().unwrap()));
+        let result = transact(&mut evm, env(Transaction::Legacy(legacy_tx()))).unwrap();
+        assert_eq!(result, ExecutionResult::Revert { output: Vec::new(), gas_used: 21000 + 6 });
+        // 转账被撤销，但nonce增加且gas费用照常支付
+        assert_eq!(evm.world_state.get_balance(RECIPIENT).unwrap(), U256::zero());
+        assert_eq!(evm.world_state.get_balance(CALLER).unwrap(), U256::from(CALLER_BALANCE - 21006 * 15));
+        assert_eq!(evm.world_state.get_nonce(CALLER).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_create_transaction() {
+        let mut evm = setup();
+        // PUSH1 0x2a PUSH1 0 MSTORE PUSH1 1 PUSH1 31 RETURN
+        let tx = LegacyTx { to: None, data: "0x602a6000526001601ff3".parse().unwrap(), ..legacy_tx() };
+        let result = transact(&mut evm, env(Transaction::Legacy(tx.clone()))).unwrap();
+        assert!(result.is_success());
+        // 固有gas 53000 + calldata 4 + 9 * 16 + init code 2，执行18，部署code 200
+        assert_eq!(result.gas_used(), 53000 + 4 + 9 * 16 + 2 + 18 + 200);
+        let address = env(Transaction::Legacy(tx.clone())).create_address().unwrap();
+        assert_eq!(evm.world_state.get_code(address).unwrap(), Bytes::from(vec![0x2a]));
+        assert_eq!(evm.world_state.get_balance(address).unwrap(), U256::from(1000));
+        assert_eq!(evm.world_state.get_nonce(address).unwrap(), 1);
+        assert_eq!(evm.world_state.get_nonce(CALLER).unwrap(), 1);
+    }
+
+    /// 使用私钥对授权签名
+    fn sign_authorization(key: &SigningKey, mut authorization: Authorization) -> Authorization {
+        let (signature, recovery_id) = key.sign_prehash_recoverable(authorization.signature_hash().as_bytes()).unwrap();
+        let signature = signature.to_bytes();
+        authorization.r = U256::from_big_endian(&signature[..32]);
+        authorization.s = U256::from_big_endian(&signature[32..]);
+        authorization.y_parity = U64::from(recovery_id.to_byte());
+        authorization
+    }
+
+    #[test]
+    fn test_set_code_transaction() {
+        let key = SigningKey::from_slice(&[0x42; 32]).unwrap();
+        let public_key = key.verifying_key().to_encoded_point(false);
+        let authority = H160::from_slice(&keccak256(&public_key.as_bytes()[1..])[12..]);
+        let delegate = H160::repeat_byte(0x03);
+        let mut evm = setup();
+        evm.spec_id = SpecId::Prague;
+        evm.world_state.new_account(authority, AccountState::new_eoa(0, U256::zero()));
+        // ADDRESS PUSH1 0 MSTORE PUSH1 32 PUSH1 0 RETURN
+        evm.world_state.new_account(delegate, AccountState::new_contract(1, U256::zero(), H256::default(), Default::default(), "0x3060005260206000f3".parse().unwrap()));
+
+        let authorization = sign_authorization(&key, Authorization { chain_id: U256::one(), address: delegate, ..Default::default() });
+        assert_eq!(authorization.authority(), Some(authority));
+        // 其他链上的授权被跳过
+        let other_chain = sign_authorization(&key, Authorization { chain_id: U256::from(5), address: H160::repeat_byte(0x04), ..Default::default() });
+        let tx = SetCodeTx {
+            chain_id: 1,
+            gas_limit: 100_000,
+            max_fee_per_gas: U256::from(20),
+            to: authority,
+            authorization_list: vec![authorization, other_chain],
+            ..Default::default()
+        };
+        let result = transact(&mut evm, env(Transaction::SetCode(tx))).unwrap();
+        assert!(result.is_success());
+        // 调用授权账户时执行被委托地址的code，但上下文仍为授权账户
+        assert_eq!(&result.output().unwrap()[12..], authority.as_bytes());
+        assert_eq!(evm.world_state.get_code(authority).unwrap(), delegation_code(delegate));
+        assert_eq!(evm.world_state.get_nonce(authority).unwrap(), 1);
+        assert_eq!(delegation_address(&delegation_code(delegate)), Some(delegate));
+        assert_eq!(delegation_address(&[0xef, 0x01, 0x00]), None);
+    }
+
+    #[test]
+    fn test_set_code_transaction_sstore() {
+        let key = SigningKey::from_slice(&[0x42; 32]).unwrap();
+        let delegate = H160::repeat_byte(0x03);
+        let mut evm = setup();
+        evm.spec_id = SpecId::Prague;
+        // PUSH1 1 PUSH1 0 SSTORE STOP
+        evm.world_state.new_account(delegate, AccountState::new_contract(1, U256::zero(), H256::default(), Default::default(), "0x600160005500".parse().unwrap()));
+        let authorization = sign_authorization(&key, Authorization { chain_id: U256::one(), address: delegate, ..Default::default() });
+        let authority = authorization.authority().unwrap();
+        let tx = SetCodeTx {
+            chain_id: 1,
+            gas_limit: 100_000,
+            max_fee_per_gas: U256::from(20),
+            to: authority,
+            authorization_list: vec![authorization],
+            ..Default::default()
+        };
+        let result = transact(&mut evm, env(Transaction::SetCode(tx))).unwrap();
+        assert!(result.is_success());
+        // 被委托的code写入的是授权账户的存储
+        assert_eq!(evm.world_state.get_storage_value(authority, H256::zero()).unwrap(), H256::from_low_u64_be(1));
+        assert_eq!(evm.world_state.get_storage_value(delegate, H256::zero()).unwrap(), H256::zero());
+    }
+
+    #[test]
+    fn test_set_code_transaction_keeps_authorization_refund() {
+        let key = SigningKey::from_slice(&[0x42; 32]).unwrap();
+        let delegate = H160::repeat_byte(0x03);
+        // 被委托的code执行REVERT(0, 0)或INVALID
+        for (code, spent) in [("0x60006000fd", 21000 + 25000 + 6), ("0xfe", 100_000)] {
+            let mut evm = setup();
+            evm.spec_id = SpecId::Prague;
+            evm.world_state.new_account(delegate, AccountState::new_contract(1, U256::zero(), H256::default(), Default::default(), code.parse().unwrap()));
+            let authorization = sign_authorization(&key, Authorization { chain_id: U256::one(), address: delegate, ..Default::default() });
+            let authority = authorization.authority().unwrap();
+            // 授权账户已经存在，退还12500
+            evm.world_state.new_account(authority, AccountState::new_eoa(0, U256::zero()));
+            let tx = SetCodeTx {
+                chain_id: 1,
+                gas_limit: 100_000,
+                max_fee_per_gas: U256::from(20),
+                to: authority,
+                authorization_list: vec![authorization],
+                ..Default::default()
+            };
+            let result = transact(&mut evm, env(Transaction::SetCode(tx))).unwrap();
+            assert!(!result.is_success());
+            // 交易revert或异常中止时授权的退款依然保留，且不超过已消耗gas的1/5
+            assert_eq!(result.gas_used(), spent - (spent / 5).min(12500), "{}", code);
+        }
+    }
+}
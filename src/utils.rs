@@ -1,9 +1,11 @@
 use std::str::FromStr;
+use ethnum::I256;
 use primitive_types::{H160, H256, U256};
 use revm_primitives::Address;
 use crate::error::exit::*;
 use crate::evm::EVM;
 use crate::opcode::opcode::Opcode;
+use crate::spec::SpecId;
 /// Convert [U256] into [H256].
 #[must_use]
 pub fn u256_to_h256(v: U256) -> H256 {
@@ -33,6 +35,18 @@ pub fn u256_to_h160(u: U256) -> H160 {
     H160::from_slice(&bytes[12..32])
 }
 
+/// Convert [U256] into [I256], interpreting the word as two's complement.
+pub fn u256_to_i256(v: U256) -> I256 {
+    let mut bytes = [0u8; 32];
+    v.to_big_endian(&mut bytes);
+    I256::from_be_bytes(bytes)
+}
+
+/// Convert [I256] into [U256] in two's complement.
+pub fn i256_to_u256(v: I256) -> U256 {
+    U256::from_big_endian(&v.to_be_bytes())
+}
+
 /// Convert [U256] to [usize].
 pub fn u256_to_usize(v: U256) -> Result<usize, EVMError> {
     if v > U256::from(usize::MAX) {
@@ -61,6 +75,17 @@ pub fn pad_right(bytes: &[u8]) -> [u8; 32] {
     padded
 }
 
+/// 读取data中从offset开始长度为size的数据，超出data范围的部分补0
+pub fn slice_with_padding(data: &[u8], offset: U256, size: usize) -> Vec<u8> {
+    let mut padded = vec![0u8; size];
+    if offset < U256::from(data.len()) {
+        let offset = offset.as_usize();
+        let len = size.min(data.len() - offset);
+        padded[..len].copy_from_slice(&data[offset..offset + len]);
+    }
+    padded
+}
+
 pub fn vec_to_string(vec: Vec<u8>) -> String {
     String::from_utf8_lossy(vec.as_ref()).parse().unwrap()
 }
@@ -88,6 +113,15 @@ fn test_address_to_h160() {
     println!("{:?}", contract);
 }
 
+#[test]
+fn test_slice_with_padding() {
+    let data = [1u8, 2, 3];
+    assert_eq!(slice_with_padding(&data, U256::from(1), 4), vec![2, 3, 0, 0]);
+    assert_eq!(slice_with_padding(&data, U256::from(3), 2), vec![0, 0]);
+    assert_eq!(slice_with_padding(&data, U256::MAX, 2), vec![0, 0]);
+    assert_eq!(slice_with_padding(&[], U256::zero(), 0), Vec::<u8>::new());
+}
+
 pub fn get1() -> u8{
     1
 }
@@ -97,7 +131,12 @@ fn get2() -> u8{
 }
 
 
-pub fn map_op(op: u8) -> Option<Opcode> {
+/// 将字节映射为当前硬分叉下可用的操作码，尚未引入的操作码返回None
+pub fn map_op(op: u8, spec: SpecId) -> Option<Opcode> {
+    map_op_any(op).filter(|opcode| spec.is_enabled_in(opcode.introduced_in()))
+}
+
+fn map_op_any(op: u8) -> Option<Opcode> {
     match op {
         0x00 => Some(Opcode::STOP),
         0x01 => Some(Opcode::ADD),
@@ -153,6 +192,8 @@ pub fn map_op(op: u8) -> Option<Opcode> {
         0x46 => Some(Opcode::CHAINID),
         0x47 => Some(Opcode::SELFBALANCE),
         0x48 => Some(Opcode::BASEFEE),
+        0x49 => Some(Opcode::BLOBHASH),
+        0x4a => Some(Opcode::BLOBBASEFEE),
 
         0x50 => Some(Opcode::POP),
         0x51 => Some(Opcode::MLOAD),
@@ -166,6 +207,8 @@ pub fn map_op(op: u8) -> Option<Opcode> {
         0x59 => Some(Opcode::MSIZE),
         0x5a => Some(Opcode::GAS),
         0x5b => Some(Opcode::JUMPDEST),
+        0x5c => Some(Opcode::TLOAD),
+        0x5d => Some(Opcode::TSTORE),
         0x5e => Some(Opcode::MCOPY),
         0x5f => Some(Opcode::PUSH0),
         0x60 => Some(Opcode::PUSH1),
@@ -255,11 +298,5 @@ pub fn map_op(op: u8) -> Option<Opcode> {
 }
 
 pub fn increment_nonce(evm :&mut EVM, address: H160) -> Result<(), Box<dyn ExitError>> {
-    let account_state = evm.world_state.state.get_mut(&address);
-    let nonce = match account_state {
-        Some(nonce) => nonce,
-        None => return Err(Box::new(EVMError::AddressNotFound(address)))
-    };
-    nonce.nonce += 1;
-    Ok(())
+    evm.world_state.increment_nonce(address)
 }
\ No newline at end of file